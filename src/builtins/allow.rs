@@ -0,0 +1,117 @@
+use crate::builtins::cd;
+use crate::builtins::registry::CommandInfo;
+use crate::engine::cerfenv;
+use crate::engine::path::normalize_path;
+use crate::engine::state::{ExecutionResult, ShellState};
+use std::path::PathBuf;
+
+pub const COMMAND_INFO_ALLOW: CommandInfo = CommandInfo {
+    name: "dir.allow",
+    description: "Trust a .cerfenv file.",
+    usage: "dir.allow [path]\n\nTrust the .cerfenv file at PATH, or the nearest one in PATH or its parents\n(default: the current directory), and load it.\n\nAn interactive shell sources a trusted .cerfenv when it enters the\ndirectory holding it, and puts back the variables it set on leaving that\ndirectory tree. Trust is given to the file's exact contents: after it is\nedited it must be allowed again. Trust is kept in\n$XDG_CONFIG_HOME/cerf/trust.",
+    run: allow_runner,
+};
+
+pub const COMMAND_INFO_DENY: CommandInfo = CommandInfo {
+    name: "dir.deny",
+    description: "Stop trusting a .cerfenv file.",
+    usage: "dir.deny [path]\n\nRevoke trust in the .cerfenv file at PATH, or the nearest one in PATH or\nits parents (default: the current directory), and unload it. The shell no\nlonger asks about the file until it changes.",
+    run: deny_runner,
+};
+
+pub fn allow_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    set_trust("dir.allow", args, true, state)
+}
+
+pub fn deny_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    set_trust("dir.deny", args, false, state)
+}
+
+fn set_trust(
+    name: &str,
+    args: &[String],
+    allow: bool,
+    state: &mut ShellState,
+) -> (ExecutionResult, i32) {
+    let code = match run(args, allow, state) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("cerf: {}: {}", name, e);
+            1
+        }
+    };
+    (ExecutionResult::KeepRunning, code)
+}
+
+fn run(args: &[String], allow: bool, state: &mut ShellState) -> Result<(), String> {
+    if args.len() > 1 {
+        return Err("too many arguments".to_string());
+    }
+    let store = state
+        .cerfenv_trust_file()
+        .ok_or("no configuration directory for the trust file")?;
+    let file = target(args.first(), state)?;
+    cerfenv::set_trust(&store, &file, allow).map_err(|e| format!("{}: {}", file.display(), e))?;
+
+    // Reload, so that the change takes effect in this shell at once.
+    if state.cerfenv.as_ref().is_some_and(|l| l.file == file) {
+        cerfenv::unload(state);
+    }
+    cerfenv::update(state);
+    Ok(())
+}
+
+/// The `.cerfenv` file that `path` (default: the current directory) refers to.
+fn target(path: Option<&String>, state: &ShellState) -> Result<PathBuf, String> {
+    let cwd = cd::logical_cwd(state).map_err(|e| e.to_string())?;
+    let path = match path {
+        Some(path) => normalize_path(&cwd.join(crate::engine::expand_home(path))),
+        None => cwd,
+    };
+    if path.is_file() {
+        return Ok(path);
+    }
+    cerfenv::find(&path)
+        .ok_or_else(|| format!("no {} found in {}", cerfenv::FILE_NAME, path.display()))
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::state::Variable;
+
+    #[test]
+    fn test_allow_and_deny_record_trust() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(project.join(cerfenv::FILE_NAME), "PROJECT_ENV=on\n").unwrap();
+
+        let mut state = ShellState::new();
+        state.interactive = true;
+        state
+            .set_var(
+                "XDG_CONFIG_HOME",
+                Variable::new_string(dir.path().join("config").display().to_string()),
+            )
+            .unwrap();
+        let arg = vec![dir.path().join("elsewhere").display().to_string()];
+        assert_eq!(allow_runner(&arg, &mut state).1, 1);
+
+        let arg = vec![project.display().to_string()];
+        assert_eq!(allow_runner(&arg, &mut state).1, 0);
+        let store = state.cerfenv_trust_file().unwrap();
+        let file = project.join(cerfenv::FILE_NAME);
+        let hash = cerfenv::hash(&file).unwrap();
+        assert_eq!(
+            cerfenv::trust(&store, &file, &hash),
+            cerfenv::Trust::Allowed
+        );
+
+        assert_eq!(deny_runner(&arg, &mut state).1, 0);
+        assert_eq!(cerfenv::trust(&store, &file, &hash), cerfenv::Trust::Denied);
+        assert_eq!(deny_runner(&["a".into(), "b".into()], &mut state).1, 1);
+    }
+}
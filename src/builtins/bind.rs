@@ -0,0 +1,193 @@
+use crate::builtins::registry::CommandInfo;
+use crate::editor::keymap;
+use crate::engine::state::{BindAction, ExecutionResult, KeyBinding, ShellState};
+
+pub const COMMAND_INFO: CommandInfo = CommandInfo {
+    name: "sys.bind",
+    description: "Bind keys to editor commands or shell commands.",
+    usage: "sys.bind [-lp] [-r keyseq] ['keyseq: function' ...]\nsys.bind -x 'keyseq: shell-command' ...\n\nBind KEYSEQ to an editor FUNCTION such as beginning-of-line, or with -x\nto a shell command. KEYSEQ may be double-quoted, as in readline:\n\n  sys.bind '\"\\C-a\": beginning-of-line'\n  sys.bind -x '\"\\C-g\": git status'\n\nKey sequences: \\C-x is Control-x, \\M-x or \\ex is Alt-x, \\e Escape, \\t Tab,\n\\r Enter, \\e[A \\e[B \\e[C \\e[D the arrow keys, \\e[H Home, \\e[F End and\n\\e[3~ Delete. A sequence of more than one key must start with \\C-x.\n\nA -x command runs with the line in READLINE_LINE and the cursor position\nin READLINE_POINT; the line is then edited again as the command left them.\n\nOptions:\n  -l         List the editor function names.\n  -p         Print the bindings in a reusable format (the default when no\n             binding is given).\n  -r keyseq  Remove the binding for KEYSEQ.\n  -x         Bind the following KEYSEQs to shell commands.\n\nThe editing mode is chosen with `set -o vi` or `set -o emacs`.",
+    run: bind_runner,
+};
+
+pub fn bind_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let mut shell = false;
+    let mut list = false;
+    let mut print = false;
+    let mut remove = Vec::new();
+    let mut i = 0;
+
+    while i < args.len() && args[i].starts_with('-') && args[i].len() > 1 {
+        let arg = &args[i];
+        i += 1;
+        if arg == "--" {
+            break;
+        }
+        for ch in arg[1..].chars() {
+            match ch {
+                'l' => list = true,
+                'p' => print = true,
+                'x' => shell = true,
+                'r' => {
+                    let Some(value) = args.get(i) else {
+                        eprintln!("cerf: sys.bind: -r: option requires an argument");
+                        return (ExecutionResult::KeepRunning, 2);
+                    };
+                    i += 1;
+                    remove.push(value);
+                }
+                _ => {
+                    eprintln!("cerf: sys.bind: -{}: invalid option", ch);
+                    eprintln!("usage: sys.bind [-lpx] [-r keyseq] ['keyseq: function' ...]");
+                    return (ExecutionResult::KeepRunning, 2);
+                }
+            }
+        }
+    }
+
+    let bindings = &args[i..];
+    let mut code = 0;
+
+    if list {
+        for name in keymap::FUNCTIONS {
+            println!("{}", name);
+        }
+    }
+
+    for keys in remove {
+        match keymap::parse_keys(keys) {
+            Ok(parsed) => {
+                let keys = keymap::format_keys(&parsed);
+                state.key_bindings.retain(|b| b.keys != keys);
+            }
+            Err(e) => {
+                eprintln!("cerf: sys.bind: {}: {}", keys, e);
+                code = 1;
+            }
+        }
+    }
+
+    for arg in bindings {
+        match parse_binding(arg, shell) {
+            Ok(binding) => {
+                state.key_bindings.retain(|b| b.keys != binding.keys);
+                state.key_bindings.push(binding);
+            }
+            Err(e) => {
+                eprintln!("cerf: sys.bind: {}", e);
+                code = 1;
+            }
+        }
+    }
+
+    if print || args.is_empty() {
+        for binding in &state.key_bindings {
+            println!("{}", format_binding(binding));
+        }
+    }
+    (ExecutionResult::KeepRunning, code)
+}
+
+/// Parse `"keyseq": value` (the quotes are optional).
+fn parse_binding(arg: &str, shell: bool) -> Result<KeyBinding, String> {
+    let (keys, value) =
+        split_binding(arg).ok_or_else(|| format!("{}: missing `:` after the key sequence", arg))?;
+    let parsed = keymap::parse_keys(keys).map_err(|e| format!("{}: {}", keys, e))?;
+    let value = value.trim();
+
+    let action = if shell {
+        let command = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        BindAction::Shell(command.to_string())
+    } else if keymap::command(value).is_some() {
+        BindAction::Function(value.to_string())
+    } else {
+        return Err(format!("{}: unknown function name", value));
+    };
+    Ok(KeyBinding {
+        keys: keymap::format_keys(&parsed),
+        action,
+    })
+}
+
+/// Split a binding at the `:` after its key sequence.
+fn split_binding(arg: &str) -> Option<(&str, &str)> {
+    let arg = arg.trim_start();
+    let Some(quoted) = arg.strip_prefix('"') else {
+        return arg.split_once(':');
+    };
+    let mut escaped = false;
+    let end = quoted.char_indices().find_map(|(i, c)| {
+        let found = c == '"' && !escaped;
+        escaped = c == '\\' && !escaped;
+        found.then_some(i)
+    })?;
+    let rest = quoted[end + 1..].trim_start().strip_prefix(':')?;
+    Some((&quoted[..end], rest))
+}
+
+/// Format a binding as a `bind` command that recreates it.
+fn format_binding(binding: &KeyBinding) -> String {
+    let spec = match &binding.action {
+        BindAction::Function(name) => format!("\"{}\": {}", binding.keys, name),
+        BindAction::Shell(command) => format!("\"{}\": \"{}\"", binding.keys, command),
+    };
+    let flag = match binding.action {
+        BindAction::Function(_) => "",
+        BindAction::Shell(_) => "-x ",
+    };
+    format!("bind {}'{}'", flag, spec.replace('\'', "'\\''"))
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str], state: &mut ShellState) -> i32 {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        bind_runner(&args, state).1
+    }
+
+    #[test]
+    fn test_bind_and_format() {
+        let mut state = ShellState::new();
+        assert_eq!(run(&["\"\\C-A\": beginning-of-line"], &mut state), 0);
+        assert_eq!(run(&["-x", "\\C-g: git status"], &mut state), 0);
+        assert_eq!(
+            run(&["-x", "\"\\C-g\": \"git status --short\""], &mut state),
+            0
+        );
+
+        assert_eq!(state.key_bindings.len(), 2);
+        assert_eq!(
+            format_binding(&state.key_bindings[0]),
+            "bind '\"\\C-a\": beginning-of-line'"
+        );
+        assert_eq!(
+            state.key_bindings[1].action,
+            BindAction::Shell("git status --short".into())
+        );
+        assert_eq!(
+            format_binding(&state.key_bindings[1]),
+            "bind -x '\"\\C-g\": \"git status --short\"'"
+        );
+
+        assert_eq!(run(&["-r", "\\C-a"], &mut state), 0);
+        assert_eq!(state.key_bindings.len(), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut state = ShellState::new();
+        assert_eq!(run(&["\\C-a: no-such-function"], &mut state), 1);
+        assert_eq!(run(&["\\C-a beginning-of-line"], &mut state), 1);
+        assert_eq!(run(&["\"\\C-q: undo"], &mut state), 1);
+        assert_eq!(run(&["-r"], &mut state), 2);
+        assert_eq!(run(&["-z"], &mut state), 2);
+        assert!(state.key_bindings.is_empty());
+        assert_eq!(split_binding("\"\\\":\": undo"), Some(("\\\":", " undo")));
+    }
+}
@@ -1,24 +1,53 @@
 use crate::builtins::registry::CommandInfo;
-use crate::engine::state::{ExecutionResult, ShellState};
+use crate::engine::cerfenv;
+use crate::engine::hooks::run_hooks;
+use crate::engine::path::{expand_home_tilde, normalize_path};
+use crate::engine::state::{ExecutionResult, HookEvent, ShellState, Variable};
 use std::env;
+use std::io;
+use std::path::{Component, Path, PathBuf};
 
 pub const COMMAND_INFO_CD: CommandInfo = CommandInfo {
     name: "dir.cd",
     description: "Change the shell working directory.",
-    usage: "dir.cd <dir>\n\nChange the current directory to DIR.",
+    usage: "dir.cd [-L | -P] [dir]\n\nChange the current directory to DIR, or to $HOME when no DIR is given.\n`cd -` changes to $OLDPWD and prints it.\n\nA relative DIR that does not start with `.` or `..` is looked up in each\ndirectory of the colon-separated CDPATH; when one is used, the resulting\npath is printed.\n\nOptions:\n  -L  Follow symbolic links logically: `..` removes the last component of\n      $PWD (the default).\n  -P  Use the physical directory structure, resolving symbolic links.\n\nPWD and OLDPWD are updated after a successful change.",
     run: cd_runner,
 };
 
 pub const COMMAND_INFO_PWD: CommandInfo = CommandInfo {
     name: "dir.pwd",
     description: "Print the name of the current working directory.",
-    usage: "dir.pwd\n\nPrint the absolute pathname of the current working directory.",
+    usage: "dir.pwd [-L | -P]\n\nPrint the absolute pathname of the current working directory.\n\nOptions:\n  -L  Print $PWD, which may contain symbolic links (the default).\n  -P  Print the physical directory, with symbolic links resolved.",
     run: pwd_runner,
 };
 
-pub fn pwd_runner(_args: &[String], _state: &mut ShellState) -> (ExecutionResult, i32) {
-    pwd();
-    (ExecutionResult::KeepRunning, 0)
+pub fn pwd_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let physical = match parse_link_options(args) {
+        Ok((physical, [])) => physical,
+        Ok(_) => {
+            eprintln!("cerf: pwd: too many arguments");
+            return (ExecutionResult::KeepRunning, 1);
+        }
+        Err(e) => {
+            eprintln!("cerf: pwd: {}", e);
+            return (ExecutionResult::KeepRunning, 2);
+        }
+    };
+    let cwd = if physical {
+        env::current_dir()
+    } else {
+        logical_cwd(state)
+    };
+    match cwd {
+        Ok(path) => {
+            println!("{}", path.display());
+            (ExecutionResult::KeepRunning, 0)
+        }
+        Err(e) => {
+            eprintln!("cerf: pwd: {}", e);
+            (ExecutionResult::KeepRunning, 1)
+        }
+    }
 }
 
 pub fn cd_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
@@ -31,43 +60,231 @@ pub fn cd_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i
     }
 }
 
+/// Change directory as `cd` does, then load `.cerfenv` files and run the
+/// `chpwd` hooks.
 pub fn run(args: &[String], state: &mut ShellState) -> Result<(), String> {
-    let current = env::current_dir().map_err(|e| e.to_string())?;
+    change_dir(args, state)?;
+    entered_dir(state);
+    Ok(())
+}
 
-    let target = if args.is_empty() {
-        return Err("too few arguments".to_string());
-    } else if args[0] == "-" {
-        state
-            .previous_dir
-            .clone()
-            .ok_or("OLDPWD not set".to_string())?
+/// Load the directory's `.cerfenv` and run the `chpwd` hooks, after a
+/// change of directory.
+pub fn entered_dir(state: &mut ShellState) {
+    cerfenv::update(state);
+    run_hooks(state, HookEvent::Chpwd, &[]);
+}
+
+/// Change directory as `cd` does, without running hooks, for callers
+/// that have more state to update first.
+pub fn change_dir(args: &[String], state: &mut ShellState) -> Result<(), String> {
+    let (physical, operands) = parse_link_options(args)?;
+    if operands.len() > 1 {
+        return Err("too many arguments".to_string());
+    }
+
+    let mut print = false;
+    let dir = match operands.first().map(String::as_str) {
+        None => state
+            .get_var_string("HOME")
+            .filter(|h| !h.is_empty())
+            .ok_or("HOME not set")?,
+        Some("-") => {
+            print = true;
+            state
+                .get_var_string("OLDPWD")
+                .filter(|d| !d.is_empty())
+                .or_else(|| {
+                    let previous = state.previous_dir.as_ref()?;
+                    Some(previous.to_string_lossy().into_owned())
+                })
+                .ok_or("OLDPWD not set")?
+        }
+        Some(dir) => dir.to_string(),
+    };
+
+    let current = logical_cwd(state).map_err(|e| e.to_string())?;
+    let (target, found_in_cdpath) = resolve_target(&dir, &current, state);
+    print |= found_in_cdpath;
+
+    let new_pwd = if physical {
+        env::set_current_dir(&target).map_err(|e| describe(&dir, e))?;
+        env::current_dir().map_err(|e| e.to_string())?
     } else {
-        crate::engine::expand_home(&args[0])
+        let target = normalize_path(&target);
+        env::set_current_dir(&target).map_err(|e| describe(&dir, e))?;
+        target
     };
 
-    if env::set_current_dir(&target).is_err() {
-        // Standard error message
-        return Err(format!("no such file or directory: {}", target.display()));
+    state.previous_dir = Some(current.clone());
+    set_dir_var(state, "OLDPWD", &current);
+    set_dir_var(state, "PWD", &new_pwd);
+    state.sync_dir_stack();
+    state.record_dir_visit(&new_pwd);
+
+    if print {
+        println!("{}", new_pwd.display());
+    }
+    Ok(())
+}
+
+/// Split off the `-L`/`-P` options; the last one given wins. Returns whether
+/// `-P` is in effect and the remaining arguments.
+fn parse_link_options(args: &[String]) -> Result<(bool, &[String]), String> {
+    let mut physical = false;
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with('-') && args[i] != "-" {
+        i += 1;
+        if args[i - 1] == "--" {
+            break;
+        }
+        for ch in args[i - 1][1..].chars() {
+            match ch {
+                'L' => physical = false,
+                'P' => physical = true,
+                _ => return Err(format!("-{}: invalid option", ch)),
+            }
+        }
+    }
+    Ok((physical, &args[i..]))
+}
+
+/// The logical working directory: `$PWD` if it names the current
+/// directory, otherwise the physical one.
+pub fn logical_cwd(state: &ShellState) -> io::Result<PathBuf> {
+    let physical = env::current_dir()?;
+    let pwd = state.get_var_string("PWD").map(PathBuf::from);
+    match pwd {
+        Some(pwd) if pwd.is_absolute() && same_dir(&pwd, &physical) => Ok(pwd),
+        _ => Ok(physical),
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
     }
+}
 
-    state.previous_dir = Some(current);
+/// Work out where `cd dir` goes from `current`, without changing
+/// directory. Returns the path and whether it was found through `CDPATH`.
+fn resolve_target(dir: &str, current: &Path, state: &ShellState) -> (PathBuf, bool) {
+    // Unlike `expand_home` this leaves `..` alone, so that `-P` can
+    // resolve it physically.
+    let path = expand_home_tilde(dir).map_or_else(|| PathBuf::from(dir), PathBuf::from);
+    if path.is_absolute() {
+        return (path, false);
+    }
 
-    // Update PWD
-    if let Ok(new_cwd) = env::current_dir() {
-        let new_pwd = new_cwd.to_string_lossy().to_string();
-        let mut var = crate::engine::state::Variable::new_string(new_pwd.clone());
-        var.exported = true;
-        state.set_var("PWD", var);
-        unsafe {
-            env::set_var("PWD", new_pwd);
+    // POSIX skips the CDPATH search for paths starting with `.` or `..`.
+    let dotted = matches!(
+        path.components().next(),
+        Some(Component::CurDir | Component::ParentDir)
+    );
+    let cdpath = state.get_var_string("CDPATH").filter(|p| !p.is_empty());
+    if let Some(cdpath) = cdpath.filter(|_| !dotted) {
+        for entry in env::split_paths(&cdpath) {
+            // An empty entry means the current directory.
+            let base = if entry.as_os_str().is_empty() {
+                current.to_path_buf()
+            } else {
+                current.join(&entry)
+            };
+            let candidate = base.join(&path);
+            if candidate.is_dir() {
+                return (candidate, !entry.as_os_str().is_empty());
+            }
         }
     }
-    Ok(())
+    (current.join(path), false)
 }
 
-pub fn pwd() {
-    match env::current_dir() {
-        Ok(path) => println!("{}", path.display()),
-        Err(e) => eprintln!("pwd: {}", e),
+/// Set an exported directory variable. Like bash, a readonly `PWD` or
+/// `OLDPWD` is left alone.
+fn set_dir_var(state: &mut ShellState, name: &str, path: &Path) {
+    let mut var = Variable::new_string(path.to_string_lossy().into_owned());
+    var.exported = true;
+    let _ = state.set_var(name, var);
+}
+
+fn describe(dir: &str, e: io::Error) -> String {
+    let reason = match e.kind() {
+        io::ErrorKind::NotFound => "no such file or directory".to_string(),
+        io::ErrorKind::NotADirectory => "not a directory".to_string(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        _ => e.to_string(),
+    };
+    format!("{}: {}", reason, dir)
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_link_options() {
+        let args = strings(&["-P", "-L", "dir"]);
+        assert_eq!(parse_link_options(&args), Ok((false, &args[2..])));
+        let args = strings(&["-LP", "--", "-x"]);
+        assert_eq!(parse_link_options(&args), Ok((true, &args[2..])));
+        let args = strings(&["-"]);
+        assert_eq!(parse_link_options(&args), Ok((false, &args[..])));
+        assert!(parse_link_options(&strings(&["-q"])).is_err());
+    }
+
+    #[test]
+    fn test_resolve_through_cdpath() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        std::fs::create_dir_all(base.join("proj")).unwrap();
+        std::fs::create_dir_all(dir.path().join("here").join("local")).unwrap();
+        let current = dir.path().join("here");
+
+        let mut state = ShellState::new();
+        let cdpath = format!(":{}", base.display());
+        state
+            .set_var("CDPATH", Variable::new_string(cdpath))
+            .unwrap();
+
+        assert_eq!(
+            resolve_target("proj", &current, &state),
+            (base.join("proj"), true)
+        );
+        // The empty entry finds it in the current directory, silently.
+        assert_eq!(
+            resolve_target("local", &current, &state),
+            (current.join("local"), false)
+        );
+        assert_eq!(
+            resolve_target("./proj", &current, &state),
+            (current.join("./proj"), false)
+        );
+        assert_eq!(
+            resolve_target("/tmp", &current, &state),
+            (PathBuf::from("/tmp"), false)
+        );
+    }
+
+    #[test]
+    fn test_expand_tilde_keeps_dotdot() {
+        let home = dirs::home_dir().unwrap();
+        let current = Path::new("/");
+        let state = ShellState::new();
+        assert_eq!(resolve_target("~", current, &state).0, home);
+        assert_eq!(
+            resolve_target("~/a/../b", current, &state).0,
+            home.join("a/../b")
+        );
+        assert_eq!(
+            resolve_target("a/~", current, &state).0,
+            PathBuf::from("/a/~")
+        );
     }
 }
@@ -20,7 +20,7 @@ pub fn command_runner(args: &[String], state: &mut ShellState) -> (ExecutionResu
     }
 
     // external executable
-    let resolved = crate::engine::path::find_executable(name)
+    let resolved = crate::engine::path::find_executable(name, state)
         .unwrap_or_else(|| crate::engine::path::expand_home(name));
 
     #[cfg(windows)]
@@ -43,6 +43,7 @@ pub fn command_runner(args: &[String], state: &mut ShellState) -> (ExecutionResu
 
     // This bypasses proper process job control for Cerf but works as a simple implementation for now.
     command.args(&args[1..]);
+    command.env_clear().envs(state.exported_env());
 
     let code = match command.status() {
         Ok(s) => s.code().unwrap_or(1),
@@ -0,0 +1,169 @@
+use crate::builtins::registry::CommandInfo;
+use crate::engine::state::{CompletionSpec, ExecutionResult, ShellState};
+use crate::parser::quote_word;
+
+pub const COMMAND_INFO_COMPLETE: CommandInfo = CommandInfo {
+    name: "sys.complete",
+    description: "Specify how arguments are to be completed.",
+    usage: "sys.complete [-fd] [-W wordlist] [-X pattern] [-F function] name ...\nsys.complete -p [name ...]\nsys.complete -r [name ...]\n\nRegister a completion specification for each NAME, used when Tab is pressed\nin an argument of that command.\n\nOptions:\n  -W wordlist  Complete words from WORDLIST (split on whitespace, variables\n               expanded when completing).\n  -f           Complete file names.\n  -d           Complete directory names.\n  -X pattern   Remove candidates matching the glob PATTERN. A leading `!`\n               keeps only the matching candidates instead.\n  -F function  Run FUNCTION with COMP_WORDS, COMP_CWORD, COMP_LINE and\n               COMP_POINT set; its candidates are read from COMPREPLY.\n  -p           Print specifications in a reusable format (the default when\n               no NAME is given).\n  -r           Remove the specification for each NAME, or all of them.",
+    run: complete_runner,
+};
+
+pub fn complete_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let mut spec = CompletionSpec::default();
+    let mut print = false;
+    let mut remove = false;
+    let mut i = 0;
+
+    while i < args.len() && args[i].starts_with('-') && args[i].len() > 1 {
+        let arg = &args[i];
+        i += 1;
+        if arg == "--" {
+            break;
+        }
+        for ch in arg[1..].chars() {
+            match ch {
+                'p' => print = true,
+                'r' => remove = true,
+                'f' => spec.files = true,
+                'd' => spec.dirs = true,
+                'W' | 'X' | 'F' => {
+                    let Some(value) = args.get(i) else {
+                        eprintln!("cerf: sys.complete: -{}: option requires an argument", ch);
+                        return (ExecutionResult::KeepRunning, 2);
+                    };
+                    i += 1;
+                    match ch {
+                        'W' => spec.words = Some(value.clone()),
+                        'X' => spec.filter = Some(value.clone()),
+                        _ => spec.function = Some(value.clone()),
+                    }
+                }
+                _ => {
+                    eprintln!("cerf: sys.complete: -{}: invalid option", ch);
+                    eprintln!(
+                        "usage: sys.complete [-fdpr] [-W wordlist] [-X pattern] [-F function] name ..."
+                    );
+                    return (ExecutionResult::KeepRunning, 2);
+                }
+            }
+        }
+    }
+
+    let names = &args[i..];
+
+    if remove {
+        if names.is_empty() {
+            state.completions.clear();
+        }
+        for name in names {
+            state.completions.remove(name);
+        }
+        return (ExecutionResult::KeepRunning, 0);
+    }
+
+    if print || names.is_empty() {
+        let mut code = 0;
+        if names.is_empty() {
+            let mut all: Vec<_> = state.completions.iter().collect();
+            all.sort_by_key(|(name, _)| name.as_str());
+            for (name, spec) in all {
+                println!("{}", format_spec(name, spec));
+            }
+        }
+        for name in names {
+            match state.completions.get(name) {
+                Some(spec) => println!("{}", format_spec(name, spec)),
+                None => {
+                    eprintln!("cerf: sys.complete: {}: no completion specification", name);
+                    code = 1;
+                }
+            }
+        }
+        return (ExecutionResult::KeepRunning, code);
+    }
+
+    for name in names {
+        state.completions.insert(name.clone(), spec.clone());
+    }
+    (ExecutionResult::KeepRunning, 0)
+}
+
+/// Format a spec as a `complete` command that recreates it.
+fn format_spec(name: &str, spec: &CompletionSpec) -> String {
+    let mut out = String::from("complete");
+    if spec.files {
+        out.push_str(" -f");
+    }
+    if spec.dirs {
+        out.push_str(" -d");
+    }
+    if let Some(words) = &spec.words {
+        out.push_str(&format!(" -W {}", quote_word(words)));
+    }
+    if let Some(filter) = &spec.filter {
+        out.push_str(&format!(" -X {}", quote_word(filter)));
+    }
+    if let Some(function) = &spec.function {
+        out.push_str(&format!(" -F {}", function));
+    }
+    out.push(' ');
+    out.push_str(name);
+    out
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str], state: &mut ShellState) -> i32 {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        complete_runner(&args, state).1
+    }
+
+    #[test]
+    fn test_register_and_format() {
+        let mut state = ShellState::new();
+        assert_eq!(
+            run(
+                &["-fW", "build test", "-X", "*.o", "mk", "make"],
+                &mut state
+            ),
+            0
+        );
+
+        let spec = &state.completions["mk"];
+        assert!(spec.files && !spec.dirs);
+        assert_eq!(spec.words.as_deref(), Some("build test"));
+        assert_eq!(state.completions["make"], *spec);
+        assert_eq!(
+            format_spec("mk", spec),
+            "complete -f -W 'build test' -X '*.o' mk"
+        );
+    }
+
+    #[test]
+    fn test_format_quotes_words() {
+        let mut state = ShellState::new();
+        run(&["-W", "it's $HOME", "-X", "[ab]*", "x"], &mut state);
+        let line = format_spec("x", &state.completions["x"]);
+        let cmd = crate::parser::parse_line(&line).unwrap();
+        let args: Vec<&str> = cmd.args().iter().map(|a| a.value.as_str()).collect();
+        assert_eq!(args, ["-W", "it's $HOME", "-X", "[ab]*", "x"]);
+        // Quoted, so the pattern is not matched against files when sourced.
+        assert!(cmd.args()[3].quoted);
+    }
+
+    #[test]
+    fn test_remove_and_errors() {
+        let mut state = ShellState::new();
+        run(&["-F", "_git", "git"], &mut state);
+        assert_eq!(run(&["-p", "git"], &mut state), 0);
+        assert_eq!(run(&["-r", "git"], &mut state), 0);
+        assert_eq!(run(&["-p", "git"], &mut state), 1);
+        assert_eq!(run(&["-W"], &mut state), 2);
+        assert_eq!(run(&["-z", "x"], &mut state), 2);
+    }
+}
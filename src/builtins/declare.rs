@@ -1,111 +1,456 @@
 use crate::builtins::registry::CommandInfo;
 use crate::engine::state::{ExecutionResult, ShellState, VarValue, Variable};
+use crate::parser::{format_function, is_identifier, parse_arg, quote_array, quote_word};
 
 pub const COMMAND_INFO_DECLARE: CommandInfo = CommandInfo {
     name: "env.declare",
     description: "Declare variables and give them attributes.",
-    usage: "env.declare [-aAirxi] [name[=value] ...]\n\nDeclare variables and give them attributes.",
+    usage: "env.declare [-afFgilnprux] [+ailnrux] [name[=value] ...]\n\nDeclare variables and give them attributes. A variable declared inside a\nfunction belongs to the function's scope unless -g is given. With no NAME,\nprint the variables that have the given attributes.\n\nOptions:\n  -a  Make each NAME an array; VALUE is written `(one two ...)`.\n  -f  Print the definitions of the named functions, or of all functions.\n  -F  Print the names of the named functions, or of all functions.\n  -g  Declare global variables, even inside a function.\n  -i  Give each NAME the integer attribute.\n  -l  Lowercase the value on every assignment.\n  -n  Make each NAME a reference to the variable named by its value.\n  -p  Print each NAME, or every variable, as a declare command.\n  -r  Make each NAME readonly.\n  -u  Uppercase the value on every assignment.\n  -x  Export each NAME to the environment of commands.\n\nUsing `+` instead of `-` removes an attribute.",
     run: declare_runner,
 };
 
+/// Variable attributes, as named by `-` or `+` options.
+#[derive(Debug, Default, PartialEq)]
+struct Attributes {
+    array: bool,
+    integer: bool,
+    lowercase: bool,
+    nameref: bool,
+    readonly: bool,
+    uppercase: bool,
+    exported: bool,
+}
+
+impl Attributes {
+    /// Turn on the attribute for option letter `ch`, if it names one.
+    fn add(&mut self, ch: char) -> bool {
+        let flag = match ch {
+            'a' => &mut self.array,
+            'i' => &mut self.integer,
+            'l' => &mut self.lowercase,
+            'n' => &mut self.nameref,
+            'r' => &mut self.readonly,
+            'u' => &mut self.uppercase,
+            'x' => &mut self.exported,
+            _ => return false,
+        };
+        *flag = true;
+        true
+    }
+
+    /// The attributes of `var`.
+    fn of(var: &Variable) -> Self {
+        Self {
+            array: matches!(var.value, VarValue::Array(_)),
+            integer: var.integer,
+            lowercase: var.lowercase,
+            nameref: var.nameref,
+            readonly: var.readonly,
+            uppercase: var.uppercase,
+            exported: var.exported,
+        }
+    }
+
+    /// Whether `other` has every attribute in `self`.
+    fn subset_of(&self, other: &Self) -> bool {
+        (!self.array || other.array)
+            && (!self.integer || other.integer)
+            && (!self.lowercase || other.lowercase)
+            && (!self.nameref || other.nameref)
+            && (!self.readonly || other.readonly)
+            && (!self.uppercase || other.uppercase)
+            && (!self.exported || other.exported)
+    }
+
+    /// The option letters, in `declare -p` order.
+    fn letters(&self) -> String {
+        [
+            (self.array, 'a'),
+            (self.integer, 'i'),
+            (self.lowercase, 'l'),
+            (self.nameref, 'n'),
+            (self.readonly, 'r'),
+            (self.uppercase, 'u'),
+            (self.exported, 'x'),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, ch)| *ch)
+        .collect()
+    }
+}
+
+/// Parsed `declare` options.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    set: Attributes,
+    unset: Attributes,
+    functions: bool,
+    function_names: bool,
+    global: bool,
+    print: bool,
+}
+
 pub fn declare_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
     let mode = run(args, state, false);
     (ExecutionResult::KeepRunning, mode)
 }
 
 pub fn run(args: &[String], state: &mut ShellState, local_scope: bool) -> i32 {
-    let mut i = 0;
-
-    let mut make_array = false;
-    let mut make_integer = false;
-    let mut make_readonly = false;
-    let mut make_export = false;
-
-    while i < args.len() {
-        let arg = &args[i];
-        if arg.starts_with('-') && arg.len() > 1 {
-            for ch in arg[1..].chars() {
-                match ch {
-                    'a' => make_array = true,
-                    'i' => make_integer = true,
-                    'r' => make_readonly = true,
-                    'x' => make_export = true,
-                    _ => {}
-                }
-            }
-            i += 1;
-        } else {
-            break;
+    let command = if local_scope { "local" } else { "declare" };
+    let (options, targets) = match parse_options(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("cerf: {}: {}", command, e);
+            eprintln!(
+                "usage: {} [-afFgilnprux] [+ailnrux] [name[=value] ...]",
+                command
+            );
+            return 2;
         }
-    }
-
-    let targets = &args[i..];
+    };
 
-    if targets.is_empty() {
-        // Just print
-        return 0;
+    if options.functions || options.function_names {
+        return print_functions(state, targets, options.function_names);
+    }
+    if options.print || targets.is_empty() {
+        return print_variables(state, targets, &options.set, command);
     }
 
+    let mut status = 0;
     for target in targets {
-        let mut name = target.as_str();
-        let mut value_str = None;
+        if let Err(e) = declare(target, &options, local_scope, state) {
+            eprintln!("cerf: {}: {}", command, e);
+            status = 1;
+        }
+    }
+    status
+}
 
-        if let Some(eq_pos) = target.find('=') {
-            name = &target[..eq_pos];
-            value_str = Some(&target[eq_pos + 1..]);
+/// Split off the options, returning them and the remaining arguments.
+fn parse_options(args: &[String]) -> Result<(Options, &[String]), String> {
+    let mut options = Options::default();
+    let mut i = 0;
+    while let Some(arg) = args.get(i).filter(|a| a.len() > 1) {
+        let (remove, letters) = match arg.split_at(1) {
+            ("-", "-") => {
+                i += 1;
+                break;
+            }
+            ("-", letters) => (false, letters),
+            ("+", letters) => (true, letters),
+            _ => break,
+        };
+        for ch in letters.chars() {
+            let known = match (remove, ch) {
+                (true, ch) => options.unset.add(ch),
+                (false, 'f') => {
+                    options.functions = true;
+                    true
+                }
+                (false, 'F') => {
+                    options.function_names = true;
+                    true
+                }
+                (false, 'g') => {
+                    options.global = true;
+                    true
+                }
+                (false, 'p') => {
+                    options.print = true;
+                    true
+                }
+                (false, ch) => options.set.add(ch),
+            };
+            if !known {
+                let sign = if remove { '+' } else { '-' };
+                return Err(format!("{}{}: invalid option", sign, ch));
+            }
         }
+        i += 1;
+    }
+    Ok((options, &args[i..]))
+}
+
+/// Declare one `name[=value]` operand.
+fn declare(
+    target: &str,
+    options: &Options,
+    local_scope: bool,
+    state: &mut ShellState,
+) -> Result<(), String> {
+    let (name, value) = match target.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (target, None),
+    };
+    if !is_identifier(name) {
+        return Err(format!("'{}': not a valid identifier", target));
+    }
+    if options.unset.array
+        && state
+            .lookup_var(name)
+            .is_some_and(|v| Attributes::of(v).array)
+    {
+        return Err(format!(
+            "{}: cannot destroy array variables in this way",
+            name
+        ));
+    }
 
-        // If not creating new, inherit old
-        let old_var = state.get_var(name).cloned();
+    // Without -n or +n, a nameref stands for the variable it points to.
+    let name = if options.set.nameref || options.unset.nameref {
+        name.to_string()
+    } else {
+        state.resolve_name(name)
+    };
+    let old_var = state.lookup_var(&name).cloned();
+    let mut var = old_var
+        .clone()
+        .unwrap_or_else(|| Variable::new_string(String::new()));
 
-        let mut var = old_var.unwrap_or_else(|| Variable::new_string(String::new()));
+    let (set, unset) = (&options.set, &options.unset);
+    if set.array && !matches!(var.value, VarValue::Array(_)) {
+        let value = var.value.as_string();
+        var.value = VarValue::Array(if value.is_empty() {
+            Vec::new()
+        } else {
+            vec![value]
+        });
+    }
+    var.integer = (var.integer || set.integer) && !unset.integer;
+    var.nameref = (var.nameref || set.nameref) && !unset.nameref;
+    var.readonly = (var.readonly || set.readonly) && !unset.readonly;
+    var.exported = (var.exported || set.exported) && !unset.exported;
+    // -l and -u exclude each other; the one given wins.
+    var.lowercase = (var.lowercase && !set.uppercase || set.lowercase) && !unset.lowercase;
+    var.uppercase = (var.uppercase && !set.lowercase || set.uppercase) && !unset.uppercase;
 
-        if make_array {
-            var.value = VarValue::Array(Vec::new());
-        }
-        if make_integer {
-            var.integer = true;
+    if let Some(val) = value {
+        if matches!(var.value, VarValue::Array(_)) {
+            let val_trim = val.trim();
+            if val_trim.starts_with('(') && val_trim.ends_with(')') {
+                let inner = &val_trim[1..val_trim.len() - 1];
+                var.value = VarValue::Array(array_elements(inner));
+            } else {
+                var.value = VarValue::Array(vec![val.to_string()]);
+            }
+        } else {
+            var.value = VarValue::String(val.to_string());
         }
-        if make_readonly {
-            var.readonly = true;
+        var.fold_case();
+    }
+
+    if var.nameref {
+        let target = var.value.as_string();
+        if !target.is_empty() && !is_identifier(&target) {
+            return Err(format!(
+                "'{}': invalid variable name for name reference",
+                target
+            ));
         }
-        if make_export {
-            var.exported = true;
+        if !target.is_empty() && state.resolve_name(&target) == name {
+            return Err(format!(
+                "{}: nameref variable self references not allowed",
+                name
+            ));
         }
+    }
 
-        if let Some(val) = value_str {
-            if var.readonly {
-                eprintln!("cerf: declare: {}: readonly variable", name);
-                return 1;
+    // Repeating the attributes of a readonly variable is allowed.
+    if old_var.as_ref() == Some(&var) && !local_scope {
+        return Ok(());
+    }
+    state.declare_var(&name, var, options.global && !local_scope)
+}
+
+/// Print variables as `declare` commands: those in `names`, or every
+/// variable that has the attributes in `filter`.
+fn print_variables(
+    state: &ShellState,
+    names: &[String],
+    filter: &Attributes,
+    command: &str,
+) -> i32 {
+    if names.is_empty() {
+        let mut all: Vec<&String> = std::iter::once(&state.variables)
+            .chain(&state.scopes)
+            .flat_map(|scope| scope.keys())
+            .collect();
+        all.sort();
+        all.dedup();
+        for name in all {
+            if let Some(var) = state.lookup_var(name)
+                && filter.subset_of(&Attributes::of(var))
+            {
+                println!("{}", declaration(name, var));
             }
-            if make_array || matches!(var.value, VarValue::Array(_)) {
-                // very simple array parsing: (1 2 3)
-                let val_trim = val.trim();
-                if val_trim.starts_with('(') && val_trim.ends_with(')') {
-                    let inner = &val_trim[1..val_trim.len() - 1];
-                    let arr_vals: Vec<String> =
-                        inner.split_whitespace().map(|s| s.to_string()).collect();
-                    var.value = VarValue::Array(arr_vals);
-                } else {
-                    var.value = VarValue::Array(vec![val.to_string()]);
-                }
-            } else {
-                var.value = VarValue::String(val.to_string());
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        match state.lookup_var(name) {
+            Some(var) => println!("{}", declaration(name, var)),
+            None => {
+                eprintln!("cerf: {}: {}: not found", command, name);
+                status = 1;
             }
         }
+    }
+    status
+}
+
+/// A `declare` command that recreates `var`.
+pub fn declaration(name: &str, var: &Variable) -> String {
+    let letters = Attributes::of(var).letters();
+    let flags = if letters.is_empty() {
+        "--".to_string()
+    } else {
+        format!("-{}", letters)
+    };
+    let value = match &var.value {
+        VarValue::String(value) => quote_word(value),
+        VarValue::Array(values) => quote_array(values),
+    };
+    format!("declare {} {}={}", flags, name, value)
+}
 
-        if var.exported {
-            unsafe {
-                std::env::set_var(name, var.value.as_string());
+/// The elements of an array value written `(one 'two words' three)`,
+/// which are split like the words of a command.
+fn array_elements(inner: &str) -> Vec<String> {
+    let mut elements = Vec::new();
+    let mut rest = inner.trim_start();
+    while !rest.is_empty() {
+        // The parser needs something between quotes, so `''` is an empty element here.
+        if let Some(after) = rest.strip_prefix("''").or_else(|| rest.strip_prefix("\"\""))
+            && (after.is_empty() || after.starts_with(char::is_whitespace))
+        {
+            elements.push(String::new());
+            rest = after.trim_start();
+            continue;
+        }
+        match parse_arg(rest) {
+            Ok((after, arg)) => {
+                elements.push(arg.value);
+                rest = after.trim_start();
+            }
+            // An operator such as `;` is kept, split on whitespace.
+            Err(_) => {
+                elements.extend(rest.split_whitespace().map(String::from));
+                break;
             }
         }
+    }
+    elements
+}
 
-        if local_scope {
-            state.set_local_var(name, var);
-        } else {
-            state.set_var(name, var);
+/// Print the named functions, or all of them: their definitions, or with
+/// `names_only`, `declare -f name` lines.
+fn print_functions(state: &ShellState, names: &[String], names_only: bool) -> i32 {
+    let mut all: Vec<&String> = state.functions.keys().collect();
+    all.sort();
+    let names: Vec<&String> = if names.is_empty() {
+        all
+    } else {
+        names.iter().collect()
+    };
+
+    let mut status = 0;
+    for name in names {
+        match state.functions.get(name) {
+            Some(_) if names_only => println!("declare -f {}", name),
+            Some(body) => println!("{}", format_function(name, body)),
+            None => status = 1,
         }
     }
+    status
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = strings(&["-gx", "+r", "--", "-a"]);
+        let (options, rest) = parse_options(&args).unwrap();
+        assert!(options.global && options.set.exported && options.unset.readonly);
+        assert_eq!(rest, &args[3..]);
+        assert!(parse_options(&strings(&["-q"])).is_err());
+        assert!(parse_options(&strings(&["+p"])).is_err());
+    }
+
+    #[test]
+    fn test_nameref_and_case() {
+        let mut state = ShellState::new();
+        let declare = |args: &[&str], state: &mut ShellState| run(&strings(args), state, false);
+
+        assert_eq!(declare(&["-u", "DECL_UP=MiXed"], &mut state), 0);
+        assert_eq!(state.get_var_string("DECL_UP").as_deref(), Some("MIXED"));
+        state
+            .set_var("DECL_UP", Variable::new_string("again".into()))
+            .unwrap();
+        assert_eq!(state.get_var_string("DECL_UP").as_deref(), Some("AGAIN"));
+        assert_eq!(declare(&["+u", "-l", "DECL_UP=Lower"], &mut state), 0);
+        assert_eq!(state.get_var_string("DECL_UP").as_deref(), Some("lower"));
+
+        assert_eq!(declare(&["-n", "DECL_REF=DECL_UP"], &mut state), 0);
+        assert_eq!(state.get_var_string("DECL_REF").as_deref(), Some("lower"));
+        state
+            .set_var("DECL_REF", Variable::new_string("Through".into()))
+            .unwrap();
+        assert_eq!(state.get_var_string("DECL_UP").as_deref(), Some("through"));
+        assert_eq!(declare(&["DECL_REF=Declared"], &mut state), 0);
+        assert_eq!(state.get_var_string("DECL_UP").as_deref(), Some("declared"));
+        assert_eq!(
+            declaration("DECL_REF", state.lookup_var("DECL_REF").unwrap()),
+            "declare -n DECL_REF=DECL_UP"
+        );
+
+        assert_eq!(declare(&["-n", "DECL_SELF=DECL_SELF"], &mut state), 1);
+        assert_eq!(declare(&["-n", "DECL_BAD=1x"], &mut state), 1);
+        assert_eq!(declare(&["-r", "+x", "DECL_RO=a b"], &mut state), 0);
+        assert_eq!(declare(&["+r", "DECL_RO"], &mut state), 1);
+        assert_eq!(
+            declaration("DECL_RO", state.lookup_var("DECL_RO").unwrap()),
+            "declare -r DECL_RO='a b'"
+        );
+    }
+
+    #[test]
+    fn test_array_round_trips() {
+        let mut state = ShellState::new();
+        let elements = ["a", "b c", "it's", "", "d"].map(String::from).to_vec();
+        state
+            .set_var("DECL_ARR", Variable::new_array(elements.clone()))
+            .unwrap();
+        let line = declaration("DECL_ARR", state.lookup_var("DECL_ARR").unwrap());
+        state.unset_var("DECL_ARR").unwrap();
 
-    0
+        let line = line.replacen("declare", "env.declare", 1);
+        let entries = crate::parser::parse_pipeline(&line, &state).unwrap();
+        crate::engine::execute_list(entries, &mut state);
+        assert_eq!(
+            state.lookup_var("DECL_ARR").unwrap().value,
+            VarValue::Array(elements)
+        );
+    }
+
+    #[test]
+    fn test_global_from_function_scope() {
+        let mut state = ShellState::new();
+        state.push_scope();
+        run(&strings(&["DECL_LOCAL=1"]), &mut state, false);
+        run(&strings(&["-g", "DECL_GLOBAL=1"]), &mut state, false);
+        state.pop_scope();
+        assert_eq!(state.get_var("DECL_LOCAL"), None);
+        assert_eq!(state.get_var_string("DECL_GLOBAL").as_deref(), Some("1"));
+    }
 }
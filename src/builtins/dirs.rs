@@ -1,13 +1,16 @@
+use crate::builtins::cd;
 use crate::builtins::registry::CommandInfo;
-use crate::engine::state::{ExecutionResult, ShellState};
-use std::env;
+use crate::engine::path::normalize_path;
+use crate::engine::state::{ExecutionResult, ShellState, VarValue, Variable};
+use std::borrow::Cow;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
 // For now, we stub redirects internally as we change the signature to match BuiltinRunner
 pub const COMMAND_INFO_PUSHD: CommandInfo = CommandInfo {
     name: "dir.pushd",
     description: "Add a directory to the directory stack, or rotate the stack.",
-    usage: "dir.pushd [-n] [+N | -N | dir]\n\nAdds a directory to the top of the directory stack, or rotates the stack, making the new top of the stack the current working directory.",
+    usage: "dir.pushd [-n] [+N | -N | dir]\n\nAdds a directory to the top of the directory stack, or rotates the stack, making the new top of the stack the current working directory. With no arguments, exchanges the top two directories.\n\nOptions:\n  -n  Change the stack without changing directory: DIR is added below\n      the top, and +N/-N rotate the entries below the top.\n  +N  Rotate the stack so that the Nth directory, counting from zero at\n      the left of the list shown by `dirs`, is at the top.\n  -N  Like +N, counting from the right.\n\nThe stack is also available as the DIRSTACK array.",
     run: pushd_runner,
 };
 
@@ -24,7 +27,7 @@ pub fn pushd_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult
 pub const COMMAND_INFO_POPD: CommandInfo = CommandInfo {
     name: "dir.popd",
     description: "Remove directories from the directory stack.",
-    usage: "dir.popd [-n] [+N | -N]\n\nRemoves entries from the directory stack.",
+    usage: "dir.popd [-n] [+N | -N]\n\nRemoves entries from the directory stack. With no arguments, removes the top directory and changes to the new top.\n\nOptions:\n  -n  Remove the second directory instead, without changing directory.\n  +N  Remove the Nth directory, counting from zero at the left of the\n      list shown by `dirs`.\n  -N  Like +N, counting from the right.",
     run: popd_runner,
 };
 
@@ -41,13 +44,26 @@ pub fn popd_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult,
 pub const COMMAND_INFO_DIRS: CommandInfo = CommandInfo {
     name: "dir.dirs",
     description: "Display the list of currently remembered directories.",
-    usage: "dir.dirs [-clpv] [+N] [-N]\n\nDisplay the list of currently remembered directories.",
+    usage: "dir.dirs [-clpv] [+N] [-N]\n\nDisplay the list of currently remembered directories, the current directory first. The home directory is shown as `~`.\n\nOptions:\n  -c  Clear the directory stack.\n  -l  Show full paths, without `~`.\n  -p  Print one directory per line.\n  -v  Print one directory per line, with its position in the stack.\n  +N  Show the Nth directory, counting from zero at the left.\n  -N  Show the Nth directory, counting from zero at the right.",
     run: dirs_runner,
 };
 
-pub fn dirs_runner(_args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
-    run_dirs(state, None);
-    (ExecutionResult::KeepRunning, 0)
+pub fn dirs_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    match dirs(args, state, None) {
+        Ok(()) => (ExecutionResult::KeepRunning, 0),
+        Err(e) => {
+            eprintln!("cerf: {}", e);
+            (ExecutionResult::KeepRunning, 1)
+        }
+    }
+}
+
+/// How `dirs` lays out the stack.
+#[derive(Default)]
+struct Format {
+    long: bool,
+    per_line: bool,
+    numbered: bool,
 }
 
 pub fn pushd(
@@ -55,86 +71,320 @@ pub fn pushd(
     state: &mut ShellState,
     stdout_redirect: Option<std::fs::File>,
 ) -> Result<(), String> {
-    let current = env::current_dir().map_err(|e| e.to_string())?;
-
-    if args.is_empty() {
-        if state.dir_stack.is_empty() {
-            return Err("pushd: no other directory".to_string());
-        }
-
-        let top = state.dir_stack.pop().unwrap();
+    let (no_cd, operands) = parse_no_cd(args).map_err(|e| format!("pushd: {}", e))?;
+    if operands.len() > 1 {
+        return Err("pushd: too many arguments".to_string());
+    }
+    let mut stack = full_stack(state)?;
+    let changed = !no_cd;
 
-        if env::set_current_dir(&top).is_err() {
-            state.dir_stack.push(top.clone());
-            return Err(format!(
-                "pushd: no such file or directory: {}",
-                top.display()
-            ));
+    match operands.first() {
+        None => {
+            if stack.len() < 2 {
+                return Err("pushd: no other directory".to_string());
+            }
+            if !no_cd {
+                stack.swap(0, 1);
+                change_to(state, &stack[0]).map_err(|e| format!("pushd: {}", e))?;
+            }
         }
-
-        state.previous_dir = Some(current.clone());
-        state.dir_stack.push(current);
-
-        run_dirs(state, stdout_redirect);
-        return Ok(());
+        Some(arg) => match stack_index(arg, stack.len()) {
+            Some(index) => {
+                let index = index.map_err(|e| format!("pushd: {}", e))?;
+                if no_cd {
+                    stack[1..].rotate_left(index.saturating_sub(1));
+                } else {
+                    stack.rotate_left(index);
+                    change_to(state, &stack[0]).map_err(|e| format!("pushd: {}", e))?;
+                }
+            }
+            None if no_cd => {
+                let dir = normalize_path(&stack[0].join(crate::engine::expand_home(arg)));
+                stack.insert(1, dir);
+            }
+            None => {
+                cd::change_dir(&["--".to_string(), arg.clone()], state)
+                    .map_err(|e| format!("pushd: {}", e))?;
+                let cwd = cd::logical_cwd(state).map_err(|e| e.to_string())?;
+                stack.insert(0, cwd);
+            }
+        },
     }
 
-    let target = crate::engine::expand_home(&args[0]);
-
-    if env::set_current_dir(&target).is_err() {
-        return Err(format!(
-            "pushd: no such file or directory: {}",
-            target.display()
-        ));
+    set_stack(state, &stack);
+    if changed {
+        cd::entered_dir(state);
     }
-
-    state.previous_dir = Some(current.clone());
-    state.dir_stack.push(current);
-
     run_dirs(state, stdout_redirect);
     Ok(())
 }
 
 pub fn popd(
-    _args: &[String],
+    args: &[String],
     state: &mut ShellState,
     stdout_redirect: Option<std::fs::File>,
 ) -> Result<(), String> {
-    if state.dir_stack.is_empty() {
+    let (no_cd, operands) = parse_no_cd(args).map_err(|e| format!("popd: {}", e))?;
+    if operands.len() > 1 {
+        return Err("popd: too many arguments".to_string());
+    }
+    let mut stack = full_stack(state)?;
+    if stack.len() < 2 {
         return Err("popd: directory stack empty".to_string());
     }
 
-    let current = env::current_dir().map_err(|e| e.to_string())?;
-    let target = state.dir_stack.pop().unwrap();
+    let index = match operands.first() {
+        None => 0,
+        Some(arg) => stack_index(arg, stack.len())
+            .ok_or_else(|| format!("popd: {}: invalid argument", arg))?
+            .map_err(|e| format!("popd: {}", e))?,
+    };
+    // `-n` leaves the current directory, so it drops the next entry instead.
+    let index = if no_cd && index == 0 { 1 } else { index };
 
-    if env::set_current_dir(&target).is_err() {
-        state.dir_stack.push(target.clone());
-        return Err(format!(
-            "popd: no such file or directory: {}",
-            target.display()
-        ));
+    let changed = index == 0;
+    if changed {
+        change_to(state, &stack[1]).map_err(|e| format!("popd: {}", e))?;
     }
+    stack.remove(index);
 
-    state.previous_dir = Some(current);
-
+    set_stack(state, &stack);
+    if changed {
+        cd::entered_dir(state);
+    }
     run_dirs(state, stdout_redirect);
     Ok(())
 }
 
-pub fn run_dirs(state: &ShellState, stdout_redirect: Option<std::fs::File>) {
-    if let Ok(current) = env::current_dir() {
-        if let Some(mut f) = stdout_redirect {
-            let _ = write!(f, "{}", current.display());
-            for dir in state.dir_stack.iter().rev() {
-                let _ = write!(f, " {}", dir.display());
+pub fn dirs(
+    args: &[String],
+    state: &mut ShellState,
+    stdout_redirect: Option<std::fs::File>,
+) -> Result<(), String> {
+    let mut format = Format::default();
+    let mut clear = false;
+    let mut index = None;
+
+    for arg in args {
+        if stack_index(arg, 0).is_some() {
+            index = Some(arg);
+            continue;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+            return Err(format!("dirs: {}: invalid argument", arg));
+        };
+        for ch in flags.chars() {
+            match ch {
+                'c' => clear = true,
+                'l' => format.long = true,
+                'p' => format.per_line = true,
+                'v' => {
+                    format.per_line = true;
+                    format.numbered = true;
+                }
+                _ => return Err(format!("dirs: -{}: invalid option", ch)),
             }
-            let _ = writeln!(f);
+        }
+    }
+
+    if clear {
+        state.dir_stack.clear();
+        state.sync_dir_stack();
+        return Ok(());
+    }
+
+    let stack = full_stack(state)?;
+    let home = dirs::home_dir();
+    let home = home.as_deref().filter(|_| !format.long);
+
+    if let Some(arg) = index {
+        let i = stack_index(arg, stack.len())
+            .unwrap_or(Ok(0))
+            .map_err(|e| format!("dirs: {}", e))?;
+        let line = format!("{}\n", display(&stack[i], home));
+        write_out(stdout_redirect, &line);
+        return Ok(());
+    }
+
+    let mut out = String::new();
+    for (i, dir) in stack.iter().enumerate() {
+        if format.numbered {
+            out.push_str(&format!("{:2}  {}\n", i, display(dir, home)));
+        } else if format.per_line {
+            out.push_str(&format!("{}\n", display(dir, home)));
         } else {
-            print!("{}", current.display());
-            for dir in state.dir_stack.iter().rev() {
-                print!(" {}", dir.display());
+            if i > 0 {
+                out.push(' ');
+            }
+            out.push_str(&display(dir, home));
+        }
+    }
+    if !format.per_line {
+        out.push('\n');
+    }
+    write_out(stdout_redirect, &out);
+    Ok(())
+}
+
+/// Print the stack the way plain `dirs` does.
+pub fn run_dirs(state: &mut ShellState, stdout_redirect: Option<std::fs::File>) {
+    if let Err(e) = dirs(&[], state, stdout_redirect) {
+        eprintln!("cerf: {}", e);
+    }
+}
+
+fn write_out(stdout_redirect: Option<std::fs::File>, text: &str) {
+    if let Some(mut f) = stdout_redirect {
+        let _ = f.write_all(text.as_bytes());
+    } else {
+        print!("{}", text);
+    }
+}
+
+/// Split off a leading `-n` (and `--`); `-N` stack indices are operands.
+fn parse_no_cd(args: &[String]) -> Result<(bool, &[String]), String> {
+    let mut no_cd = false;
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        match arg.as_str() {
+            "-n" => no_cd = true,
+            "--" => return Ok((no_cd, &args[i + 1..])),
+            _ if arg.len() > 1 && arg.starts_with('-') && stack_index(arg, 0).is_none() => {
+                return Err(format!("{}: invalid option", arg));
             }
-            println!();
+            _ => break,
+        }
+        i += 1;
+    }
+    Ok((no_cd, &args[i..]))
+}
+
+/// Parse `+N` or `-N` into a position in a stack of `len` entries, `+N`
+/// counting from the left and `-N` from the right. Returns `None` if `arg`
+/// is not of that form.
+fn stack_index(arg: &str, len: usize) -> Option<Result<usize, String>> {
+    let (from_left, digits) = match arg.split_at_checked(1)? {
+        ("+", digits) => (true, digits),
+        ("-", digits) => (false, digits),
+        _ => return None,
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    match digits.parse::<usize>() {
+        Ok(n) if n < len => Some(Ok(if from_left { n } else { len - 1 - n })),
+        _ => Some(Err(format!("{}: directory stack index out of range", arg))),
+    }
+}
+
+/// The whole stack as `dirs` shows it: the current directory, then
+/// `dir_stack` from the top down. Changes made through `DIRSTACK` are
+/// taken over first.
+fn full_stack(state: &mut ShellState) -> Result<Vec<PathBuf>, String> {
+    if let Some(Variable {
+        value: VarValue::Array(dirs),
+        ..
+    }) = state.get_var("DIRSTACK").map(Cow::into_owned)
+    {
+        state.dir_stack = dirs.iter().skip(1).rev().map(PathBuf::from).collect();
+    }
+    let cwd = cd::logical_cwd(state).map_err(|e| e.to_string())?;
+    let mut stack = vec![cwd];
+    stack.extend(state.dir_stack.iter().rev().cloned());
+    Ok(stack)
+}
+
+/// Store `stack` (current directory first) back into the shell state.
+fn set_stack(state: &mut ShellState, stack: &[PathBuf]) {
+    state.dir_stack = stack[1..].iter().rev().cloned().collect();
+    state.sync_dir_stack();
+}
+
+/// Change to `dir`; the caller calls [`cd::entered_dir`] once the stack is
+/// updated.
+fn change_to(state: &mut ShellState, dir: &Path) -> Result<(), String> {
+    cd::change_dir(
+        &["--".to_string(), dir.to_string_lossy().into_owned()],
+        state,
+    )
+}
+
+/// Show `dir` with a leading home directory as `~`.
+fn display(dir: &Path, home: Option<&Path>) -> String {
+    match home.and_then(|h| dir.strip_prefix(h).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => dir.display().to_string(),
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn stack_var(state: &ShellState) -> Vec<String> {
+        match &state.get_var("DIRSTACK").unwrap().value {
+            VarValue::Array(dirs) => dirs[1..].to_vec(),
+            VarValue::String(_) => panic!("DIRSTACK is not an array"),
+        }
+    }
+
+    #[test]
+    fn test_stack_index() {
+        assert_eq!(stack_index("+0", 3), Some(Ok(0)));
+        assert_eq!(stack_index("-0", 3), Some(Ok(2)));
+        assert_eq!(stack_index("+2", 3), Some(Ok(2)));
+        assert!(matches!(stack_index("+3", 3), Some(Err(_))));
+        assert_eq!(stack_index("-n", 3), None);
+        assert_eq!(stack_index("dir", 3), None);
+        assert_eq!(stack_index("+", 3), None);
+    }
+
+    #[test]
+    fn test_no_cd_keeps_dirstack_in_sync() {
+        // `-n` never changes directory, so this is safe alongside other tests.
+        let mut state = ShellState::new();
+        for dir in ["/a", "/b", "/c"] {
+            pushd(&strings(&["-n", dir]), &mut state, None).unwrap();
         }
+        assert_eq!(stack_var(&state), ["/c", "/b", "/a"]);
+        assert_eq!(state.dir_stack.last(), Some(&PathBuf::from("/c")));
+
+        pushd(&strings(&["-n", "+2"]), &mut state, None).unwrap();
+        assert_eq!(stack_var(&state), ["/b", "/a", "/c"]);
+
+        popd(&strings(&["-n"]), &mut state, None).unwrap();
+        assert_eq!(stack_var(&state), ["/a", "/c"]);
+        popd(&strings(&["-0"]), &mut state, None).unwrap();
+        assert_eq!(stack_var(&state), ["/a"]);
+
+        // Editing DIRSTACK edits the stack.
+        state
+            .set_var(
+                "DIRSTACK",
+                Variable::new_array(strings(&["/cwd", "/x", "/y"])),
+            )
+            .unwrap();
+        dirs(&strings(&["-v"]), &mut state, None).unwrap();
+        assert_eq!(state.dir_stack, [PathBuf::from("/y"), PathBuf::from("/x")]);
+        dirs(&strings(&["-c"]), &mut state, None).unwrap();
+        assert!(state.dir_stack.is_empty());
+        assert!(popd(&[], &mut state, None).is_err());
+        assert!(dirs(&strings(&["-q"]), &mut state, None).is_err());
+    }
+
+    #[test]
+    fn test_display_home() {
+        let home = Path::new("/home/ada");
+        assert_eq!(display(Path::new("/home/ada"), Some(home)), "~");
+        assert_eq!(display(Path::new("/home/ada/src"), Some(home)), "~/src");
+        assert_eq!(display(Path::new("/home/adam"), Some(home)), "/home/adam");
+        assert_eq!(display(Path::new("/home/ada/src"), None), "/home/ada/src");
     }
 }
@@ -0,0 +1,309 @@
+use crate::builtins::{cd, registry};
+use crate::engine::path::{find_in_path, normalize_path};
+use crate::engine::state::{ExecutionResult, ShellState, Variable};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+pub const COMMAND_INFO: registry::CommandInfo = registry::CommandInfo {
+    name: "sys.env",
+    description: "Run a command in a modified environment.",
+    usage: "sys.env [-i] [-u name] [-C dir] [--json] [name=value ...] [command [arg ...]]\n\nRun COMMAND with the exported variables, changed as given: each NAME=VALUE\nis set and exported for it. COMMAND may be an external command, a builtin\nor a function; a builtin or function runs in the shell, and its changes to\nvariables are discarded afterwards. Without a COMMAND, print the resulting\nenvironment as NAME=VALUE lines.\n\nOptions:\n  -i, -     Start with an empty environment.\n  -u name   Remove NAME from the environment.\n  -C dir    Run COMMAND in DIR.\n  --json    Print the environment as a JSON object instead of running a\n            command.\n\nThe exit status is that of COMMAND, or 125 if sys.env itself failed, 126\nif COMMAND could not be run and 127 if it was not found.",
+    run: env_runner,
+};
+
+/// Parsed `sys.env` arguments.
+#[derive(Debug, Default, PartialEq)]
+struct Options<'a> {
+    ignore_environment: bool,
+    unset: Vec<&'a str>,
+    dir: Option<&'a str>,
+    json: bool,
+    assignments: Vec<(&'a str, &'a str)>,
+    command: &'a [String],
+}
+
+pub fn env_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("cerf: sys.env: {}", e);
+            eprintln!(
+                "usage: sys.env [-i] [-u name] [-C dir] [--json] [name=value ...] [command [arg ...]]"
+            );
+            return (ExecutionResult::KeepRunning, 125);
+        }
+    };
+
+    let mut env = if options.ignore_environment {
+        BTreeMap::new()
+    } else {
+        state.exported_env()
+    };
+    for name in &options.unset {
+        env.remove(*name);
+    }
+    for (name, value) in &options.assignments {
+        env.insert(name.to_string(), value.to_string());
+    }
+
+    let dir = match options.dir {
+        Some(dir) => match cd::logical_cwd(state) {
+            Ok(cwd) => Some(normalize_path(&cwd.join(crate::engine::expand_home(dir)))),
+            Err(e) => {
+                eprintln!("cerf: sys.env: {}", e);
+                return (ExecutionResult::KeepRunning, 125);
+            }
+        },
+        None => None,
+    };
+
+    let Some((name, command_args)) = options.command.split_first() else {
+        if options.json {
+            println!("{}", to_json(&env));
+        } else {
+            for (name, value) in &env {
+                println!("{}={}", name, value);
+            }
+        }
+        return (ExecutionResult::KeepRunning, 0);
+    };
+
+    if state.functions.contains_key(name) || registry::find_command(name).is_some() {
+        run_in_shell(name, command_args, &env, dir, state)
+    } else {
+        (
+            ExecutionResult::KeepRunning,
+            run_external(name, command_args, &env, dir),
+        )
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options<'_>, String> {
+    let mut options = Options::default();
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if arg == "--" {
+            i += 1;
+            break;
+        }
+        if arg == "-" {
+            options.ignore_environment = true;
+        } else if arg == "--json" {
+            options.json = true;
+        } else if let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) {
+            for (pos, ch) in flags.char_indices() {
+                match ch {
+                    'i' => options.ignore_environment = true,
+                    'u' | 'C' => {
+                        // The value is the rest of this word or the next one.
+                        let rest = &flags[pos + 1..];
+                        let value = if rest.is_empty() {
+                            i += 1;
+                            args.get(i)
+                                .map(String::as_str)
+                                .ok_or_else(|| format!("-{}: option requires an argument", ch))?
+                        } else {
+                            rest
+                        };
+                        if ch == 'u' {
+                            options.unset.push(value);
+                        } else {
+                            options.dir = Some(value);
+                        }
+                        break;
+                    }
+                    _ => return Err(format!("-{}: invalid option", ch)),
+                }
+            }
+        } else {
+            break;
+        }
+        i += 1;
+    }
+
+    while let Some((name, value)) = args.get(i).and_then(|a| a.split_once('=')) {
+        if name.is_empty() {
+            return Err(format!("{}: invalid variable name", args[i]));
+        }
+        options.assignments.push((name, value));
+        i += 1;
+    }
+    options.command = &args[i..];
+
+    if options.json && !options.command.is_empty() {
+        return Err("--json cannot be used with a command".to_string());
+    }
+    Ok(options)
+}
+
+/// Run a builtin or function with `env` as the exported variables, then
+/// put the shell's variables and directory back.
+fn run_in_shell(
+    name: &str,
+    args: &[String],
+    env: &BTreeMap<String, String>,
+    dir: Option<PathBuf>,
+    state: &mut ShellState,
+) -> (ExecutionResult, i32) {
+    let saved_variables = state.variables.clone();
+    let saved_scopes = state.scopes.clone();
+    let saved_dir = match dir {
+        Some(dir) => {
+            let previous = std::env::current_dir();
+            if let Err(e) = std::env::set_current_dir(&dir) {
+                eprintln!("cerf: sys.env: {}: {}", dir.display(), e);
+                return (ExecutionResult::KeepRunning, 125);
+            }
+            previous.ok()
+        }
+        None => None,
+    };
+
+    // Readonly variables keep their values.
+    for (var_name, _) in state.exported_env() {
+        if !env.contains_key(&var_name) {
+            let _ = state.unset_var(&var_name);
+        }
+    }
+    for (var_name, value) in env {
+        let mut var = Variable::new_string(value.clone());
+        var.exported = true;
+        let _ = state.set_var(var_name, var);
+    }
+
+    let result = crate::engine::call_function(name, args, state).unwrap_or_else(|| {
+        // The caller checked that it is a builtin if not a function.
+        let info = registry::find_command(name).expect("sys.env: not a builtin");
+        (info.run)(args, state)
+    });
+
+    state.variables = saved_variables;
+    state.scopes = saved_scopes;
+    if let Some(previous) = saved_dir {
+        let _ = std::env::set_current_dir(previous);
+    }
+    result
+}
+
+/// Run an external command with exactly `env` as its environment.
+fn run_external(
+    name: &str,
+    args: &[String],
+    env: &BTreeMap<String, String>,
+    dir: Option<PathBuf>,
+) -> i32 {
+    let Some(resolved) = find_in_path(name, env.get("PATH").map(String::as_str)) else {
+        eprintln!("cerf: sys.env: {}: command not found", name);
+        return 127;
+    };
+    let mut command = Command::new(&resolved);
+    command.args(args).env_clear().envs(env);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    match command.status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            eprintln!("cerf: sys.env: {}: {}", name, e);
+            if e.kind() == std::io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            }
+        }
+    }
+}
+
+/// Format `env` as a JSON object.
+fn to_json(env: &BTreeMap<String, String>) -> String {
+    let fields: Vec<String> = env
+        .iter()
+        .map(|(name, value)| format!("{}:{}", json_string(name), json_string(value)))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::execute_list;
+    use crate::parser;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = strings(&["-iuHOME", "-C", "/tmp", "A=1", "B=x=y", "cmd", "C=2"]);
+        let options = parse_args(&args).unwrap();
+        assert!(options.ignore_environment);
+        assert_eq!(options.unset, ["HOME"]);
+        assert_eq!(options.dir, Some("/tmp"));
+        assert_eq!(options.assignments, [("A", "1"), ("B", "x=y")]);
+        assert_eq!(options.command, &args[5..]);
+
+        let args = strings(&["--", "-i"]);
+        assert_eq!(parse_args(&args).unwrap().command, &args[1..]);
+        assert!(parse_args(&strings(&["-u"])).is_err());
+        assert!(parse_args(&strings(&["-q"])).is_err());
+        assert!(parse_args(&strings(&["--json", "ls"])).is_err());
+    }
+
+    #[test]
+    fn test_json_escapes() {
+        let env = BTreeMap::from([
+            ("A".to_string(), "say \"hi\"\n".to_string()),
+            ("B".to_string(), "back\\slash\u{1}".to_string()),
+        ]);
+        assert_eq!(
+            to_json(&env),
+            r#"{"A":"say \"hi\"\n","B":"back\\slash\u0001"}"#
+        );
+    }
+
+    #[test]
+    fn test_function_sees_changed_env() {
+        let mut state = ShellState::new();
+        let define = "func probe { test.check x$$PROBE = xon; }";
+        execute_list(
+            parser::parse_pipeline(define, &state.variables).unwrap(),
+            &mut state,
+        );
+
+        let env = |args: &[&str], state: &mut ShellState| env_runner(&strings(args), state).1;
+        assert_eq!(env(&["PROBE=on", "probe"], &mut state), 0);
+        assert_eq!(env(&["probe"], &mut state), 1);
+        assert_eq!(state.get_var("PROBE"), None);
+
+        let mut probe = Variable::new_string("on".into());
+        probe.exported = true;
+        state.set_var("PROBE", probe).unwrap();
+        assert_eq!(env(&["probe"], &mut state), 0);
+        assert_eq!(env(&["-u", "PROBE", "probe"], &mut state), 1);
+        assert_eq!(state.get_var_string("PROBE").as_deref(), Some("on"));
+    }
+}
@@ -17,7 +17,7 @@ pub fn eval_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult,
 
     let command_str = args.join(" ");
 
-    if let Some(entries) = parser::parse_pipeline(&command_str, &state.variables) {
+    if let Some(entries) = parser::parse_pipeline(&command_str, state) {
         engine::execute_list(entries, state)
     } else {
         (ExecutionResult::KeepRunning, 0)
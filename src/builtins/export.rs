@@ -1,6 +1,6 @@
 use crate::builtins::registry::CommandInfo;
-use crate::engine::state::{ExecutionResult, ShellState};
-use std::collections::HashMap;
+use crate::engine::state::{ExecutionResult, ShellState, Variable};
+use std::borrow::Cow;
 
 pub const COMMAND_INFO: CommandInfo = CommandInfo {
     name: "env.export",
@@ -10,26 +10,24 @@ pub const COMMAND_INFO: CommandInfo = CommandInfo {
 };
 
 pub fn export_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
-    run(args, &mut state.variables);
-    (ExecutionResult::KeepRunning, 0)
+    (ExecutionResult::KeepRunning, run(args, state))
 }
 
 /// Run the `export` builtin.
 ///
 /// Behaviour:
-/// - `export`             → print all environment variables
-/// - `export name=value`  → set variable in both shell and environment
-/// - `export name`        → promote existing shell variable to environment
-pub fn run(args: &[String], variables: &mut HashMap<String, crate::engine::state::Variable>) {
+/// - `export`             → print the exported variables
+/// - `export name=value`  → set a variable and export it
+/// - `export name`        → export an existing shell variable
+pub fn run(args: &[String], state: &mut ShellState) -> i32 {
     if args.is_empty() {
-        let mut pairs: Vec<(String, String)> = std::env::vars().collect();
-        pairs.sort_by_key(|(k, _)| k.clone());
-        for (name, value) in pairs {
+        for (name, value) in state.exported_env() {
             println!("export {}='{}'", name, value);
         }
-        return;
+        return 0;
     }
 
+    let mut status = 0;
     for arg in args {
         if let Some(eq_pos) = arg.find('=') {
             // Assignment: name=value
@@ -37,30 +35,72 @@ pub fn run(args: &[String], variables: &mut HashMap<String, crate::engine::state
             let value = arg[eq_pos + 1..].to_string();
             if name.is_empty() {
                 eprintln!("cerf: export: '{}': not a valid identifier", arg);
+                status = 1;
             } else {
-                variables.insert(
-                    name.clone(),
-                    crate::engine::state::Variable::new_string(value.clone()),
-                );
-                unsafe {
-                    std::env::set_var(name, value);
+                let mut var = Variable::new_string(value);
+                var.exported = true;
+                if let Err(e) = state.set_var(&name, var) {
+                    eprintln!("cerf: export: {}", e);
+                    status = 1;
                 }
             }
         } else {
-            // Export existing: promote existing shell variable to env
-            if let Some(value) = variables.get(arg) {
-                unsafe {
-                    std::env::set_var(arg, value.value.as_string());
-                }
-            } else {
-                // If not in shell variables but already in env, do nothing
-                // If not in either, bash usually does nothing or adds it to exported list but empty.
-                // For simplicity, we just check env.
-                if std::env::var(arg).is_err() {
-                    // Optional: bash allows 'export FOO' which marks FOO as exported even if not set.
-                    // We'll skip that for now or just set as empty.
+            // Export existing: mark an existing shell variable for export.
+            // Like bash, naming an unset variable is not an error.
+            if let Some(mut var) = state.get_var(arg).map(Cow::into_owned) {
+                var.exported = true;
+                if var.readonly {
+                    // Exporting leaves the value alone, so it is allowed.
+                    if let Some(current) = state.get_var_mut(arg) {
+                        current.exported = true;
+                    }
+                } else {
+                    let _ = state.set_var(arg, var);
                 }
             }
         }
     }
+    status
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_child_env_follows_exports() {
+        let mut state = ShellState::new();
+        state
+            .set_var("PLAIN", Variable::new_string("x".into()))
+            .unwrap();
+        run(&strings(&["NEW=1", "PLAIN"]), &mut state);
+        let env = state.exported_env();
+        assert_eq!(env.get("NEW").map(String::as_str), Some("1"));
+        assert_eq!(env.get("PLAIN").map(String::as_str), Some("x"));
+
+        // Assigning keeps the export; unsetting drops it.
+        state
+            .set_var("NEW", Variable::new_string("2".into()))
+            .unwrap();
+        assert_eq!(state.exported_env()["NEW"], "2");
+        crate::builtins::unset::run(&strings(&["PLAIN"]), &mut state);
+        assert!(!state.exported_env().contains_key("PLAIN"));
+
+        // A local variable hides an exported global of the same name.
+        state.push_scope();
+        state
+            .declare_var("NEW", Variable::new_string("local".into()), false)
+            .unwrap();
+        assert!(!state.exported_env().contains_key("NEW"));
+        run(&strings(&["NEW"]), &mut state);
+        assert_eq!(state.exported_env()["NEW"], "local");
+        state.pop_scope();
+        assert_eq!(state.exported_env()["NEW"], "2");
+    }
 }
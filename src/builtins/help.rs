@@ -10,7 +10,26 @@ pub const COMMAND_INFO: CommandInfo = CommandInfo {
     run: help_runner,
 };
 
-pub fn help_runner(args: &[String], _state: &mut ShellState) -> (ExecutionResult, i32) {
+/// Help topic describing the files read at startup and exit.
+const STARTUP_HELP: &str = "\
+startup: files read when cerf starts and exits
+
+Interactive shells read, in order:
+  /etc/cerf/cerfrc                 System-wide configuration
+  $XDG_CONFIG_HOME/cerf/config.cf  User configuration (falls back to ~/.cerfrc)
+
+Login shells (`cerf -l`, or argv[0] starting with `-`) then read:
+  $XDG_CONFIG_HOME/cerf/profile.cf (falls back to ~/.cerf_profile)
+and read $XDG_CONFIG_HOME/cerf/logout.cf when they exit.
+
+Non-interactive shells (scripts and `cerf -c`) read the file named by
+$CERF_ENV, if it is set.
+
+`--rcfile <path>` replaces both interactive files, `--norc` skips them,
+and `--noprofile` skips the login profile. On Windows the system-wide file
+is %PROGRAMDATA%\\cerf\\cerfrc.";
+
+pub fn help_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
     let mut exit_code = 0;
 
     if args.is_empty() {
@@ -21,8 +40,9 @@ pub fn help_runner(args: &[String], _state: &mut ShellState) -> (ExecutionResult
         );
         help_text.push_str("Type `help name` to find out more about the function `name`.\n");
         help_text.push_str(
-            "Use `man -k` or `info` to find out more about commands not in this list.\n\n",
+            "Use `man -k` or `info` to find out more about commands not in this list.\n",
         );
+        help_text.push_str("Type `help startup` to see which files are read at startup.\n\n");
 
         let max_len = BUILTINS.iter().map(|b| b.name.len()).max().unwrap_or(0);
 
@@ -37,31 +57,34 @@ pub fn help_runner(args: &[String], _state: &mut ShellState) -> (ExecutionResult
         print!("{}", help_text);
     } else {
         for arg in args {
-            if let Some(cmd) = find_command(arg) {
+            if arg == "startup" {
+                println!("{}", STARTUP_HELP);
+            } else if let Some(cmd) = find_command(arg) {
                 println!("{}: {}", cmd.name, cmd.description);
                 println!("{}", cmd.usage);
             } else {
                 // OS Fallback
                 #[cfg(unix)]
                 {
-                    if find_executable("man").is_some() {
+                    if find_executable("man", state).is_some() {
                         let mut command = Command::new("man");
                         command.arg(arg);
+                        command.env_clear().envs(state.exported_env());
                         match command.status() {
                             Ok(status) if status.success() => {}
                             _ => {
                                 // Fallback to `<cmd> --help` if `man` fails
-                                try_help_flag(arg);
+                                try_help_flag(arg, state);
                             }
                         }
                     } else {
-                        try_help_flag(arg);
+                        try_help_flag(arg, state);
                     }
                 }
 
                 #[cfg(windows)]
                 {
-                    try_help_flag(arg);
+                    try_help_flag(arg, state);
                 }
                 exit_code = 127; // Will be overwritten if successful, or kept if not a known builtin/command
             }
@@ -70,10 +93,11 @@ pub fn help_runner(args: &[String], _state: &mut ShellState) -> (ExecutionResult
     (ExecutionResult::KeepRunning, exit_code)
 }
 
-fn try_help_flag(cmd_name: &str) {
-    if find_executable(cmd_name).is_some() {
-        let mut command = Command::new(cmd_name);
+fn try_help_flag(cmd_name: &str, state: &ShellState) {
+    if let Some(path) = find_executable(cmd_name, state) {
+        let mut command = Command::new(path);
         command.arg("--help");
+        command.env_clear().envs(state.exported_env());
         let _ = command.status();
     } else {
         eprintln!("cerf: help: no help topics match `{}`", cmd_name);
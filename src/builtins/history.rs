@@ -1,32 +1,378 @@
 use crate::builtins::registry::CommandInfo;
-use crate::engine::state::{ExecutionResult, ShellState};
+use crate::editor::picker::{self, PickOptions, StatusFilter};
+use crate::engine::state::{ExecutionResult, HistoryEntry, ShellState};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::fmt::Write as _;
 use std::io::Write;
 
 pub const COMMAND_INFO: CommandInfo = CommandInfo {
     name: "sys.history",
-    description: "Display the history list with line numbers.",
-    usage: "sys.history\n\nDisplay the history list with line numbers. Lines listed with a `*` have been modified.",
+    description: "Display or manipulate the history list.",
+    usage: "sys.history [--cwd] [--failed] [--since when] [n]\nsys.history --pick [--cwd] [--failed]\nsys.history -c\nsys.history -d offset\nsys.history -a | -n | -w\n\nDisplay the history list with line numbers, or the last N entries. When\nHISTTIMEFORMAT is set, its strftime format is shown before each entry.\n\nOptions:\n  -c            Clear the history list.\n  -d offset     Delete the entry at OFFSET; negative offsets count back\n                from the end.\n  -a            Append this session's new entries to $HISTFILE.\n  -n            Read entries added to $HISTFILE since it was last read,\n                e.g. by other sessions.\n  -w            Write the whole history list to $HISTFILE.\n  --cwd         Only entries run in the current directory.\n  --failed      Only entries that exited with a non-zero status.\n  --since when  Only entries newer than WHEN: a duration ago (30m, 2h, 3d,\n                1w) or a date (2024-05-01, \"2024-05-01 14:30\").\n  --pick        Choose an entry in the full-screen fuzzy picker (as on\n                Ctrl-R) and print it. Exits with 1 if cancelled.\n\nHISTSIZE and HISTFILESIZE limit the entries kept in memory and in the\nfile. HISTCONTROL is a colon-separated list of ignorespace, ignoredups,\nignoreboth and erasedups; HISTIGNORE is a colon-separated list of glob\npatterns of lines not to save (`&` is the previous entry).",
     run: history_runner,
 };
 
-// We will use standard stdout redirect logic in execution.rs later, but for now we'll match history's previous custom signature minimally
-pub fn history_runner(_args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
-    run(state, None); // Redirs will be handled automatically later
+/// Which entries to list.
+#[derive(Default)]
+struct Filter {
+    cwd: bool,
+    failed: bool,
+    pick: bool,
+    since: Option<i64>,
+    count: Option<usize>,
+}
+
+pub fn history_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let mut filter = Filter::default();
+    let mut actions = Vec::new();
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = &args[i];
+        i += 1;
+        match arg.as_str() {
+            "--cwd" => filter.cwd = true,
+            "--failed" => filter.failed = true,
+            "--pick" => filter.pick = true,
+            "--since" => {
+                let Some(value) = args.get(i) else {
+                    eprintln!("cerf: sys.history: --since: option requires an argument");
+                    return (ExecutionResult::KeepRunning, 2);
+                };
+                i += 1;
+                match parse_since(value) {
+                    Some(ts) => filter.since = Some(ts),
+                    None => {
+                        eprintln!("cerf: sys.history: {}: invalid time", value);
+                        return (ExecutionResult::KeepRunning, 2);
+                    }
+                }
+            }
+            "-d" => {
+                let Some(value) = args.get(i) else {
+                    eprintln!("cerf: sys.history: -d: option requires an argument");
+                    return (ExecutionResult::KeepRunning, 2);
+                };
+                i += 1;
+                actions.push(Action::Delete(value.clone()));
+            }
+            "-c" => actions.push(Action::Clear),
+            "-a" => actions.push(Action::Append),
+            "-n" => actions.push(Action::ReadNew),
+            "-w" => actions.push(Action::Write),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                eprintln!("cerf: sys.history: {}: invalid option", arg);
+                eprintln!(
+                    "usage: sys.history [-c] [-d offset] [-anw] [--pick] [--cwd] [--failed] [--since when] [n]"
+                );
+                return (ExecutionResult::KeepRunning, 2);
+            }
+            _ => match arg.parse() {
+                Ok(n) => filter.count = Some(n),
+                Err(_) => {
+                    eprintln!("cerf: sys.history: {}: numeric argument required", arg);
+                    return (ExecutionResult::KeepRunning, 2);
+                }
+            },
+        }
+    }
+
+    if filter.pick {
+        return (ExecutionResult::KeepRunning, pick(state, &filter));
+    }
+    if actions.is_empty() {
+        list(state, &filter);
+        return (ExecutionResult::KeepRunning, 0);
+    }
+    for action in actions {
+        if let Err(e) = action.apply(state) {
+            eprintln!("cerf: sys.history: {}", e);
+            return (ExecutionResult::KeepRunning, 1);
+        }
+    }
     (ExecutionResult::KeepRunning, 0)
 }
-/// Run the `history` builtin.
-///
-/// Prints all recorded history entries, numbered starting from 1.
-pub fn run(state: &ShellState, stdout_redirect: Option<std::fs::File>) {
-    let entries = &state.history;
 
-    if let Some(mut f) = stdout_redirect {
-        for (i, entry) in entries.iter().enumerate() {
-            let _ = writeln!(f, "  {}  {}", i + 1, entry);
+enum Action {
+    Clear,
+    Delete(String),
+    Append,
+    ReadNew,
+    Write,
+}
+
+impl Action {
+    fn apply(&self, state: &mut ShellState) -> Result<(), String> {
+        match self {
+            Action::Clear => {
+                state.history.clear();
+                state.history_saved = 0;
+            }
+            Action::Delete(offset) => {
+                let len = state.history.len() as i64;
+                let index = match offset.parse::<i64>() {
+                    Ok(n) if n > 0 && n <= len => n - 1,
+                    Ok(n) if n < 0 && -n <= len => len + n,
+                    _ => return Err(format!("{}: history position out of range", offset)),
+                };
+                state.delete_history(index as usize);
+            }
+            Action::Append => state.append_history(),
+            Action::ReadNew => {
+                state.read_new_history();
+            }
+            Action::Write => state.write_history().map_err(|e| e.to_string())?,
+        }
+        Ok(())
+    }
+}
+
+/// Print the entries matching `filter`, numbered by their position in the
+/// full list.
+fn list(state: &ShellState, filter: &Filter) {
+    let cwd = std::env::current_dir().ok();
+    let time_format = state
+        .get_var_string("HISTTIMEFORMAT")
+        .filter(|f| !f.is_empty());
+
+    let matching: Vec<(usize, &HistoryEntry)> = state
+        .history
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            (!filter.cwd || entry.cwd.is_some() && entry.cwd == cwd)
+                && (!filter.failed || entry.exit_code.is_some_and(|c| c != 0))
+                && filter
+                    .since
+                    .is_none_or(|since| entry.timestamp.is_some_and(|t| t >= since))
+        })
+        .collect();
+    let skip = filter.count.map_or(0, |n| matching.len().saturating_sub(n));
+
+    let mut out = std::io::stdout().lock();
+    for (i, entry) in &matching[skip..] {
+        let mut time = String::new();
+        if let Some(format) = &time_format
+            && let Some(ts) = entry.timestamp
+            && let Some(when) = Local.timestamp_opt(ts, 0).single()
+        {
+            // An invalid format makes `write!` fail rather than panic.
+            let _ = write!(time, "{}", when.format(format));
+        }
+        let _ = writeln!(out, "  {}  {}{}", i + 1, time, entry.line);
+    }
+}
+
+/// Show the picker and print the chosen line.
+fn pick(state: &ShellState, filter: &Filter) -> i32 {
+    let options = PickOptions {
+        cwd_only: filter.cwd,
+        status: if filter.failed {
+            StatusFilter::Failed
+        } else {
+            StatusFilter::Any
+        },
+        ..Default::default()
+    };
+    match picker::pick(&state.history, options) {
+        Ok(Some(picker::Pick::Run(line) | picker::Pick::Insert(line))) => {
+            println!("{}", line);
+            0
         }
-    } else {
-        for (i, entry) in entries.iter().enumerate() {
-            println!("  {}  {}", i + 1, entry);
+        Ok(None) => 1,
+        Err(e) => {
+            eprintln!("cerf: sys.history: --pick: {}", e);
+            1
         }
     }
 }
+
+/// Parse a `--since` argument into a Unix timestamp: a duration before now
+/// (`90s`, `30m`, `2h`, `3d`, `1w`) or a local date and optional time.
+fn parse_since(value: &str) -> Option<i64> {
+    let now = Local::now();
+    if let Some(unit) = value.chars().last().filter(char::is_ascii_alphabetic)
+        && let Ok(n) = value[..value.len() - 1].parse::<i64>()
+    {
+        let secs = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 7 * 86400,
+            _ => return None,
+        };
+        // Too far back to count is an error, not a wrapped timestamp.
+        return now.timestamp().checked_sub(n.checked_mul(secs)?);
+    }
+
+    let datetime = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })?;
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(|t| t.timestamp())
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::state::Variable;
+
+    fn state_with_history(dir: &tempfile::TempDir) -> ShellState {
+        let mut state = ShellState::new();
+        let file = dir.path().join("history");
+        state
+            .set_var(
+                "HISTFILE",
+                Variable::new_string(file.to_string_lossy().to_string()),
+            )
+            .unwrap();
+        state.load_history();
+        state
+    }
+
+    fn run(args: &[&str], state: &mut ShellState) -> i32 {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        history_runner(&args, state).1
+    }
+
+    fn lines(state: &ShellState) -> Vec<&str> {
+        state.history.iter().map(|e| e.line.as_str()).collect()
+    }
+
+    #[test]
+    fn test_delete_clear_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = state_with_history(&dir);
+        for line in ["one", "two", "three"] {
+            state.add_history(line);
+            state.finish_history_entry(0, std::time::Duration::ZERO);
+        }
+
+        assert_eq!(run(&["-d", "-1"], &mut state), 0);
+        assert_eq!(run(&["-d", "1"], &mut state), 0);
+        assert_eq!(lines(&state), ["two"]);
+        assert_eq!(run(&["-d", "5"], &mut state), 1);
+
+        // The file still has every entry until it is rewritten.
+        let mut other = state_with_history(&dir);
+        assert_eq!(lines(&other), ["one", "two", "three"]);
+        assert_eq!(run(&["-w"], &mut state), 0);
+        other.history.clear();
+        other.history_mark = Default::default();
+        assert_eq!(run(&["-n"], &mut other), 0);
+        assert_eq!(lines(&other), ["two"]);
+
+        assert_eq!(run(&["-c"], &mut state), 0);
+        assert!(state.history.is_empty());
+    }
+
+    #[test]
+    fn test_control_and_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = state_with_history(&dir);
+        state
+            .set_var(
+                "HISTCONTROL",
+                Variable::new_string("ignorespace:erasedups".into()),
+            )
+            .unwrap();
+        state
+            .set_var("HISTSIZE", Variable::new_string("3".into()))
+            .unwrap();
+        state
+            .set_var("HISTFILESIZE", Variable::new_string("2".into()))
+            .unwrap();
+
+        for line in ["a", " secret", "b", "a", "c", "d"] {
+            if state.add_history(line) {
+                state.finish_history_entry(0, std::time::Duration::ZERO);
+            }
+        }
+        assert_eq!(lines(&state), ["a", "c", "d"]);
+        assert_eq!(lines(&state_with_history(&dir)), ["c", "d"]);
+    }
+
+    #[test]
+    fn test_file_compacted_past_slack() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = state_with_history(&dir);
+        state
+            .set_var("HISTFILESIZE", Variable::new_string("20".into()))
+            .unwrap();
+        let saved = |dir| state_with_history(dir).history.len();
+
+        for i in 0..22 {
+            state.add_history(&format!("echo {}", i));
+            state.finish_history_entry(0, std::time::Duration::ZERO);
+        }
+        assert_eq!(saved(&dir), 22);
+        state.add_history("echo 22");
+        state.finish_history_entry(0, std::time::Duration::ZERO);
+        assert_eq!(saved(&dir), 20);
+
+        state.add_history("echo 23");
+        state.finish_history_entry(0, std::time::Duration::ZERO);
+        assert_eq!(saved(&dir), 21);
+        state.compact_history();
+        let reloaded = state_with_history(&dir);
+        assert_eq!(reloaded.history.len(), 20);
+        assert_eq!(reloaded.history.last().unwrap().line, "echo 23");
+    }
+
+    #[test]
+    fn test_erasedups_reaches_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = state_with_history(&dir);
+        state
+            .set_var("HISTCONTROL", Variable::new_string("erasedups".into()))
+            .unwrap();
+        for line in ["a", "b", "a", "c", "b"] {
+            state.add_history(line);
+            state.finish_history_entry(0, std::time::Duration::ZERO);
+        }
+        assert_eq!(lines(&state), ["a", "c", "b"]);
+        assert_eq!(lines(&state_with_history(&dir)), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn test_reload_after_histfile_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut other = state_with_history(&dir);
+        other.add_history("from the default file");
+        other.finish_history_entry(0, std::time::Duration::ZERO);
+
+        let mut state = state_with_history(&dir);
+        state.reload_history_if_changed();
+        assert_eq!(lines(&state), ["from the default file"]);
+
+        let file = dir.path().join("moved");
+        std::fs::write(&file, "from the rc file\n").unwrap();
+        state
+            .set_var("HISTFILE", Variable::new_string(file.display().to_string()))
+            .unwrap();
+        state.reload_history_if_changed();
+        assert_eq!(lines(&state), ["from the rc file"]);
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = Local::now().timestamp();
+        assert!((parse_since("2h").unwrap() - (now - 7200)).abs() < 5);
+        assert!(parse_since("2024-05-01").is_some());
+        assert!(parse_since("2024-05-01 14:30").is_some());
+        assert_eq!(parse_since("soon"), None);
+        assert_eq!(parse_since("3y"), None);
+        assert_eq!(parse_since("99999999999999999w"), None);
+        assert_eq!(parse_since(&format!("{}s", i64::MIN)), None);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::builtins::registry::CommandInfo;
+use crate::engine::state::{ExecutionResult, HookEvent, ShellState};
+
+pub const COMMAND_INFO: CommandInfo = CommandInfo {
+    name: "sys.hook",
+    description: "Run shell functions when shell events happen.",
+    usage: "sys.hook add <event> <function>\nsys.hook rm <event> <function>\nsys.hook [list]\n\nCall FUNCTION whenever EVENT happens. Functions hooked to the same event\nrun in the order they were added, and need not be defined yet.\n\nEvents:\n  chpwd    The working directory changed (cd, pushd, popd, ...).\n  preexec  A command line was read and is about to run; $1 is its text.\n  precmd   The prompt is about to be shown.\n  exit     The shell is exiting.\n\nHooks do not change $?. With no arguments, the hooks are printed in a\nreusable format.",
+    run: hook_runner,
+};
+
+pub fn hook_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let code = match run(args, state) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("cerf: sys.hook: {}", e);
+            1
+        }
+    };
+    (ExecutionResult::KeepRunning, code)
+}
+
+fn run(args: &[String], state: &mut ShellState) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            for event in HookEvent::ALL {
+                for function in state.hooks.get(&event).into_iter().flatten() {
+                    println!("sys.hook add {} {}", event.name(), function);
+                }
+            }
+            Ok(())
+        }
+        Some(sub @ ("add" | "rm")) => {
+            let [event, function] = &args[1..] else {
+                return Err(format!("usage: sys.hook {} <event> <function>", sub));
+            };
+            let event = HookEvent::from_name(event).ok_or_else(|| {
+                let names: Vec<_> = HookEvent::ALL.iter().map(|e| e.name()).collect();
+                format!("{}: unknown event (expected {})", event, names.join(", "))
+            })?;
+            let functions = state.hooks.entry(event).or_default();
+            if sub == "add" {
+                if !functions.contains(function) {
+                    functions.push(function.clone());
+                }
+            } else {
+                let before = functions.len();
+                functions.retain(|f| f != function);
+                if functions.len() == before {
+                    return Err(format!("{}: not hooked to {}", function, event.name()));
+                }
+            }
+            Ok(())
+        }
+        Some(other) => Err(format!(
+            "{}: unknown subcommand\nusage: sys.hook add <event> <function> | rm <event> <function> | list",
+            other
+        )),
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(args: &[&str], state: &mut ShellState) -> i32 {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        hook_runner(&args, state).1
+    }
+
+    #[test]
+    fn test_add_and_remove_hooks() {
+        let mut state = ShellState::new();
+        assert_eq!(hook(&["add", "chpwd", "ls_here"], &mut state), 0);
+        assert_eq!(hook(&["add", "chpwd", "title"], &mut state), 0);
+        assert_eq!(hook(&["add", "chpwd", "ls_here"], &mut state), 0);
+        assert_eq!(state.hooks[&HookEvent::Chpwd], ["ls_here", "title"]);
+
+        assert_eq!(hook(&["add", "postexec", "f"], &mut state), 1);
+        assert_eq!(hook(&["add", "precmd"], &mut state), 1);
+        assert_eq!(hook(&["frob"], &mut state), 1);
+
+        assert_eq!(hook(&["rm", "chpwd", "ls_here"], &mut state), 0);
+        assert_eq!(hook(&["rm", "chpwd", "ls_here"], &mut state), 1);
+        assert_eq!(state.hooks[&HookEvent::Chpwd], ["title"]);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::builtins::registry::CommandInfo;
+use crate::engine::frecency;
+use crate::engine::state::{ExecutionResult, ShellState};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+pub const COMMAND_INFO: CommandInfo = CommandInfo {
+    name: "dir.jump",
+    description: "Change to a frequently used directory.",
+    usage: "dir.jump [-i] fragment ...\ndir.jump -l [fragment ...]\ndir.jump -x [dir]\n\nChange to the highest-ranked directory whose path contains every FRAGMENT,\nin order. The last fragment must match the final path component. Matching\nignores case unless a fragment has an upper-case letter.\n\nDirectories are ranked by frecency: how often and how recently they were\nentered with `cd`. When two matches rank closely on a terminal, a numbered\nmenu asks which one to use.\n\nThe database is $CERF_DIRS_FILE, or $XDG_DATA_HOME/cerf/dirs by default;\nset CERF_DIRS_FILE to an empty string to stop recording directories.\n\nOptions:\n  -i      Always choose from the menu of matches.\n  -l      List the matching directories with their scores, best last.\n  -x dir  Remove DIR (default: the current directory) from the database.",
+    run: jump_runner,
+};
+
+pub fn jump_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let mut list = false;
+    let mut remove = false;
+    let mut interactive = false;
+    let mut i = 0;
+
+    while i < args.len() && args[i].starts_with('-') && args[i].len() > 1 {
+        let arg = &args[i];
+        i += 1;
+        if arg == "--" {
+            break;
+        }
+        for ch in arg[1..].chars() {
+            match ch {
+                'i' => interactive = true,
+                'l' => list = true,
+                'x' => remove = true,
+                _ => {
+                    eprintln!("cerf: dir.jump: -{}: invalid option", ch);
+                    eprintln!("usage: dir.jump [-ilx] [fragment ...]");
+                    return (ExecutionResult::KeepRunning, 2);
+                }
+            }
+        }
+    }
+    let fragments = &args[i..];
+
+    let Some(file) = state.dirs_file() else {
+        eprintln!("cerf: dir.jump: the directory database is disabled");
+        return (ExecutionResult::KeepRunning, 1);
+    };
+
+    let code = if remove {
+        forget(&file, fragments.first())
+    } else if list || fragments.is_empty() {
+        for (score, path) in candidates(&file, fragments).iter().rev() {
+            println!("{:<10.1} {}", score, path.display());
+        }
+        0
+    } else {
+        jump(state, &file, fragments, interactive)
+    };
+    (ExecutionResult::KeepRunning, code)
+}
+
+/// The existing directories matching `fragments`, best first.
+fn candidates(file: &Path, fragments: &[String]) -> Vec<(f64, PathBuf)> {
+    let entries = frecency::load(file);
+    let now = chrono::Local::now().timestamp();
+    frecency::ranked(&entries, fragments, now)
+        .into_iter()
+        .filter(|(_, e)| e.path.is_dir())
+        .map(|(score, e)| (score, e.path.clone()))
+        .collect()
+}
+
+fn jump(state: &mut ShellState, file: &Path, fragments: &[String], interactive: bool) -> i32 {
+    let cwd = std::env::current_dir().ok();
+    let found: Vec<(f64, PathBuf)> = candidates(file, fragments)
+        .into_iter()
+        .filter(|(_, path)| Some(path) != cwd.as_ref())
+        .collect();
+
+    let target = match found.as_slice() {
+        [] => {
+            // Fall back to a directory given by its path, like `cd`.
+            let path = crate::engine::expand_home(&fragments[0]);
+            if fragments.len() == 1 && path.is_dir() {
+                path
+            } else {
+                eprintln!(
+                    "cerf: dir.jump: {}: no matching directory",
+                    fragments.join(" ")
+                );
+                return 1;
+            }
+        }
+        [(_, only)] if !interactive => only.clone(),
+        [(best, path), (second, _), ..] if !interactive && *second * 2.0 <= *best => path.clone(),
+        _ if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() => {
+            found[0].1.clone()
+        }
+        _ => match choose(&found) {
+            Some(path) => path,
+            None => return 1,
+        },
+    };
+
+    match super::cd::run(&[target.to_string_lossy().into_owned()], state) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("cerf: dir.jump: {}", e);
+            1
+        }
+    }
+}
+
+/// Ask which of `found` to jump to. An empty answer picks the first.
+fn choose(found: &[(f64, PathBuf)]) -> Option<PathBuf> {
+    let shown = &found[..found.len().min(9)];
+    let mut err = std::io::stderr().lock();
+    for (n, (_, path)) in shown.iter().enumerate() {
+        let _ = writeln!(err, "{}) {}", n + 1, path.display());
+    }
+    let _ = write!(err, "jump to [1]: ");
+    let _ = err.flush();
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer).ok()?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Some(shown[0].1.clone());
+    }
+    match answer.parse::<usize>() {
+        Ok(n) if (1..=shown.len()).contains(&n) => Some(shown[n - 1].1.clone()),
+        _ => None,
+    }
+}
+
+/// Remove `dir` (default: the current directory) from the database.
+fn forget(file: &Path, dir: Option<&String>) -> i32 {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let dir = match dir {
+        Some(d) => cwd.join(crate::engine::expand_home(d)),
+        None => cwd,
+    };
+    let mut found = false;
+    let result = frecency::update(file, |entries| {
+        let before = entries.len();
+        entries.retain(|e| e.path != dir);
+        found = entries.len() != before;
+    });
+    match result {
+        Ok(()) if found => 0,
+        Ok(()) => {
+            eprintln!("cerf: dir.jump: {}: not in the database", dir.display());
+            1
+        }
+        Err(e) => {
+            eprintln!("cerf: dir.jump: {}: {}", file.display(), e);
+            1
+        }
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::state::Variable;
+
+    #[test]
+    fn test_record_rank_and_forget() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("data").join("dirs");
+        let mut state = ShellState::new();
+        state
+            .set_var(
+                "CERF_DIRS_FILE",
+                Variable::new_string(file.to_string_lossy().to_string()),
+            )
+            .unwrap();
+
+        let src = dir.path().join("project").join("src");
+        let docs = dir.path().join("project").join("docs");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(&docs).unwrap();
+        state.record_dir_visit(&docs);
+        state.record_dir_visit(&src);
+        state.record_dir_visit(&src);
+        state.record_dir_visit(&dir.path().join("gone"));
+
+        let found = candidates(&file, &["proj".to_string()]);
+        assert!(found.is_empty());
+        let found = candidates(&file, &[]);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].1, src);
+        let found = candidates(&file, &["proj".to_string(), "DOCS".to_string()]);
+        assert!(found.is_empty());
+        let found = candidates(&file, &["proj".to_string(), "doc".to_string()]);
+        assert_eq!(found.len(), 1);
+
+        assert_eq!(forget(&file, Some(&src.to_string_lossy().to_string())), 0);
+        assert_eq!(forget(&file, Some(&src.to_string_lossy().to_string())), 1);
+        assert_eq!(candidates(&file, &[]).len(), 1);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut state = ShellState::new();
+        state
+            .set_var("CERF_DIRS_FILE", Variable::new_string(String::new()))
+            .unwrap();
+        assert_eq!(state.dirs_file(), None);
+        let args = vec!["-l".to_string()];
+        assert_eq!(jump_runner(&args, &mut state).1, 1);
+    }
+}
@@ -27,6 +27,12 @@ pub fn mapfile_runner(args: &[String], state: &mut ShellState) -> (ExecutionResu
         }
     }
 
+    // Fail before consuming any input.
+    if let Err(e) = state.check_writable(&array_name) {
+        eprintln!("cerf: mapfile: {}", e);
+        return (ExecutionResult::KeepRunning, 1);
+    }
+
     let mut lines = Vec::new();
     let stdin = io::stdin();
     let mut handle = stdin.lock();
@@ -50,7 +56,11 @@ pub fn mapfile_runner(args: &[String], state: &mut ShellState) -> (ExecutionResu
         }
     }
 
-    state.set_var(&array_name, Variable::new_array(lines));
-
-    (ExecutionResult::KeepRunning, 0)
+    match state.set_var(&array_name, Variable::new_array(lines)) {
+        Ok(()) => (ExecutionResult::KeepRunning, 0),
+        Err(e) => {
+            eprintln!("cerf: mapfile: {}", e);
+            (ExecutionResult::KeepRunning, 1)
+        }
+    }
 }
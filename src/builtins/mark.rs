@@ -0,0 +1,192 @@
+use crate::builtins::cd;
+use crate::builtins::registry::CommandInfo;
+use crate::engine::bookmarks;
+use crate::engine::path::{expand_tilde, normalize_path};
+use crate::engine::state::{ExecutionResult, ShellState};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub const COMMAND_INFO_MARK: CommandInfo = CommandInfo {
+    name: "dir.mark",
+    description: "Manage directory bookmarks.",
+    usage: "dir.mark add <name> [path]\ndir.mark rm <name> ...\ndir.mark [list]\n\nBookmark PATH (default: the current directory) as NAME. A bookmark can be\nused as `~name` at the start of any path, e.g. `ls ~web/src`, and jumped\nto with `dir.go name`.\n\nNames may contain letters, digits, `_`, `-` and `.`. Bookmarks are kept\nin $XDG_CONFIG_HOME/cerf/bookmarks and shared by all shells.",
+    run: mark_runner,
+};
+
+pub const COMMAND_INFO_GO: CommandInfo = CommandInfo {
+    name: "dir.go",
+    description: "Change to a bookmarked directory.",
+    usage: "dir.go <name>[/path]\n\nChange to the directory bookmarked as NAME with `dir.mark`, or to PATH\ninside it.",
+    run: go_runner,
+};
+
+pub fn mark_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let code = match run(args, state) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("cerf: dir.mark: {}", e);
+            1
+        }
+    };
+    (ExecutionResult::KeepRunning, code)
+}
+
+pub fn go_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    let [target] = args else {
+        eprintln!("usage: dir.go <name>[/path]");
+        return (ExecutionResult::KeepRunning, 2);
+    };
+    if let Err(e) = reload(state) {
+        eprintln!("cerf: dir.go: {}", e);
+        return (ExecutionResult::KeepRunning, 1);
+    }
+    let name = target
+        .split(std::path::is_separator)
+        .next()
+        .unwrap_or_default();
+    let dir = bookmarks::is_valid_name(name)
+        .then(|| expand_tilde(&format!("~{}", target)))
+        .flatten();
+    let Some(dir) = dir else {
+        eprintln!("cerf: dir.go: {}: no such bookmark", target);
+        return (ExecutionResult::KeepRunning, 1);
+    };
+    match cd::run(&["--".to_string(), dir], state) {
+        Ok(()) => (ExecutionResult::KeepRunning, 0),
+        Err(e) => {
+            eprintln!("cerf: dir.go: {}", e);
+            (ExecutionResult::KeepRunning, 1)
+        }
+    }
+}
+
+fn run(args: &[String], state: &mut ShellState) -> Result<i32, String> {
+    let file = state
+        .bookmarks_file()
+        .ok_or("no configuration directory for the bookmarks file")?;
+    let io_error = |e: std::io::Error| format!("{}: {}", file.display(), e);
+
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            for (name, path) in &reload(state)? {
+                println!("{:<12} {}", name, path.display());
+            }
+            Ok(0)
+        }
+        Some("add") => {
+            let (name, path) = match &args[1..] {
+                [name] => (name, None),
+                [name, path] => (name, Some(path)),
+                _ => return Err("usage: dir.mark add <name> [path]".to_string()),
+            };
+            if !bookmarks::is_valid_name(name) {
+                return Err(format!("{}: invalid bookmark name", name));
+            }
+            let cwd = cd::logical_cwd(state).map_err(|e| e.to_string())?;
+            let dir = match path {
+                Some(path) => normalize_path(&cwd.join(crate::engine::expand_home(path))),
+                None => cwd,
+            };
+            if !dir.is_dir() {
+                return Err(format!("{}: not a directory", dir.display()));
+            }
+            bookmarks::update(&file, |marks| marks.insert(name.clone(), dir)).map_err(io_error)?;
+            Ok(0)
+        }
+        Some("rm") if args.len() == 1 => Err("usage: dir.mark rm <name> ...".to_string()),
+        Some("rm") => {
+            let missing = bookmarks::update(&file, |marks| {
+                args[1..]
+                    .iter()
+                    .filter(|name| marks.remove(name.as_str()).is_none())
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .map_err(io_error)?;
+            for name in &missing {
+                eprintln!("cerf: dir.mark: {}: no such bookmark", name);
+            }
+            Ok(if missing.is_empty() { 0 } else { 1 })
+        }
+        Some(other) => Err(format!(
+            "{}: unknown subcommand\nusage: dir.mark add <name> [path] | rm <name> ... | list",
+            other
+        )),
+    }
+}
+
+/// Switch to the current bookmarks file, which moves with `XDG_CONFIG_HOME`,
+/// and return its bookmarks.
+fn reload(state: &ShellState) -> Result<BTreeMap<String, PathBuf>, String> {
+    let file = state
+        .bookmarks_file()
+        .ok_or("no configuration directory for the bookmarks file")?;
+    bookmarks::watch(&file);
+    Ok(bookmarks::all())
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::state::Variable;
+
+    fn mark(args: &[&str], state: &mut ShellState) -> i32 {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        mark_runner(&args, state).1
+    }
+
+    #[test]
+    fn test_add_expand_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let web = dir.path().join("web");
+        std::fs::create_dir(&web).unwrap();
+        let mut state = ShellState::new();
+        state
+            .set_var(
+                "XDG_CONFIG_HOME",
+                Variable::new_string(dir.path().join("config").display().to_string()),
+            )
+            .unwrap();
+
+        let web_arg = web.display().to_string();
+        assert_eq!(mark(&["add", "web", &web_arg], &mut state), 0);
+        assert_eq!(mark(&["add", "bad/name", &web_arg], &mut state), 1);
+        assert_eq!(mark(&["add", "file", "/no/such/dir"], &mut state), 1);
+        assert_eq!(mark(&["frob"], &mut state), 1);
+
+        let file = dir.path().join("config").join("cerf").join("bookmarks");
+        assert_eq!(bookmarks::load(&file).get("web"), Some(&web));
+        assert_eq!(
+            expand_tilde("~web/src"),
+            Some(format!("{}/src", web.display()))
+        );
+        assert_eq!(expand_tilde("~nosuchmark/src"), None);
+
+        // Only a `~web` that was typed names the bookmark, not one in a variable.
+        let mut vars = std::collections::HashMap::new();
+        vars.insert("X".to_string(), Variable::new_string("~web".to_string()));
+        let cmd = crate::parser::parse_line_with_vars("echo ~web/src $X", &vars).unwrap();
+        let args: Vec<&str> = cmd.args().iter().map(|a| a.value.as_str()).collect();
+        assert_eq!(args, [format!("{}/src", web.display()).as_str(), "~web"]);
+        assert_eq!(crate::engine::expand_home("~web"), PathBuf::from("~web"));
+
+        // A bookmark added by another shell is seen without reloading.
+        let api = dir.path().join("my api");
+        std::fs::create_dir(&api).unwrap();
+        let contents = std::fs::read_to_string(&file).unwrap();
+        std::fs::write(&file, format!("{}api\t{}\n", contents, api.display())).unwrap();
+        assert_eq!(expand_tilde("~api"), Some(api.display().to_string()));
+
+        // A path with a space stays one argument.
+        let cmd = crate::parser::parse_line_with_vars("cd ~api/x ~api", &vars).unwrap();
+        let args: Vec<&str> = cmd.args().iter().map(|a| a.value.as_str()).collect();
+        let api = api.display().to_string();
+        assert_eq!(args, [format!("{}/x", api).as_str(), api.as_str()]);
+
+        assert_eq!(mark(&["rm", "web", "api", "other"], &mut state), 1);
+        assert!(bookmarks::load(&file).is_empty());
+        assert_eq!(bookmarks::get("web"), None);
+    }
+}
@@ -1,24 +1,32 @@
 pub mod alias;
+pub mod allow;
 pub mod bg;
+pub mod bind;
 pub mod boolean;
 pub mod builtin_cmd;
 pub mod cd;
 pub mod command_cmd;
+pub mod complete;
 pub mod declare;
 pub mod dirs;
 pub mod echo;
+pub mod env_cmd;
 pub mod eval;
 pub mod export;
 pub mod fg;
 pub mod fs;
 pub mod help;
 pub mod history;
+pub mod hook;
 pub mod jobs;
+pub mod jump;
 pub mod kill_cmd;
 pub mod local;
 pub mod mapfile;
+pub mod mark;
 pub mod printf;
 pub mod read;
+pub mod readonly;
 pub mod registry;
 pub mod set;
 pub mod shift;
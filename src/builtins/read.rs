@@ -51,6 +51,10 @@ pub fn run(args: &[String], state: &mut ShellState) -> Result<(), String> {
     if var_names.is_empty() {
         var_names.push("REPLY".to_string());
     }
+    // Fail before consuming any input.
+    for name in &var_names {
+        state.check_writable(name)?;
+    }
 
     if let Some(p) = prompt {
         print!("{}", p);
@@ -128,12 +132,7 @@ pub fn run(args: &[String], state: &mut ShellState) -> Result<(), String> {
         state.set_var(
             var_name,
             crate::engine::state::Variable::new_string(val.to_string()),
-        );
-        if std::env::var(var_name).is_ok() {
-            unsafe {
-                std::env::set_var(var_name, val);
-            }
-        }
+        )?;
     }
 
     Ok(())
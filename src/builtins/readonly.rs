@@ -0,0 +1,152 @@
+use crate::builtins::registry::CommandInfo;
+use crate::engine::state::{ExecutionResult, ShellState, VarValue, Variable};
+use crate::parser::{is_identifier, quote_word};
+use std::borrow::Cow;
+
+pub const COMMAND_INFO: CommandInfo = CommandInfo {
+    name: "env.readonly",
+    description: "Mark shell variables as unchangeable.",
+    usage: "env.readonly [-p] [name[=value] ...]\n\nMark each NAME as readonly, assigning VALUE first if it is given. A readonly\nvariable cannot be assigned to or unset for the rest of the session.\n\nWith no NAME, or with -p, print the readonly variables.",
+    run: readonly_runner,
+};
+
+pub fn readonly_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    (ExecutionResult::KeepRunning, run(args, state))
+}
+
+/// Run the `readonly` builtin.
+///
+/// Behaviour:
+/// - `readonly` / `readonly -p` → print the readonly variables
+/// - `readonly name=value`      → set a variable and make it readonly
+/// - `readonly name`            → make a variable readonly, creating it empty
+pub fn run(args: &[String], state: &mut ShellState) -> i32 {
+    let mut operands = args;
+    while let Some(option) = operands.first().filter(|a| a.starts_with('-')) {
+        match option.as_str() {
+            "-p" => operands = &operands[1..],
+            "--" => {
+                operands = &operands[1..];
+                break;
+            }
+            _ => {
+                eprintln!("cerf: readonly: {}: invalid option", option);
+                eprintln!("usage: readonly [-p] [name[=value] ...]");
+                return 2;
+            }
+        }
+    }
+
+    if operands.is_empty() {
+        for line in listing(state) {
+            println!("{}", line);
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in operands {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_identifier(name) {
+            eprintln!("cerf: readonly: '{}': not a valid identifier", arg);
+            status = 1;
+            continue;
+        }
+
+        let Some(value) = value else {
+            // Marking an existing variable leaves its value alone.
+            match state.get_var_mut(name) {
+                Some(var) => var.readonly = true,
+                None => {
+                    let mut var = Variable::new_string(String::new());
+                    var.readonly = true;
+                    let _ = state.set_var(name, var);
+                }
+            }
+            continue;
+        };
+
+        let mut var = state
+            .get_var(name)
+            .map(Cow::into_owned)
+            .unwrap_or_else(|| Variable::new_string(String::new()));
+        var.value = VarValue::String(value.to_string());
+        if let Err(e) = state.set_var(name, var) {
+            eprintln!("cerf: readonly: {}", e);
+            status = 1;
+        } else if let Some(var) = state.get_var_mut(name) {
+            var.readonly = true;
+        }
+    }
+    status
+}
+
+/// The readonly variables in scope, as `readonly` commands, sorted by name.
+fn listing(state: &ShellState) -> Vec<String> {
+    let mut names: Vec<&String> = std::iter::once(&state.variables)
+        .chain(&state.scopes)
+        .flat_map(|scope| scope.keys())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let var = state.get_var(name).filter(|v| v.readonly)?;
+            Some(match &var.value {
+                VarValue::String(value) => format!("readonly {}={}", name, quote_word(value)),
+                // `readonly` can't make arrays; `declare -ar` can.
+                VarValue::Array(_) => crate::builtins::declare::declaration(name, &var),
+            })
+        })
+        .collect()
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_readonly_blocks_changes() {
+        let mut state = ShellState::new();
+        assert_eq!(run(&strings(&["RO_VALUE=1", "RO_EMPTY"]), &mut state), 0);
+        assert_eq!(state.get_var_string("RO_VALUE").as_deref(), Some("1"));
+
+        let two = Variable::new_string("2".into());
+        assert!(state.set_var("RO_VALUE", two).is_err());
+        assert!(state.unset_var("RO_EMPTY").is_err());
+        assert_eq!(run(&strings(&["RO_VALUE=3"]), &mut state), 1);
+        assert_eq!(state.get_var_string("RO_VALUE").as_deref(), Some("1"));
+        assert_eq!(run(&strings(&["1BAD=x"]), &mut state), 1);
+        assert!(
+            state
+                .set_var("PPID", Variable::new_string("0".into()))
+                .is_err()
+        );
+        assert!(state.unset_var("CERF_VERSION").is_err());
+
+        let mut quoted = Variable::new_string("it's $5".into());
+        quoted.readonly = true;
+        state.set_var("RO_QUOTED", quoted).unwrap();
+        let mut array = Variable::new_array(vec!["a".into(), "b c".into()]);
+        array.readonly = true;
+        state.set_var("RO_ARRAY", array).unwrap();
+
+        let listing = listing(&state);
+        assert!(listing.contains(&"readonly RO_VALUE=1".to_string()));
+        assert!(listing.contains(&"readonly RO_EMPTY=''".to_string()));
+        assert!(listing.contains(&"readonly RO_QUOTED='it'\"'\"'s $$5'".to_string()));
+        assert!(listing.contains(&"declare -ar RO_ARRAY='(a '\"'\"'b c'\"'\"')'".to_string()));
+        assert!(!listing.iter().any(|l| l.starts_with("readonly HOME=")));
+    }
+}
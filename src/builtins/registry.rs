@@ -12,7 +12,10 @@ pub struct CommandInfo {
 
 pub const BUILTINS: &[CommandInfo] = &[
     builtins::alias::COMMAND_INFO,
+    builtins::allow::COMMAND_INFO_ALLOW,
+    builtins::allow::COMMAND_INFO_DENY,
     builtins::bg::COMMAND_INFO,
+    builtins::bind::COMMAND_INFO,
     builtins::boolean::COMMAND_INFO_FALSE,
     builtins::boolean::COMMAND_INFO_TRUE,
     builtins::cd::COMMAND_INFO_CD,
@@ -21,13 +24,19 @@ pub const BUILTINS: &[CommandInfo] = &[
     builtins::dirs::COMMAND_INFO_POPD,
     builtins::dirs::COMMAND_INFO_PUSHD,
     builtins::echo::COMMAND_INFO,
+    builtins::env_cmd::COMMAND_INFO,
     builtins::export::COMMAND_INFO,
     builtins::fg::COMMAND_INFO,
     builtins::help::COMMAND_INFO,
     builtins::history::COMMAND_INFO,
+    builtins::hook::COMMAND_INFO,
     builtins::jobs::COMMAND_INFO,
+    builtins::jump::COMMAND_INFO,
     builtins::kill_cmd::COMMAND_INFO,
+    builtins::mark::COMMAND_INFO_GO,
+    builtins::mark::COMMAND_INFO_MARK,
     builtins::read::COMMAND_INFO,
+    builtins::readonly::COMMAND_INFO,
     builtins::set::COMMAND_INFO,
     builtins::source::COMMAND_INFO_SOURCE,
     builtins::system::COMMAND_INFO_CLEAR,
@@ -48,6 +57,7 @@ pub const BUILTINS: &[CommandInfo] = &[
     builtins::eval::COMMAND_INFO_EVAL,
     builtins::builtin_cmd::COMMAND_INFO_BUILTIN,
     builtins::command_cmd::COMMAND_INFO_COMMAND,
+    builtins::complete::COMMAND_INFO_COMPLETE,
     builtins::ulimit::COMMAND_INFO_ULIMIT,
     builtins::umask::COMMAND_INFO_UMASK,
     builtins::fs::mkdir::COMMAND_INFO,
@@ -26,6 +26,9 @@ pub fn set_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult,
 /// - `set -u` / `set +u`  → short-form to enable / disable `nounset`
 /// - `set -x` / `set +x`  → short-form to enable / disable `xtrace`
 /// - `set -f` / `set +f`  → short-form to enable / disable `noglob`
+/// - `set -H` / `set +H`  → short-form to enable / disable `histexpand`
+/// - `set -o vi` / `set -o emacs` → choose the line-editing mode
+/// - `set -o autocd`      → run a bare directory name as `cd` into it
 /// - `set -- arg …`      → set positional parameters ($1, $2, …)
 pub fn run(args: &[String], state: &mut ShellState) -> i32 {
     // No arguments: print all shell variables, sorted.
@@ -116,8 +119,8 @@ pub fn run(args: &[String], state: &mut ShellState) -> i32 {
 
 // ── Helpers ─────────────────────────────────────────────────────────────
 
-/// Map a single-character flag to its long option name and set the option.
-fn set_option_by_char(ch: char, enable: bool, state: &mut ShellState) -> Result<(), String> {
+/// Map a single-character flag to its long option name.
+pub fn option_for_char(ch: char) -> Option<&'static str> {
     let name = match ch {
         'e' => "errexit",
         'u' => "nounset",
@@ -128,16 +131,23 @@ fn set_option_by_char(ch: char, enable: bool, state: &mut ShellState) -> Result<
         'h' => "hashall",
         'b' => "notify",
         'C' => "noclobber",
-        _ => return Err(format!("set: invalid option: -{}", ch)),
+        'H' => "histexpand",
+        _ => return None,
     };
+    Some(name)
+}
+
+/// Map a single-character flag to its long option name and set the option.
+fn set_option_by_char(ch: char, enable: bool, state: &mut ShellState) -> Result<(), String> {
+    let name = option_for_char(ch).ok_or_else(|| format!("set: invalid option: -{}", ch))?;
     set_option_by_name(name, enable, state)
 }
 
 /// Enable or disable a shell option by its long name.
-fn set_option_by_name(name: &str, enable: bool, state: &mut ShellState) -> Result<(), String> {
+pub fn set_option_by_name(name: &str, enable: bool, state: &mut ShellState) -> Result<(), String> {
     match name {
         "errexit" | "nounset" | "xtrace" | "noglob" | "noexec" | "verbose" | "hashall"
-        | "notify" | "noclobber" => {
+        | "notify" | "noclobber" | "autocd" | "autosuggest" | "histexpand" | "share_history" => {
             if enable {
                 state.set_options.insert(name.to_string());
             } else {
@@ -145,6 +155,15 @@ fn set_option_by_name(name: &str, enable: bool, state: &mut ShellState) -> Resul
             }
             Ok(())
         }
+        // The two editing modes exclude each other; turning one off selects
+        // the other.
+        "vi" | "emacs" => {
+            let other = if name == "vi" { "emacs" } else { "vi" };
+            let (on, off) = if enable { (name, other) } else { (other, name) };
+            state.set_options.insert(on.to_string());
+            state.set_options.remove(off);
+            Ok(())
+        }
         _ => Err(format!("set: unrecognised option: {}", name)),
     }
 }
@@ -173,16 +192,22 @@ fn print_options_commands(state: &ShellState) {
 }
 
 /// Canonical ordered list of supported option names.
-fn option_names() -> &'static [&'static str] {
+pub fn option_names() -> &'static [&'static str] {
     &[
+        "autocd",
+        "autosuggest",
+        "emacs",
         "errexit",
         "hashall",
+        "histexpand",
         "noclobber",
         "noexec",
         "noglob",
         "notify",
         "nounset",
+        "share_history",
         "verbose",
+        "vi",
         "xtrace",
     ]
 }
@@ -190,7 +215,7 @@ fn option_names() -> &'static [&'static str] {
 /// Set positional parameters ($1, $2, …) as shell variables.
 ///
 /// Previous positional parameters are cleared first.
-fn set_positional_params(params: &[String], state: &mut ShellState) {
+pub fn set_positional_params(params: &[String], state: &mut ShellState) {
     // Remove old positional parameters.
     let mut idx = 1;
     loop {
@@ -206,12 +231,12 @@ fn set_positional_params(params: &[String], state: &mut ShellState) {
 
     // Set new positional parameters.
     for (i, val) in params.iter().enumerate() {
-        state.set_var(
+        let _ = state.set_var(
             &(i + 1).to_string(),
             crate::engine::state::Variable::new_string(val.clone()),
         );
     }
-    state.set_var(
+    let _ = state.set_var(
         "#",
         crate::engine::state::Variable::new_string(params.len().to_string()),
     );
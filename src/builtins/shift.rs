@@ -38,12 +38,12 @@ pub fn shift_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult
 
     let params = state.positional_args.clone();
     for (i, val) in params.iter().enumerate() {
-        state.set_var(
+        let _ = state.set_var(
             &(i + 1).to_string(),
             crate::engine::state::Variable::new_string(val.clone()),
         );
     }
-    state.set_var(
+    let _ = state.set_var(
         "#",
         crate::engine::state::Variable::new_string(params.len().to_string()),
     );
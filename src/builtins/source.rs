@@ -55,6 +55,11 @@ pub fn run(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
     }
 
     SOURCE_DEPTH.with(|d| d.set(depth + 1));
+    let saved_lineno = std::mem::replace(&mut state.specials.lineno, 1);
+    state
+        .specials
+        .source_stack
+        .push(path.to_string_lossy().into_owned());
 
     let last_result;
     let last_code: i32;
@@ -62,7 +67,7 @@ pub fn run(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
     // Parse the entire file as a single unit. The parser already handles
     // newlines as command separators and supports multi-line constructs
     // (if/for/while/func blocks) natively — no comma continuations needed.
-    match parser::parse_pipeline(&contents, &state.variables) {
+    match parser::parse_pipeline(&contents, state) {
         Some(entries) => {
             let (res, code) = execute_list(entries, state);
             last_result = res;
@@ -75,6 +80,8 @@ pub fn run(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
     }
 
     SOURCE_DEPTH.with(|d| d.set(depth));
+    state.specials.source_stack.pop();
+    state.specials.lineno = saved_lineno;
 
     (last_result, last_code)
 }
@@ -33,8 +33,8 @@ pub const COMMAND_INFO_EXEC: CommandInfo = CommandInfo {
     run: exec_runner,
 };
 
-pub fn exec_runner(args: &[String], _state: &mut ShellState) -> (ExecutionResult, i32) {
-    match exec(args) {
+pub fn exec_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
+    match exec(args, state) {
         Ok(code) => (ExecutionResult::Exit, code),
         Err(e) => {
             eprintln!("{}", e);
@@ -64,7 +64,7 @@ pub fn clear() {
 ///   it, and return its exit code; the caller should exit the shell.
 ///
 /// If no command is given, `exec` is a no-op (returns success).
-pub fn exec(args: &[String]) -> Result<i32, String> {
+pub fn exec(args: &[String], state: &ShellState) -> Result<i32, String> {
     if args.is_empty() {
         // No command — just succeed (POSIX: `exec` with no args is a no-op).
         return Ok(0);
@@ -73,7 +73,7 @@ pub fn exec(args: &[String]) -> Result<i32, String> {
     let cmd_name = &args[0];
     let cmd_args = &args[1..];
 
-    let resolved: PathBuf = find_executable(cmd_name).unwrap_or_else(|| expand_home(cmd_name));
+    let resolved: PathBuf = find_executable(cmd_name, state).unwrap_or_else(|| expand_home(cmd_name));
 
     // ── Unix: true exec (replaces the process image) ─────────────────
     #[cfg(unix)]
@@ -81,9 +81,13 @@ pub fn exec(args: &[String]) -> Result<i32, String> {
         use std::os::unix::process::CommandExt;
 
         // Restore default signal handling before exec-ing.
-        let err = Command::new(&resolved).args(cmd_args).exec(); // never returns on success
+        let err = Command::new(&resolved)
+            .args(cmd_args)
+            .env_clear()
+            .envs(state.exported_env())
+            .exec(); // never returns on success
 
-        return Err(format!("cerf: exec: {}: {}", cmd_name, err));
+        Err(format!("cerf: exec: {}: {}", cmd_name, err))
     }
 
     // ── Windows: spawn + exit (best-effort emulation) ────────────────
@@ -103,6 +107,7 @@ pub fn exec(args: &[String]) -> Result<i32, String> {
         };
 
         command.args(cmd_args);
+        command.env_clear().envs(state.exported_env());
 
         match command.spawn() {
             Ok(mut child) => {
@@ -74,6 +74,7 @@ fn set_tether(args: &[String], state: &mut ShellState, tether: bool) -> i32 {
                     }
                     #[cfg(unix)]
                     {
+                        let _ = job;
                         eprintln!("cerf: tether/untether is not supported on Unix.");
                         code = 1;
                     }
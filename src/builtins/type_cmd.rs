@@ -1,6 +1,5 @@
 use crate::builtins::registry::CommandInfo;
 use crate::engine::state::{ExecutionResult, ShellState};
-use std::collections::HashMap;
 
 pub const COMMAND_INFO: CommandInfo = CommandInfo {
     name: "sys.type",
@@ -10,14 +9,14 @@ pub const COMMAND_INFO: CommandInfo = CommandInfo {
 };
 
 pub fn type_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
-    run(args, &state.aliases);
+    run(args, state);
     (ExecutionResult::KeepRunning, 0)
 }
 
 /// Return the type description for a single command name.
-pub fn type_of(cmd: &str, aliases: &HashMap<String, String>) -> String {
+pub fn type_of(cmd: &str, state: &ShellState) -> String {
     // 1. Check aliases first (they shadow everything else, just like bash).
-    if let Some(value) = aliases.get(cmd) {
+    if let Some(value) = state.aliases.get(cmd) {
         return format!("{} is aliased to `{}`", cmd, value);
     }
 
@@ -32,20 +31,20 @@ pub fn type_of(cmd: &str, aliases: &HashMap<String, String>) -> String {
     }
 
     // 3. Search PATH and other locations.
-    if let Some(path) = crate::engine::find_executable(cmd) {
+    if let Some(path) = crate::engine::find_executable(cmd, state) {
         return format!("{} is {}", cmd, path.display());
     }
 
     format!("cerf: type: {}: not found", cmd)
 }
 
-pub fn run(args: &[String], aliases: &HashMap<String, String>) {
+pub fn run(args: &[String], state: &ShellState) {
     if args.is_empty() {
         return;
     }
 
     for cmd in args {
-        let desc = type_of(cmd, aliases);
+        let desc = type_of(cmd, state);
         if desc.starts_with("cerf: type:") {
             eprintln!("{}", desc);
         } else {
@@ -60,26 +59,26 @@ mod tests {
 
     #[test]
     fn test_type_of_keyword() {
-        let aliases = HashMap::new();
-        assert_eq!(type_of("if", &aliases), "if is a shell keyword");
-        assert_eq!(type_of("while", &aliases), "while is a shell keyword");
-        assert_eq!(type_of("for", &aliases), "for is a shell keyword");
-        assert_eq!(type_of("{", &aliases), "{ is a shell keyword");
-        assert_eq!(type_of("!", &aliases), "! is a shell keyword");
+        let state = ShellState::new();
+        assert_eq!(type_of("if", &state), "if is a shell keyword");
+        assert_eq!(type_of("while", &state), "while is a shell keyword");
+        assert_eq!(type_of("for", &state), "for is a shell keyword");
+        assert_eq!(type_of("{", &state), "{ is a shell keyword");
+        assert_eq!(type_of("!", &state), "! is a shell keyword");
     }
 
     #[test]
     fn test_type_of_builtin() {
-        let aliases = HashMap::new();
+        let state = ShellState::new();
         // Internal names are prefixed
-        assert_eq!(type_of("dir.cd", &aliases), "dir.cd is a shell builtin");
-        assert_eq!(type_of("io.echo", &aliases), "io.echo is a shell builtin");
+        assert_eq!(type_of("dir.cd", &state), "dir.cd is a shell builtin");
+        assert_eq!(type_of("io.echo", &state), "io.echo is a shell builtin");
     }
 
     #[test]
     fn test_type_of_alias() {
-        let mut aliases = HashMap::new();
-        aliases.insert("cd".to_string(), "dir.cd".to_string());
-        assert_eq!(type_of("cd", &aliases), "cd is aliased to `dir.cd`");
+        let mut state = ShellState::new();
+        state.aliases.insert("cd".to_string(), "dir.cd".to_string());
+        assert_eq!(type_of("cd", &state), "cd is aliased to `dir.cd`");
     }
 }
@@ -1,6 +1,5 @@
 use crate::builtins::registry::CommandInfo;
 use crate::engine::state::{ExecutionResult, ShellState};
-use std::collections::HashMap;
 
 pub const COMMAND_INFO: CommandInfo = CommandInfo {
     name: "env.unset",
@@ -10,25 +9,22 @@ pub const COMMAND_INFO: CommandInfo = CommandInfo {
 };
 
 pub fn unset_runner(args: &[String], state: &mut ShellState) -> (ExecutionResult, i32) {
-    run(args, &mut state.variables);
-    (ExecutionResult::KeepRunning, 0)
+    (ExecutionResult::KeepRunning, run(args, state))
 }
 
 /// Run the `unset` builtin.
 ///
 /// Behaviour:
 /// - `unset name …` → remove each named variable from shell and environment
-pub fn run(args: &[String], variables: &mut HashMap<String, crate::engine::state::Variable>) {
-    if args.is_empty() {
-        return;
-    }
-
+/// - a readonly variable is kept, with an error and status 1
+pub fn run(args: &[String], state: &mut ShellState) -> i32 {
+    let mut status = 0;
     for arg in args {
         // Bash allows 'unset' to fail silently if the variable doesn't exist.
-        // It also removes it from the environment.
-        variables.remove(arg);
-        unsafe {
-            std::env::remove_var(arg);
+        if state.unset_var(arg).is_err() {
+            eprintln!("cerf: unset: {}: cannot unset: readonly variable", arg);
+            status = 1;
         }
     }
+    status
 }
@@ -0,0 +1,218 @@
+use crate::builtins::set;
+use crate::engine::ShellState;
+
+pub const USAGE: &str = "\
+Usage: cerf [option ...] [script-file [argument ...]]
+       cerf [option ...] -c command [argument ...]
+
+Options:
+  -c command        Read and execute COMMAND, then exit.
+  -e, -u, -x, ...   Enable a shell option, exactly like `env.set -e`.
+  +e, +u, +x, ...   Disable a shell option.
+  -o option         Enable the named shell option (see `env.set -o`).
+  +o option         Disable the named shell option.
+  -n                Read commands and check their syntax without executing them.
+  -l, --login       Act as a login shell.
+  --noprofile       Do not read the login profile.
+  --norc            Do not read the interactive startup file.
+  --rcfile <path>   Read PATH instead of the default interactive startup file.
+  --help            Print this help and exit.
+  --version         Print the version and exit.
+  --                End of options; the remaining arguments are the script and its arguments.";
+
+/// Command-line options for the `cerf` binary.
+#[derive(Debug, Default, PartialEq)]
+pub struct Cli {
+    /// The command string passed with `-c`.
+    pub command: Option<String>,
+    /// A script file to execute instead of starting an interactive session.
+    pub script: Option<String>,
+    /// Positional parameters for the command or script (`$1`, `$2`, …).
+    pub args: Vec<String>,
+    /// Shell options to apply, in order, as `(long-name, enable)` pairs.
+    pub options: Vec<(&'static str, bool)>,
+    pub login: bool,
+    pub noprofile: bool,
+    pub norc: bool,
+    pub rcfile: Option<String>,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Cli {
+    /// Whether the shell should run the interactive read-eval loop.
+    pub fn is_interactive(&self) -> bool {
+        self.command.is_none() && self.script.is_none()
+    }
+
+    /// Apply the parsed `-e`/`-o …` style options to the shell state.
+    pub fn apply_options(&self, state: &mut ShellState) {
+        for (name, enable) in &self.options {
+            // Names were validated while parsing, so this cannot fail.
+            let _ = set::set_option_by_name(name, *enable, state);
+        }
+    }
+}
+
+/// Parse the process arguments (excluding `argv[0]`).
+///
+/// Returns a human-readable message for unknown or malformed flags.
+pub fn parse(args: &[String]) -> Result<Cli, String> {
+    let mut cli = Cli::default();
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = args[i].as_str();
+        i += 1;
+
+        match arg {
+            "--" => break,
+            "--help" => cli.help = true,
+            "--version" => cli.version = true,
+            "--login" => cli.login = true,
+            "--noprofile" => cli.noprofile = true,
+            "--norc" => cli.norc = true,
+            "--rcfile" => {
+                let path = args
+                    .get(i)
+                    .ok_or("--rcfile: option requires an argument")?;
+                cli.rcfile = Some(path.clone());
+                i += 1;
+            }
+            _ if arg.starts_with("--rcfile=") => {
+                cli.rcfile = Some(arg["--rcfile=".len()..].to_string());
+            }
+            _ if arg.starts_with("--") => {
+                return Err(format!("{}: invalid option", arg));
+            }
+            _ if (arg.starts_with('-') || arg.starts_with('+')) && arg.len() > 1 => {
+                let enable = arg.starts_with('-');
+                for ch in arg[1..].chars() {
+                    match ch {
+                        'c' if enable => {
+                            let command = args
+                                .get(i)
+                                .ok_or("-c: option requires an argument")?;
+                            cli.command = Some(command.clone());
+                            i += 1;
+                        }
+                        'l' if enable => cli.login = true,
+                        'o' => {
+                            let name = args.get(i).ok_or_else(|| {
+                                format!("{}o: option requires an argument", &arg[..1])
+                            })?;
+                            let name = set::option_names()
+                                .iter()
+                                .find(|n| **n == name.as_str())
+                                .ok_or_else(|| format!("{}: invalid option name", name))?;
+                            cli.options.push((name, enable));
+                            i += 1;
+                        }
+                        _ => {
+                            let name = set::option_for_char(ch).ok_or_else(|| {
+                                format!("{}{}: invalid option", &arg[..1], ch)
+                            })?;
+                            cli.options.push((name, enable));
+                        }
+                    }
+                }
+            }
+            _ => {
+                // First operand ends option processing.
+                i -= 1;
+                break;
+            }
+        }
+    }
+
+    let mut rest = args[i..].iter().cloned();
+    if cli.command.is_none() {
+        cli.script = rest.next();
+    }
+    cli.args = rest.collect();
+
+    Ok(cli)
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_strs(args: &[&str]) -> Result<Cli, String> {
+        let owned: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        parse(&owned)
+    }
+
+    #[test]
+    fn test_no_args_is_interactive() {
+        let cli = parse_strs(&[]).unwrap();
+        assert!(cli.is_interactive());
+        assert_eq!(cli, Cli::default());
+    }
+
+    #[test]
+    fn test_command_with_positional_args() {
+        let cli = parse_strs(&["-c", "echo $1", "a", "b"]).unwrap();
+        assert_eq!(cli.command.as_deref(), Some("echo $1"));
+        assert_eq!(cli.script, None);
+        assert_eq!(cli.args, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_clustered_short_flags() {
+        let cli = parse_strs(&["-lex", "-c", "true"]).unwrap();
+        assert!(cli.login);
+        assert_eq!(cli.options, vec![("errexit", true), ("xtrace", true)]);
+        assert_eq!(cli.command.as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn test_long_and_plus_options() {
+        let cli = parse_strs(&["-o", "nounset", "+o", "noglob", "+e"]).unwrap();
+        assert_eq!(
+            cli.options,
+            vec![("nounset", true), ("noglob", false), ("errexit", false)]
+        );
+    }
+
+    #[test]
+    fn test_startup_file_flags() {
+        let cli = parse_strs(&["--login", "--noprofile", "--rcfile", "/tmp/rc"]).unwrap();
+        assert!(cli.login && cli.noprofile);
+        assert_eq!(cli.rcfile.as_deref(), Some("/tmp/rc"));
+
+        let cli = parse_strs(&["--norc", "--rcfile=/tmp/other"]).unwrap();
+        assert!(cli.norc);
+        assert_eq!(cli.rcfile.as_deref(), Some("/tmp/other"));
+    }
+
+    #[test]
+    fn test_double_dash_ends_options() {
+        let cli = parse_strs(&["-n", "--", "-script.cf", "-x"]).unwrap();
+        assert_eq!(cli.options, vec![("noexec", true)]);
+        assert_eq!(cli.script.as_deref(), Some("-script.cf"));
+        assert_eq!(cli.args, vec!["-x"]);
+    }
+
+    #[test]
+    fn test_script_stops_option_parsing() {
+        let cli = parse_strs(&["build.cf", "-e", "--norc"]).unwrap();
+        assert_eq!(cli.script.as_deref(), Some("build.cf"));
+        assert_eq!(cli.args, vec!["-e", "--norc"]);
+        assert!(cli.options.is_empty() && !cli.norc);
+    }
+
+    #[test]
+    fn test_invalid_flags() {
+        assert_eq!(parse_strs(&["-z"]).unwrap_err(), "-z: invalid option");
+        assert_eq!(parse_strs(&["--bogus"]).unwrap_err(), "--bogus: invalid option");
+        assert_eq!(
+            parse_strs(&["-o", "nope"]).unwrap_err(),
+            "nope: invalid option name"
+        );
+        assert!(parse_strs(&["-c"]).is_err());
+        assert!(parse_strs(&["--rcfile"]).is_err());
+    }
+}
@@ -0,0 +1,586 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use rustyline::completion::Pair;
+
+use crate::builtins::registry::BUILTINS;
+use crate::engine::bookmarks;
+use crate::engine::path::{expand_tilde, normalize_path};
+use crate::engine::state::{CompletionSpec, VarValue, Variable};
+use crate::engine::{ShellState, execute_list};
+use crate::parser::expand_vars;
+
+use super::{COMMAND_KEYWORDS, is_assignment};
+
+/// Builtins whose arguments are job specifications (`%1`, `%2`, …).
+const JOB_COMMANDS: &[&str] = &["job.fg", "job.bg", "job.kill"];
+
+/// Characters that force a completed word to be quoted.
+const SPECIAL_CHARS: &[char] = &[' ', '\t', '"', '\'', ';', '&', '|', '<', '>', '(', ')'];
+
+/// A lexed piece of the line before the cursor.
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    /// `|`, `;`, `&`, `(`, `)` or a newline: a new command starts after it.
+    Separator,
+    /// `<` or `>`: the next word is a file name.
+    Redirect,
+}
+
+/// Compute completions for the word under the cursor.
+///
+/// Returns the byte offset where the replaced text starts, and the
+/// candidates sorted by name. The state is mutable because `sys.complete -F`
+/// functions run here.
+pub fn complete(line: &str, pos: usize, state: &mut ShellState) -> (usize, Vec<Pair>) {
+    let before = &line[..pos];
+    let (tokens, start, word) = tokenize(before);
+    let raw = &before[start..];
+
+    // Words of the command being completed, starting with its name.
+    let mut words: Vec<&str> = Vec::new();
+    let mut after_redirect = false;
+    for token in &tokens {
+        match token {
+            Token::Separator => {
+                words.clear();
+                after_redirect = false;
+            }
+            Token::Redirect => after_redirect = true,
+            Token::Word(_) if after_redirect => after_redirect = false,
+            Token::Word(w) if w == "{" || w == "}" => words.clear(),
+            Token::Word(w)
+                if words.is_empty()
+                    && (COMMAND_KEYWORDS.contains(&w.as_str()) || is_assignment(w)) => {}
+            Token::Word(w) => words.push(w),
+        }
+    }
+
+    if let Some(dollar) = raw.rfind('$') {
+        let name = &raw[dollar + 1..];
+        let braced = name.starts_with('{');
+        let name = name.trim_start_matches('{');
+        if name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            let offset = start + dollar + 1 + braced as usize;
+            return (offset, complete_variables(name, braced, state));
+        }
+    }
+
+    if after_redirect {
+        return (start, complete_paths(&word, PathKind::Any));
+    }
+
+    let Some(&command) = words.first() else {
+        if word.contains(std::path::is_separator) || word.starts_with('~') {
+            return (start, complete_paths(&word, PathKind::Executables));
+        }
+        return (start, complete_commands(&word, state));
+    };
+
+    let resolved = state
+        .aliases
+        .get(command)
+        .and_then(|a| a.split_whitespace().next())
+        .unwrap_or(command);
+
+    let spec = state
+        .completions
+        .get(command)
+        .or_else(|| state.completions.get(resolved))
+        .cloned();
+    if let Some(spec) = spec {
+        let comp_words: Vec<String> = words
+            .iter()
+            .map(|w| w.to_string())
+            .chain(std::iter::once(word.clone()))
+            .collect();
+        return (
+            start,
+            complete_from_spec(&spec, &comp_words, line, pos, state),
+        );
+    }
+
+    if word.starts_with('%') && JOB_COMMANDS.contains(&resolved) {
+        return (start, complete_jobs(&word, state));
+    }
+
+    match (resolved, words.len()) {
+        ("dir.mark", 1) => {
+            let subcommands = ["add", "list", "rm"]
+                .into_iter()
+                .filter(|c| c.starts_with(&word));
+            return (
+                start,
+                candidates(subcommands.map(|c| Match::word(c).into_pair())),
+            );
+        }
+        ("dir.mark", _) if words[1] == "rm" => return (start, complete_bookmarks(&word, " ")),
+        ("dir.go", 1) if !word.contains(std::path::is_separator) => {
+            return (start, complete_bookmarks(&word, "/"));
+        }
+        ("dir.go", 1) => {
+            // Complete inside the bookmark, then drop the `~` again.
+            let found = path_matches(&format!("~{}", word), PathKind::Dirs);
+            let found = found.into_iter().map(|m| Match {
+                text: m.text[1..].to_string(),
+                ..m
+            });
+            return (start, candidates(found.map(Match::into_pair)));
+        }
+        _ => {}
+    }
+
+    (start, complete_paths(&word, PathKind::Any))
+}
+
+/// Apply a `sys.complete` spec. `words` ends with the word being completed.
+fn complete_from_spec(
+    spec: &CompletionSpec,
+    words: &[String],
+    line: &str,
+    pos: usize,
+    state: &mut ShellState,
+) -> Vec<Pair> {
+    let word = words.last().map(String::as_str).unwrap_or_default();
+    let mut found: Vec<Match> = Vec::new();
+
+    if let Some(list) = &spec.words {
+        let list = expand_vars(list, state);
+        found.extend(
+            list.split_whitespace()
+                .filter(|w| w.starts_with(word))
+                .map(Match::word),
+        );
+    }
+    if spec.dirs {
+        found.extend(path_matches(word, PathKind::Dirs));
+    } else if spec.files {
+        found.extend(path_matches(word, PathKind::Any));
+    }
+    if let Some(function) = &spec.function {
+        let reply = run_completion_function(function, words, line, pos, state);
+        found.extend(reply.iter().map(|w| Match::word(w)));
+    }
+
+    if let Some(filter) = &spec.filter {
+        let (keep_matching, pattern) = match filter.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, filter.as_str()),
+        };
+        if let Ok(pattern) = glob::Pattern::new(pattern) {
+            found.retain(|m| pattern.matches(&m.text) == keep_matching);
+        }
+    }
+
+    candidates(found.into_iter().map(Match::into_pair))
+}
+
+/// Run a `-F` completion function and return the contents of `COMPREPLY`.
+fn run_completion_function(
+    name: &str,
+    words: &[String],
+    line: &str,
+    pos: usize,
+    state: &mut ShellState,
+) -> Vec<String> {
+    let Some(body) = state.functions.get(name).cloned() else {
+        return Vec::new();
+    };
+
+    // The COMP_* variables and COMPREPLY live in a scope of their own, so
+    // that variables of the same name are left as they were.
+    state.push_scope();
+    let cword = words.len().saturating_sub(1);
+    for (var, value) in [
+        ("COMP_WORDS", Variable::new_array(words.to_vec())),
+        ("COMP_CWORD", Variable::new_string(cword.to_string())),
+        ("COMP_LINE", Variable::new_string(line.to_string())),
+        ("COMP_POINT", Variable::new_string(pos.to_string())),
+        ("COMPREPLY", Variable::new_array(Vec::new())),
+    ] {
+        let _ = state.declare_var(var, value, false);
+    }
+
+    let saved_code = state.last_exit_code;
+    let _ = execute_list(body, state);
+    state.last_exit_code = saved_code;
+
+    let reply = match state.get_var("COMPREPLY").map(|v| v.into_owned().value) {
+        Some(VarValue::Array(items)) => items,
+        Some(VarValue::String(s)) => s.split_whitespace().map(String::from).collect(),
+        None => Vec::new(),
+    };
+    state.pop_scope();
+    reply
+}
+
+/// Split `line` into tokens. Also returns the byte offset where the word
+/// under the cursor begins and that word with its quotes removed.
+fn tokenize(line: &str) -> (Vec<Token>, usize, String) {
+    let mut tokens = Vec::new();
+    let mut word: Option<(usize, String)> = None;
+    let mut quote: Option<char> = None;
+
+    for (i, c) in line.char_indices() {
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            } else if let Some((_, w)) = &mut word {
+                w.push(c);
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => {
+                word.get_or_insert((i, String::new()));
+                quote = Some(c);
+            }
+            ' ' | '\t' => tokens.extend(word.take().map(|(_, w)| Token::Word(w))),
+            '|' | ';' | '&' | '(' | ')' | '\n' => {
+                tokens.extend(word.take().map(|(_, w)| Token::Word(w)));
+                tokens.push(Token::Separator);
+            }
+            '<' | '>' => {
+                tokens.extend(word.take().map(|(_, w)| Token::Word(w)));
+                tokens.push(Token::Redirect);
+            }
+            _ => word.get_or_insert((i, String::new())).1.push(c),
+        }
+    }
+
+    match word {
+        Some((start, current)) => (tokens, start, current),
+        None => (tokens, line.len(), String::new()),
+    }
+}
+
+/// Builtins, aliases, functions and executables on `PATH`.
+fn complete_commands(prefix: &str, state: &ShellState) -> Vec<Pair> {
+    let mut names: Vec<String> = Vec::new();
+    names.extend(BUILTINS.iter().map(|b| b.name.to_string()));
+    names.extend(state.aliases.keys().cloned());
+    names.extend(state.functions.keys().cloned());
+
+    if let Some(path) = state.get_var_string("PATH") {
+        for dir in std::env::split_paths(&path) {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with(prefix) && is_executable(&entry.path()) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+
+    candidates(
+        names
+            .into_iter()
+            .filter(|n| n.starts_with(prefix))
+            .map(|n| Match::word(&n).into_pair()),
+    )
+}
+
+/// Shell variable names, including those in local scopes.
+fn complete_variables(prefix: &str, braced: bool, state: &ShellState) -> Vec<Pair> {
+    let names = state
+        .variables
+        .keys()
+        .chain(state.scopes.iter().flat_map(|s| s.keys()))
+        .filter(|n| n.starts_with(prefix));
+    let close = if braced { "}" } else { "" };
+    candidates(names.map(|n| (n.clone(), format!("{}{}", n, close))))
+}
+
+/// `%N` for every job, displayed with the job's command line.
+fn complete_jobs(prefix: &str, state: &ShellState) -> Vec<Pair> {
+    candidates(
+        state
+            .jobs
+            .values()
+            .map(|job| (format!("%{}", job.id), job))
+            .filter(|(spec, _)| spec.starts_with(prefix))
+            .map(|(spec, job)| (format!("{}  {}", spec, job.command), format!("{} ", spec))),
+    )
+}
+
+/// Which entries path completion offers.
+#[derive(Clone, Copy, PartialEq)]
+enum PathKind {
+    Any,
+    /// Directories and executable files (command position).
+    Executables,
+    Dirs,
+}
+
+/// A candidate before it is turned into a [`Pair`].
+struct Match {
+    /// The unquoted text to insert.
+    text: String,
+    /// Set for paths: the file name to display, and whether it is a directory.
+    path: Option<(String, bool)>,
+}
+
+impl Match {
+    fn word(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            path: None,
+        }
+    }
+
+    /// `(display, replacement)`. Directories get a trailing separator,
+    /// everything else a trailing space.
+    fn into_pair(self) -> (String, String) {
+        match self.path {
+            Some((name, true)) => (format!("{}/", name), quote(&format!("{}/", self.text))),
+            Some((name, false)) => (name, format!("{} ", quote(&self.text))),
+            None => (self.text.clone(), format!("{} ", quote(&self.text))),
+        }
+    }
+}
+
+/// `dir.mark` bookmark names starting with `prefix`, followed by `suffix`.
+fn complete_bookmarks(prefix: &str, suffix: &str) -> Vec<Pair> {
+    candidates(
+        bookmarks::all()
+            .into_keys()
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| (name.clone(), format!("{}{}", name, suffix))),
+    )
+}
+
+/// Files and directories matching `word`, which may start with `~` or a
+/// `~name` bookmark.
+fn complete_paths(word: &str, kind: PathKind) -> Vec<Pair> {
+    if let Some(prefix) = word.strip_prefix('~')
+        && !word.contains(std::path::is_separator)
+    {
+        let home = prefix
+            .is_empty()
+            .then(|| ("~/".to_string(), "~/".to_string()));
+        let marks = complete_bookmarks(prefix, "/")
+            .into_iter()
+            .map(|p| (format!("~{}", p.display), format!("~{}", p.replacement)));
+        return candidates(home.into_iter().chain(marks));
+    }
+    candidates(path_matches(word, kind).into_iter().map(Match::into_pair))
+}
+
+fn path_matches(word: &str, kind: PathKind) -> Vec<Match> {
+    let (dir_part, prefix) = match word.rfind(std::path::is_separator) {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word),
+    };
+    let search_dir = if dir_part.is_empty() {
+        Path::new(".").to_path_buf()
+    } else {
+        let dir = expand_tilde(dir_part).unwrap_or_else(|| dir_part.to_string());
+        normalize_path(Path::new(&dir))
+    };
+    let Ok(entries) = fs::read_dir(&search_dir) else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+            continue;
+        }
+        let path = entry.path();
+        let is_dir = path.is_dir();
+        let wanted = match kind {
+            PathKind::Any => true,
+            PathKind::Executables => is_dir || is_executable(&path),
+            PathKind::Dirs => is_dir,
+        };
+        if wanted {
+            found.push(Match {
+                text: format!("{}{}", dir_part, name),
+                path: Some((name, is_dir)),
+            });
+        }
+    }
+    found
+}
+
+/// Turn `(display, replacement)` pairs into sorted, de-duplicated candidates.
+fn candidates(pairs: impl Iterator<Item = (String, String)>) -> Vec<Pair> {
+    let unique: BTreeMap<String, String> = pairs.map(|(d, r)| (r, d)).collect();
+    unique
+        .into_iter()
+        .map(|(replacement, display)| Pair {
+            display,
+            replacement,
+        })
+        .collect()
+}
+
+/// Quote `s` if it contains characters the parser would split on.
+fn quote(s: &str) -> String {
+    if !s.contains(SPECIAL_CHARS) {
+        s.to_string()
+    } else if s.contains('\'') {
+        format!("\"{}\"", s)
+    } else {
+        format!("'{}'", s)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    path.is_file()
+        && path.extension().is_some_and(|ext| {
+            let ext = format!(".{}", ext.to_string_lossy());
+            pathext.split(';').any(|e| e.eq_ignore_ascii_case(&ext))
+        })
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::state::Job;
+
+    fn replacements(line: &str, state: &mut ShellState) -> (usize, Vec<String>) {
+        let (start, pairs) = complete(line, line.len(), state);
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn test_command_position() {
+        let mut state = ShellState::new();
+        state.functions.insert("fsync_all".to_string(), Vec::new());
+
+        let (start, words) = replacements("echo hi | fs.c", &mut state);
+        assert_eq!(start, 10);
+        assert_eq!(words, vec!["fs.cat ", "fs.cp "]);
+
+        let (_, words) = replacements("X=1 fsy", &mut state);
+        assert_eq!(words, vec!["fsync_all "]);
+
+        let (_, words) = replacements("if true { mkd", &mut state);
+        assert!(words.contains(&"mkdir ".to_string()));
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut state = ShellState::new();
+        state
+            .set_var("CERF_COMPLETE_ME", Variable::new_string("x".to_string()))
+            .unwrap();
+
+        let (start, words) = replacements("echo pre$CERF_COMPLETE", &mut state);
+        assert_eq!(start, 9);
+        assert_eq!(words, vec!["CERF_COMPLETE_ME"]);
+
+        let (_, words) = replacements("echo ${CERF_COMPLETE", &mut state);
+        assert_eq!(words, vec!["CERF_COMPLETE_ME}"]);
+    }
+
+    #[test]
+    fn test_job_specs() {
+        let mut state = ShellState::new();
+        state.jobs.insert(
+            3,
+            Job {
+                id: 3,
+                pgid: 0,
+                #[cfg(windows)]
+                job_handle: 0,
+                command: "sleep 10".to_string(),
+                processes: Vec::new(),
+                reported_done: false,
+            },
+        );
+
+        let (_, pairs) = complete("fg %", 4, &mut state);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].replacement, "%3 ");
+        assert_eq!(pairs[0].display, "%3  sleep 10");
+
+        // Other commands complete files instead.
+        assert!(complete("echo %", 6, &mut state).1.is_empty());
+    }
+
+    #[test]
+    fn test_completion_specs() {
+        let mut state = ShellState::new();
+        state
+            .set_var(
+                "CERF_TARGETS",
+                Variable::new_string("build bench".to_string()),
+            )
+            .unwrap();
+        state.completions.insert(
+            "mk".to_string(),
+            CompletionSpec {
+                words: Some("$CERF_TARGETS test".to_string()),
+                filter: Some("bench".to_string()),
+                ..Default::default()
+            },
+        );
+        let (_, words) = replacements("mk b", &mut state);
+        assert_eq!(words, vec!["build "]);
+
+        let body = crate::parser::parse_pipeline(
+            "env.declare -a COMPREPLY=\"(one $$COMP_CWORD)\"",
+            &state.variables,
+        )
+        .unwrap();
+        state.functions.insert("_tool".to_string(), body);
+        state.completions.insert(
+            "tool".to_string(),
+            CompletionSpec {
+                function: Some("_tool".to_string()),
+                ..Default::default()
+            },
+        );
+        let mut line = Variable::new_string("mine".to_string());
+        line.readonly = true;
+        state.set_var("COMP_LINE", line).unwrap();
+        let (_, words) = replacements("tool x ", &mut state);
+        assert_eq!(words, vec!["2 ", "one "]);
+        assert!(state.get_var("COMP_WORDS").is_none());
+        assert!(state.get_var("COMPREPLY").is_none());
+        assert_eq!(state.get_var_string("COMP_LINE").as_deref(), Some("mine"));
+    }
+
+    #[test]
+    fn test_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub dir")).unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+        let base = dir.path().to_string_lossy().to_string();
+        let mut state = ShellState::new();
+
+        let (_, words) = replacements(&format!("cat {}/", base), &mut state);
+        assert_eq!(
+            words,
+            vec![
+                format!("'{}/sub dir/'", base),
+                format!("{}/notes.txt ", base)
+            ]
+        );
+
+        let (_, words) = replacements(&format!("ls '{}/sub d", base), &mut state);
+        assert_eq!(words, vec![format!("'{}/sub dir/'", base)]);
+
+        let (_, words) = replacements(&format!("cat < {}/.h", base), &mut state);
+        assert_eq!(words, vec![format!("{}/.hidden ", base)]);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::builtins::registry::find_command;
+use crate::engine::{ShellState, find_executable};
+use crate::parser::{is_reserved_word, parse_arg};
+
+use super::multiline::PADDING;
+use super::{COMMAND_KEYWORDS, is_assignment};
+
+/// Highlight classes, each with the shell variable that overrides its
+/// colour and the default SGR parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Style {
+    Command,
+    Unknown,
+    Keyword,
+    String,
+    Variable,
+    Operator,
+    Error,
+}
+
+impl Style {
+    fn variable(self) -> &'static str {
+        match self {
+            Style::Command => "CERF_COLOR_COMMAND",
+            Style::Unknown => "CERF_COLOR_UNKNOWN",
+            Style::Keyword => "CERF_COLOR_KEYWORD",
+            Style::String => "CERF_COLOR_STRING",
+            Style::Variable => "CERF_COLOR_VARIABLE",
+            Style::Operator => "CERF_COLOR_OPERATOR",
+            Style::Error => "CERF_COLOR_ERROR",
+        }
+    }
+
+    fn default_sgr(self) -> &'static str {
+        match self {
+            Style::Command => "32",
+            Style::Unknown => "31",
+            Style::Keyword => "1;34",
+            Style::String => "33",
+            Style::Variable => "36",
+            Style::Operator => "35",
+            Style::Error => "1;37;41",
+        }
+    }
+}
+
+/// Operators, longest first so `&&` wins over `&`.
+const OPERATORS: &[&str] = &["&&", "||", ">>", "|", "&", ";", ">", "<", "\n"];
+
+/// A styled byte range of the line.
+type Span = (usize, usize, Style);
+
+/// Return `line` with ANSI colours for commands, keywords, strings,
+/// variables and operators. The first unbalanced quote or brace is marked
+/// with the error style.
+///
+/// Colours are SGR parameters read from `CERF_COLOR_COMMAND`,
+/// `CERF_COLOR_UNKNOWN`, `CERF_COLOR_KEYWORD`, `CERF_COLOR_STRING`,
+/// `CERF_COLOR_VARIABLE`, `CERF_COLOR_OPERATOR` and `CERF_COLOR_ERROR`
+/// (e.g. `1;32`); an empty value disables that colour.
+pub fn highlight(line: &str, state: &ShellState) -> String {
+    let spans = classify(line, state);
+    if spans.is_empty() {
+        return line.to_string();
+    }
+
+    let mut out = String::with_capacity(line.len() * 2);
+    let mut last = 0;
+    for (start, end, style) in spans {
+        let sgr = state
+            .get_var_string(style.variable())
+            .unwrap_or_else(|| style.default_sgr().to_string());
+        out.push_str(&line[last..start]);
+        if sgr.is_empty() {
+            out.push_str(&line[start..end]);
+        } else {
+            out.push_str(&format!("\x1b[{}m{}\x1b[0m", sgr, &line[start..end]));
+        }
+        last = end;
+    }
+    out.push_str(&line[last..]);
+    out
+}
+
+/// Split `line` into non-overlapping, ordered styled spans.
+fn classify(line: &str, state: &ShellState) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut open_braces: Vec<usize> = Vec::new();
+    let mut unbalanced: Option<usize> = None;
+
+    // Words of the current command; empty means command position.
+    let mut words: Vec<&str> = Vec::new();
+    let mut after_redirect = false;
+    let mut pos = 0;
+
+    while pos < line.len() {
+        let rest = &line[pos..];
+        let ch = rest.chars().next().unwrap_or_default();
+
+        if ch == ' ' || ch == '\t' || ch == '\r' || ch == PADDING {
+            pos += ch.len_utf8();
+            continue;
+        }
+        if ch == '#' && words.is_empty() {
+            break;
+        }
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            if *op != "\n" {
+                spans.push((pos, pos + op.len(), Style::Operator));
+            }
+            if op.starts_with(['>', '<']) {
+                after_redirect = true;
+            } else {
+                words.clear();
+                after_redirect = false;
+            }
+            pos += op.len();
+            continue;
+        }
+
+        let end = match parse_arg(rest) {
+            Ok((after, _)) => pos + rest.len() - after.len(),
+            Err(_) if rest.starts_with("\"\"") || rest.starts_with("''") => pos + 2,
+            Err(_) => {
+                // Only an unterminated quote makes `parse_arg` fail here.
+                unbalanced.get_or_insert(pos);
+                spans.push((pos, pos + 1, Style::Error));
+                if pos + 1 < line.len() {
+                    spans.push((pos + 1, line.len(), Style::String));
+                }
+                break;
+            }
+        };
+        let word = &line[pos..end];
+
+        if after_redirect {
+            after_redirect = false;
+            word_parts(word, pos, &mut spans);
+        } else if word == "{" || word == "}" {
+            if word == "{" {
+                open_braces.push(pos);
+            } else if open_braces.pop().is_none() {
+                unbalanced.get_or_insert(pos);
+            }
+            spans.push((pos, end, Style::Keyword));
+            words.clear();
+        } else if words.is_empty() && COMMAND_KEYWORDS.contains(&word) {
+            spans.push((pos, end, Style::Keyword));
+        } else if words.is_empty() && is_assignment(word) {
+            let eq = word.find('=').unwrap_or(0) + 1;
+            word_parts(&word[eq..], pos + eq, &mut spans);
+        } else if words.is_empty() {
+            let style = if is_reserved_word(word) {
+                Style::Keyword
+            } else if is_known_command(word, state) {
+                Style::Command
+            } else {
+                Style::Unknown
+            };
+            spans.push((pos, end, style));
+            words.push(word);
+        } else {
+            if words.len() == 2 && words[0] == "for" && word == "in" {
+                spans.push((pos, end, Style::Keyword));
+            } else {
+                word_parts(word, pos, &mut spans);
+            }
+            words.push(word);
+        }
+        pos = end;
+    }
+
+    let unbalanced = match (unbalanced, open_braces.first()) {
+        (Some(a), Some(&b)) => Some(a.min(b)),
+        (a, b) => a.or(b.copied()),
+    };
+    if let Some(at) = unbalanced
+        && let Some(span) = spans.iter_mut().find(|(s, _, _)| *s == at)
+    {
+        span.2 = Style::Error;
+    }
+    spans
+}
+
+/// Style the quoted strings and `$VAR` references inside an argument.
+fn word_parts(word: &str, offset: usize, spans: &mut Vec<Span>) {
+    let bytes = word.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' => {
+                let end = word[i + 1..].find('\'').map_or(word.len(), |e| i + e + 2);
+                spans.push((offset + i, offset + end, Style::String));
+                i = end;
+            }
+            b'"' => {
+                let end = word[i + 1..].find('"').map_or(word.len(), |e| i + e + 2);
+                // Variables inside double quotes keep their own colour.
+                let mut start = i;
+                let mut j = i + 1;
+                while j < end {
+                    if let Some(var_end) = variable_end(word, j).filter(|&e| e <= end) {
+                        spans.push((offset + start, offset + j, Style::String));
+                        spans.push((offset + j, offset + var_end, Style::Variable));
+                        start = var_end;
+                        j = var_end;
+                    } else {
+                        j += char_len(word, j);
+                    }
+                }
+                if start < end {
+                    spans.push((offset + start, offset + end, Style::String));
+                }
+                i = end;
+            }
+            _ => match variable_end(word, i) {
+                Some(end) => {
+                    spans.push((offset + i, offset + end, Style::Variable));
+                    i = end;
+                }
+                None => i += char_len(word, i),
+            },
+        }
+    }
+}
+
+/// If a variable reference (`$NAME`, `${NAME}`, `$1`, `$?`) starts at `i`,
+/// return the byte offset just past it.
+fn variable_end(word: &str, i: usize) -> Option<usize> {
+    let rest = word[i..].strip_prefix('$')?;
+    if let Some(inner) = rest.strip_prefix('{') {
+        return Some(inner.find('}').map_or(word.len(), |e| i + e + 3));
+    }
+    let len = match rest.chars().next()? {
+        '?' | '#' | '$' | '0'..='9' => 1,
+        c if c.is_alphabetic() || c == '_' => rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len()),
+        _ => return None,
+    };
+    Some(i + 1 + len)
+}
+
+fn char_len(s: &str, i: usize) -> usize {
+    s[i..].chars().next().map_or(1, char::len_utf8)
+}
+
+fn is_known_command(word: &str, state: &ShellState) -> bool {
+    find_command(word).is_some()
+        || state.aliases.contains_key(word)
+        || state.functions.contains_key(word)
+        || find_executable(word, state).is_some()
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styles(line: &str) -> Vec<(&str, Style)> {
+        let state = ShellState::new();
+        classify(line, &state)
+            .into_iter()
+            .map(|(s, e, style)| (&line[s..e], style))
+            .collect()
+    }
+
+    #[test]
+    fn test_commands_and_operators() {
+        assert_eq!(
+            styles("echo $HOME | nosuchcmd-xyz && ls > out"),
+            vec![
+                ("echo", Style::Command),
+                ("$HOME", Style::Variable),
+                ("|", Style::Operator),
+                ("nosuchcmd-xyz", Style::Unknown),
+                ("&&", Style::Operator),
+                ("ls", Style::Command),
+                (">", Style::Operator),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_and_keywords() {
+        assert_eq!(
+            styles("if true { echo \"hi $USER\" 'x' }"),
+            vec![
+                ("if", Style::Keyword),
+                ("true", Style::Command),
+                ("{", Style::Keyword),
+                ("echo", Style::Command),
+                ("\"hi ", Style::String),
+                ("$USER", Style::Variable),
+                ("\"", Style::String),
+                ("'x'", Style::String),
+                ("}", Style::Keyword),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multibyte_text() {
+        assert_eq!(
+            styles("echo ünï\"cødé $X\""),
+            vec![
+                ("echo", Style::Command),
+                ("\"cødé ", Style::String),
+                ("$X", Style::Variable),
+                ("\"", Style::String),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unbalanced() {
+        assert_eq!(
+            styles("echo \"abc"),
+            vec![
+                ("echo", Style::Command),
+                ("\"", Style::Error),
+                ("abc", Style::String)
+            ]
+        );
+        let spans = styles("while true { if x { echo");
+        assert_eq!(spans[2], ("{", Style::Error));
+        assert_eq!(spans[5], ("{", Style::Keyword));
+    }
+
+    #[test]
+    fn test_colours_from_variables() {
+        let mut state = ShellState::new();
+        state
+            .set_var(
+                "CERF_COLOR_COMMAND",
+                crate::engine::state::Variable::new_string("1;92".to_string()),
+            )
+            .unwrap();
+        state
+            .set_var(
+                "CERF_COLOR_OPERATOR",
+                crate::engine::state::Variable::new_string(String::new()),
+            )
+            .unwrap();
+        assert_eq!(highlight("echo;", &state), "\x1b[1;92mecho\x1b[0m;");
+    }
+}
@@ -0,0 +1,392 @@
+//! Key bindings made with `sys.bind`, and tracking of vi's input modes for
+//! the `\s{vimode}` prompt segment.
+//!
+//! Key sequences use readline's notation: `\C-x` is Control-x, `\M-x` or
+//! `\ex` is Meta (Alt)-x, `\e` is Escape, `\t` Tab, `\r` Enter, and `\e[A`,
+//! `\e[B`, `\e[C`, `\e[D`, `\e[H`, `\e[F`, `\e[3~` … the cursor and editing
+//! keys.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rustyline::{
+    Anchor, At, Cmd, ConditionalEventHandler, EditMode, Event, EventContext, InputMode, KeyCode,
+    KeyEvent, Modifiers, Movement, RepeatCount, Word,
+};
+
+use crate::engine::execute_list;
+use crate::engine::state::{ExecutionResult, ShellState, Variable};
+use crate::parser;
+
+/// Editor commands that keys can be bound to, named as in readline.
+pub const FUNCTIONS: &[&str] = &[
+    "abort",
+    "accept-line",
+    "backward-char",
+    "backward-delete-char",
+    "backward-kill-line",
+    "backward-kill-word",
+    "backward-word",
+    "beginning-of-history",
+    "beginning-of-line",
+    "capitalize-word",
+    "clear-screen",
+    "complete",
+    "delete-char",
+    "downcase-word",
+    "end-of-history",
+    "end-of-line",
+    "forward-char",
+    "forward-search-history",
+    "forward-word",
+    "history-search-backward",
+    "history-search-forward",
+    "insert-newline",
+    "kill-line",
+    "kill-whole-line",
+    "kill-word",
+    "next-history",
+    "previous-history",
+    "quoted-insert",
+    "redraw-current-line",
+    "reverse-search-history",
+    "transpose-chars",
+    "transpose-words",
+    "undo",
+    "unix-line-discard",
+    "unix-word-rubout",
+    "upcase-word",
+    "yank",
+    "yank-pop",
+];
+
+/// The editor command for a name in [`FUNCTIONS`].
+pub fn command(name: &str) -> Option<Cmd> {
+    let cmd = match name {
+        "abort" => Cmd::Abort,
+        "accept-line" => Cmd::AcceptLine,
+        "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+        "backward-delete-char" => Cmd::Kill(Movement::BackwardChar(1)),
+        "backward-kill-line" | "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+        "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+        "backward-word" => Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+        "beginning-of-history" => Cmd::BeginningOfHistory,
+        "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+        "capitalize-word" => Cmd::CapitalizeWord,
+        "clear-screen" => Cmd::ClearScreen,
+        "complete" => Cmd::Complete,
+        "delete-char" => Cmd::Kill(Movement::ForwardChar(1)),
+        "downcase-word" => Cmd::DowncaseWord,
+        "end-of-history" => Cmd::EndOfHistory,
+        "end-of-line" => Cmd::Move(Movement::EndOfLine),
+        "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+        "forward-search-history" => Cmd::ForwardSearchHistory,
+        "forward-word" => Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+        "history-search-backward" => Cmd::HistorySearchBackward,
+        "history-search-forward" => Cmd::HistorySearchForward,
+        "insert-newline" => Cmd::Newline,
+        "kill-line" => Cmd::Kill(Movement::EndOfLine),
+        "kill-whole-line" => Cmd::Kill(Movement::WholeLine),
+        "kill-word" => Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+        "next-history" => Cmd::NextHistory,
+        "previous-history" => Cmd::PreviousHistory,
+        "quoted-insert" => Cmd::QuotedInsert,
+        "redraw-current-line" => Cmd::Repaint,
+        "reverse-search-history" => Cmd::ReverseSearchHistory,
+        "transpose-chars" => Cmd::TransposeChars,
+        "transpose-words" => Cmd::TransposeWords(1),
+        "undo" => Cmd::Undo(1),
+        "unix-word-rubout" => Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+        "upcase-word" => Cmd::UpcaseWord,
+        "yank" => Cmd::Yank(1, Anchor::Before),
+        "yank-pop" => Cmd::YankPop,
+        _ => return None,
+    };
+    Some(cmd)
+}
+
+/// Parse a key sequence such as `\C-x\C-e` or `\e[A`.
+pub fn parse_keys(spec: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut keys = Vec::new();
+    let mut chars = spec.chars().peekable();
+    let mut mods = Modifiers::NONE;
+
+    while let Some(c) = chars.next() {
+        let key = if c != '\\' {
+            KeyEvent::new(c, mods)
+        } else {
+            let Some(esc) = chars.next() else {
+                return Err("trailing backslash".to_string());
+            };
+            match esc {
+                'C' | 'M' if chars.peek() == Some(&'-') => {
+                    chars.next();
+                    mods |= if esc == 'C' {
+                        Modifiers::CTRL
+                    } else {
+                        Modifiers::ALT
+                    };
+                    continue;
+                }
+                'e' if chars.peek() == Some(&'[') => {
+                    chars.next();
+                    let mut code = String::new();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        code.push(digit);
+                    }
+                    let end = chars.next().unwrap_or_default();
+                    let key = match (code.as_str(), end) {
+                        ("", 'A') => KeyCode::Up,
+                        ("", 'B') => KeyCode::Down,
+                        ("", 'C') => KeyCode::Right,
+                        ("", 'D') => KeyCode::Left,
+                        ("", 'H') => KeyCode::Home,
+                        ("", 'F') => KeyCode::End,
+                        ("2", '~') => KeyCode::Insert,
+                        ("3", '~') => KeyCode::Delete,
+                        ("5", '~') => KeyCode::PageUp,
+                        ("6", '~') => KeyCode::PageDown,
+                        _ => return Err(format!("unknown key `\\e[{}{}`", code, end)),
+                    };
+                    KeyEvent(key, mods)
+                }
+                // Escape followed by a key is how terminals send Meta.
+                'e' if chars.peek().is_some() => {
+                    mods |= Modifiers::ALT;
+                    continue;
+                }
+                'e' => KeyEvent::new('\x1b', mods),
+                't' => KeyEvent::new('\t', mods),
+                'r' => KeyEvent::new('\r', mods),
+                'n' => KeyEvent::new('\n', mods),
+                'a' => KeyEvent::new('\x07', mods),
+                '\\' | '"' | '\'' => KeyEvent::new(esc, mods),
+                other => return Err(format!("unknown escape `\\{}`", other)),
+            }
+        };
+        keys.push(KeyEvent::normalize(key));
+        mods = Modifiers::NONE;
+    }
+
+    if !mods.is_empty() {
+        return Err("missing key after modifier".to_string());
+    }
+    match keys.first() {
+        None => Err("empty key sequence".to_string()),
+        // rustyline only reads further keys after Control-x.
+        Some(first) if keys.len() > 1 && *first != KeyEvent::ctrl('X') => {
+            Err("sequences of more than one key must start with \\C-x".to_string())
+        }
+        Some(_) => Ok(keys),
+    }
+}
+
+/// Write `keys` in the notation [`parse_keys`] reads.
+pub fn format_keys(keys: &[KeyEvent]) -> String {
+    let mut out = String::new();
+    for &KeyEvent(code, mods) in keys {
+        if mods.contains(Modifiers::ALT) {
+            out.push_str("\\e");
+        }
+        if mods.contains(Modifiers::CTRL) {
+            out.push_str("\\C-");
+        }
+        match code {
+            KeyCode::Char(c) if mods.contains(Modifiers::CTRL) => out.push(c.to_ascii_lowercase()),
+            KeyCode::Char(c @ ('\\' | '"')) => {
+                out.push('\\');
+                out.push(c);
+            }
+            KeyCode::Char(c) => out.push(c),
+            KeyCode::Esc => out.push_str("\\e"),
+            KeyCode::Tab => out.push_str("\\t"),
+            KeyCode::Enter => out.push_str("\\r"),
+            KeyCode::Up => out.push_str("\\e[A"),
+            KeyCode::Down => out.push_str("\\e[B"),
+            KeyCode::Right => out.push_str("\\e[C"),
+            KeyCode::Left => out.push_str("\\e[D"),
+            KeyCode::Home => out.push_str("\\e[H"),
+            KeyCode::End => out.push_str("\\e[F"),
+            KeyCode::Insert => out.push_str("\\e[2~"),
+            KeyCode::Delete => out.push_str("\\e[3~"),
+            KeyCode::PageUp => out.push_str("\\e[5~"),
+            KeyCode::PageDown => out.push_str("\\e[6~"),
+            other => out.push_str(&format!("{:?}", other)),
+        }
+    }
+    out
+}
+
+/// A shell command to run for a key bound with `sys.bind -x`, with the
+/// line being edited when the key was pressed.
+#[derive(Debug)]
+pub struct ShellCall {
+    pub command: String,
+    pub line: String,
+    pub pos: usize,
+}
+
+/// Key handler for `sys.bind -x`. Key handlers can't run commands, so the
+/// call is left in `pending` and the line is accepted for the read-eval
+/// loop to run it with [`run_shell_call`] and read the line again.
+pub struct ShellBinding {
+    pub command: String,
+    pub pending: Arc<Mutex<Option<ShellCall>>>,
+}
+
+impl ConditionalEventHandler for ShellBinding {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        *self.pending.lock().ok()? = Some(ShellCall {
+            command: self.command.clone(),
+            line: ctx.line().to_string(),
+            pos: ctx.pos(),
+        });
+        Some(Cmd::AcceptLine)
+    }
+}
+
+/// Run a `sys.bind -x` command with `READLINE_LINE` and `READLINE_POINT`
+/// (in characters) set, and return the line split at the cursor as the
+/// command left them. `None` if the command exits the shell.
+pub fn run_shell_call(call: ShellCall, state: &mut ShellState) -> Option<(String, String)> {
+    let point = call.line[..call.pos].chars().count();
+    let _ = state.set_var("READLINE_LINE", Variable::new_string(call.line));
+    let _ = state.set_var("READLINE_POINT", Variable::new_string(point.to_string()));
+
+    // Like `PROMPT_COMMAND`, the binding leaves `$?` alone.
+    let saved_code = state.last_exit_code;
+    let mut result = ExecutionResult::KeepRunning;
+    if let Some(entries) = parser::parse_pipeline(&call.command, state) {
+        result = execute_list(entries, state).0;
+    }
+    state.last_exit_code = saved_code;
+
+    let line = state.get_var_string("READLINE_LINE").unwrap_or_default();
+    let point = state
+        .get_var_string("READLINE_POINT")
+        .and_then(|p| p.parse::<usize>().ok())
+        .unwrap_or(usize::MAX);
+    state.variables.remove("READLINE_LINE");
+    state.variables.remove("READLINE_POINT");
+    if let ExecutionResult::Exit = result {
+        return None;
+    }
+
+    let split = line
+        .char_indices()
+        .nth(point)
+        .map_or(line.len(), |(i, _)| i);
+    let (left, right) = line.split_at(split);
+    Some((left.to_string(), right.to_string()))
+}
+
+/// Follows vi's input mode for the `\s{vimode}` prompt segment. It is
+/// bound to every key but never handles one, so it records the mode the
+/// key is about to switch to rather than the one rustyline reports.
+pub struct ViModeTracker {
+    pub command: Arc<AtomicBool>,
+}
+
+impl ConditionalEventHandler for ViModeTracker {
+    fn handle(
+        &self,
+        evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let Event::KeySeq(keys) = evt else {
+            return None;
+        };
+        if ctx.mode() != EditMode::Vi {
+            return None;
+        }
+        let &KeyEvent(code, mods) = keys.first()?;
+        let command = match ctx.input_mode() {
+            InputMode::Command => !enters_insert(code, mods),
+            // Alt-key runs the key as a command.
+            _ if mods == Modifiers::ALT => !enters_insert(code, Modifiers::NONE),
+            _ => code == KeyCode::Esc,
+        };
+        self.command.store(command, Ordering::Relaxed);
+        None
+    }
+}
+
+/// Whether a vi command-mode key switches to insert (or replace) mode.
+fn enters_insert(code: KeyCode, mods: Modifiers) -> bool {
+    matches!(
+        (code, mods),
+        (
+            KeyCode::Char('a' | 'A' | 'c' | 'C' | 'i' | 'I' | 's' | 'S' | 'R'),
+            Modifiers::NONE
+        ) | (KeyCode::Char('R' | 'S'), Modifiers::CTRL)
+    )
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_keys() {
+        assert_eq!(parse_keys("\\C-g").unwrap(), [KeyEvent::ctrl('G')]);
+        assert_eq!(parse_keys("\\M-f").unwrap(), [KeyEvent::alt('f')]);
+        assert_eq!(parse_keys("\\ef").unwrap(), [KeyEvent::alt('f')]);
+        assert_eq!(
+            parse_keys("\\e[A").unwrap(),
+            [KeyEvent(KeyCode::Up, Modifiers::NONE)]
+        );
+        assert_eq!(
+            parse_keys("\\C-x\\C-e").unwrap(),
+            [KeyEvent::ctrl('X'), KeyEvent::ctrl('E')]
+        );
+
+        for spec in [
+            "\\C-g",
+            "\\ef",
+            "\\e",
+            "\\e[3~",
+            "\\C-x\\C-e",
+            "\\e\\C-h",
+            "\\t",
+            "x",
+        ] {
+            assert_eq!(format_keys(&parse_keys(spec).unwrap()), spec);
+        }
+
+        assert!(parse_keys("").is_err());
+        assert!(parse_keys("\\C-").is_err());
+        assert!(parse_keys("ab").is_err());
+        assert!(parse_keys("\\e[9~").is_err());
+    }
+
+    #[test]
+    fn test_functions_are_commands() {
+        for name in FUNCTIONS {
+            assert!(command(name).is_some(), "{}", name);
+        }
+        assert_eq!(command("self-destruct"), None);
+    }
+
+    #[test]
+    fn test_run_shell_call() {
+        let mut state = ShellState::new();
+        let call = ShellCall {
+            command: "READLINE_LINE=\"git $READLINE_LINE\"; READLINE_POINT=3".to_string(),
+            line: "status".to_string(),
+            pos: 6,
+        };
+        let (left, right) = run_shell_call(call, &mut state).unwrap();
+        assert_eq!((left.as_str(), right.as_str()), ("git", " status"));
+        assert!(state.get_var_string("READLINE_LINE").is_none());
+    }
+}
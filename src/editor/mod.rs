@@ -0,0 +1,298 @@
+mod complete;
+mod highlight;
+pub mod keymap;
+mod multiline;
+pub mod picker;
+mod suggest;
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::config::Configurer;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::{DefaultHistory, History};
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{
+    Context, EditMode, Editor, Event, EventHandler, Helper, KeyCode, KeyEvent, Modifiers,
+};
+
+use crate::engine::ShellState;
+use crate::engine::state::{BindAction, HistoryEntry, KeyBinding};
+use crate::prompt::Prompt;
+
+/// Reserved words after which the next word is still a command name.
+const COMMAND_KEYWORDS: &[&str] = &["if", "elif", "else", "while", "loop", "!", "{", "}"];
+
+/// Whether `word` is a `NAME=value` assignment rather than a command.
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+/// Line-editor helper for the interactive shell.
+pub struct CerfHelper {
+    /// The prompt as it should be printed, including the non-printing
+    /// sequences that were left out of the prompt passed to `readline`.
+    pub display_prompt: String,
+    /// `display_prompt` as drawn in vi command mode.
+    command_prompt: String,
+    /// Whether vi is in command mode, as followed by [`keymap::ViModeTracker`].
+    vi_command: Arc<AtomicBool>,
+    /// `PS2` as printed at the start of each continuation line.
+    continuation_prompt: String,
+    /// The width of `PS2`, which [`multiline::AutoIndent`] pads new lines to.
+    continuation_width: Arc<AtomicUsize>,
+    /// The shell, shared with the read-eval loop. It is only borrowed here
+    /// while `readline` is running.
+    shell: Rc<RefCell<ShellState>>,
+    /// Copy of the history for the Ctrl-R picker, kept up to date by
+    /// [`sync_history`].
+    history: Arc<Mutex<Vec<HistoryEntry>>>,
+    /// The command chosen in the picker, to be run or edited instead of the
+    /// accepted line.
+    picked: Arc<Mutex<Option<picker::Pick>>>,
+    /// A `sys.bind -x` command to run instead of the accepted line.
+    shell_call: Arc<Mutex<Option<keymap::ShellCall>>>,
+    /// The `sys.bind` bindings installed in the editor.
+    bindings: Vec<KeyBinding>,
+}
+
+impl CerfHelper {
+    pub fn new(shell: Rc<RefCell<ShellState>>) -> Self {
+        Self {
+            display_prompt: String::new(),
+            command_prompt: String::new(),
+            vi_command: Arc::default(),
+            continuation_prompt: String::new(),
+            continuation_width: Arc::default(),
+            shell,
+            history: Arc::default(),
+            picked: Arc::default(),
+            shell_call: Arc::default(),
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Use `prompt` for the next line and `continuation` for the lines
+    /// after it. Each line starts in vi insert mode.
+    pub fn set_prompt(&mut self, prompt: &Prompt, continuation: &Prompt) {
+        self.display_prompt = prompt.display.clone();
+        self.command_prompt = prompt.command_display.clone();
+        self.continuation_prompt = continuation.display.clone();
+        self.continuation_width
+            .store(continuation.plain.chars().count(), Ordering::Relaxed);
+        self.vi_command.store(false, Ordering::Relaxed);
+    }
+
+    /// The accepted `line` without the room left for `PS2` on its
+    /// continuation lines.
+    pub fn strip_continuation(&self, line: &str) -> String {
+        let width = self.continuation_width.load(Ordering::Relaxed);
+        multiline::strip_continuation(line, width)
+    }
+
+    /// The command chosen in the Ctrl-R picker, if `readline` returned
+    /// because of it.
+    pub fn take_picked(&self) -> Option<picker::Pick> {
+        self.picked.lock().ok()?.take()
+    }
+
+    /// The `sys.bind -x` command to run, if `readline` returned because its
+    /// key was pressed.
+    pub fn take_shell_call(&self) -> Option<keymap::ShellCall> {
+        self.shell_call.lock().ok()?.take()
+    }
+}
+
+/// Install cerf's key bindings on top of rustyline's defaults.
+pub fn bind_keys(rl: &mut Editor<CerfHelper, DefaultHistory>) {
+    // Right arrow already accepts a suggestion; End and Alt-F do too.
+    rl.bind_sequence(
+        KeyEvent(KeyCode::End, Modifiers::NONE),
+        EventHandler::Conditional(Box::new(suggest::AcceptSuggestion { one_word: false })),
+    );
+    rl.bind_sequence(
+        KeyEvent::alt('f'),
+        EventHandler::Conditional(Box::new(suggest::AcceptSuggestion { one_word: true })),
+    );
+    if let Some(helper) = rl.helper() {
+        let indent = multiline::AutoIndent {
+            continuation_width: Arc::clone(&helper.continuation_width),
+        };
+        let picker = picker::HistoryPicker {
+            history: Arc::clone(&helper.history),
+            picked: Arc::clone(&helper.picked),
+        };
+        let tracker = keymap::ViModeTracker {
+            command: Arc::clone(&helper.vi_command),
+        };
+        rl.bind_sequence(
+            KeyEvent(KeyCode::Enter, Modifiers::NONE),
+            EventHandler::Conditional(Box::new(indent)),
+        );
+        rl.bind_sequence(
+            KeyEvent::ctrl('r'),
+            EventHandler::Conditional(Box::new(picker)),
+        );
+        rl.bind_sequence(Event::Any, EventHandler::Conditional(Box::new(tracker)));
+    }
+}
+
+/// Apply the editing mode chosen with `set -o vi`/`emacs` and the key
+/// bindings made with `sys.bind` since the last line was read.
+pub fn sync_keymap(rl: &mut Editor<CerfHelper, DefaultHistory>, state: &ShellState) {
+    let mode = if state.set_options.contains("vi") {
+        EditMode::Vi
+    } else {
+        EditMode::Emacs
+    };
+    if rl.config_mut().edit_mode() != mode {
+        rl.set_edit_mode(mode);
+    }
+
+    let Some(helper) = rl.helper_mut() else {
+        return;
+    };
+    if helper.bindings == state.key_bindings {
+        return;
+    }
+    // Start over from cerf's own bindings, which a removed binding may
+    // have replaced.
+    let previous = std::mem::replace(&mut helper.bindings, state.key_bindings.clone());
+    let shell_call = Arc::clone(&helper.shell_call);
+    for binding in previous {
+        if let Ok(keys) = keymap::parse_keys(&binding.keys) {
+            rl.unbind_sequence(Event::KeySeq(keys));
+        }
+    }
+    bind_keys(rl);
+    for binding in &state.key_bindings {
+        let Ok(keys) = keymap::parse_keys(&binding.keys) else {
+            continue;
+        };
+        let handler = match &binding.action {
+            BindAction::Function(name) => match keymap::command(name) {
+                Some(cmd) => EventHandler::Simple(cmd),
+                None => continue,
+            },
+            BindAction::Shell(command) => {
+                EventHandler::Conditional(Box::new(keymap::ShellBinding {
+                    command: command.clone(),
+                    pending: Arc::clone(&shell_call),
+                }))
+            }
+        };
+        rl.bind_sequence(Event::KeySeq(keys), handler);
+    }
+}
+
+/// Bring the editor's history (for Up/Down) and the picker's copy up to
+/// date with the shell's, which commands like `history -c` or other
+/// sessions (with `share_history`) may have changed.
+pub fn sync_history(rl: &mut Editor<CerfHelper, DefaultHistory>, state: &ShellState) {
+    if rl.history().len() != state.history.len() {
+        let _ = rl.clear_history();
+        for entry in &state.history {
+            let _ = rl.add_history_entry(entry.line.as_str());
+        }
+    }
+
+    let Some(mut copy) = rl.helper().and_then(|h| h.history.lock().ok()) else {
+        return;
+    };
+    // Usually only new entries were added at the end.
+    let appended = copy.len() <= state.history.len()
+        && copy.last() == copy.len().checked_sub(1).map(|i| &state.history[i]);
+    if appended {
+        let start = copy.len();
+        copy.extend_from_slice(&state.history[start..]);
+    } else {
+        *copy = state.history.clone();
+    }
+}
+
+impl Helper for CerfHelper {}
+
+impl Completer for CerfHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        match self.shell.try_borrow_mut() {
+            Ok(mut state) => Ok(complete::complete(line, pos, &mut state)),
+            Err(_) => Ok((pos, Vec::new())),
+        }
+    }
+}
+
+impl Hinter for CerfHelper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() {
+            return None;
+        }
+        let state = self.shell.try_borrow().ok()?;
+        let cwd = std::env::current_dir().ok();
+        suggest::suggest(line, cwd.as_deref(), &state)
+    }
+}
+
+impl Validator for CerfHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(multiline::validate(ctx.input()))
+    }
+}
+
+impl Highlighter for CerfHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let highlighted = match self.shell.try_borrow() {
+            Ok(state) => Cow::Owned(highlight::highlight(line, &state)),
+            Err(_) => Cow::Borrowed(line),
+        };
+        let width = self.continuation_width.load(Ordering::Relaxed);
+        if width == 0 || !line.contains('\n') {
+            return highlighted;
+        }
+        Cow::Owned(multiline::show_continuation(
+            line,
+            &highlighted,
+            width,
+            &self.continuation_prompt,
+        ))
+    }
+
+    fn highlight_char(&self, line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        // Any edit can change how the whole line is classified.
+        !line.is_empty()
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        // `default` is false for prompts rustyline draws itself (e.g. search).
+        if default && self.vi_command.load(Ordering::Relaxed) && !self.command_prompt.is_empty() {
+            Cow::Borrowed(&self.command_prompt)
+        } else if default && !self.display_prompt.is_empty() {
+            Cow::Borrowed(&self.display_prompt)
+        } else {
+            Cow::Borrowed(prompt)
+        }
+    }
+}
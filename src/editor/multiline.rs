@@ -0,0 +1,171 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rustyline::validate::ValidationResult;
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+
+use crate::parser;
+
+/// Indentation inserted per open brace.
+const INDENT: &str = "    ";
+
+/// What [`AutoIndent`] pads continuation lines with: a no-break space is as
+/// wide as a space, but isn't typed, so text the user typed, pasted or
+/// recalled from history is never taken for padding.
+pub const PADDING: char = '\u{a0}';
+
+/// Keep editing while the buffer is an incomplete construct: open braces,
+/// a trailing `|`, `&&` or `||`, or a trailing `,` continuation.
+pub fn validate(input: &str) -> ValidationResult {
+    if parser::is_incomplete(input) {
+        ValidationResult::Incomplete
+    } else {
+        ValidationResult::Valid(None)
+    }
+}
+
+/// Enter at the end of an incomplete buffer: start a new line indented to
+/// the current brace depth instead of leaving it to the validator, which
+/// would insert a bare newline.
+///
+/// The new line first gets as many [`PADDING`] characters as `PS2` is wide.
+/// The highlighter draws `PS2` over them and [`strip_continuation`] takes
+/// them out again before the input is run.
+///
+/// A closing `}` is not dedented as it is typed: rustyline can't delete the
+/// indentation and leave the cursor after the brace in one command.
+pub struct AutoIndent {
+    /// The width of `PS2`, shared with the helper that renders it.
+    pub continuation_width: Arc<AtomicUsize>,
+}
+
+impl ConditionalEventHandler for AutoIndent {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let line = ctx.line();
+        if ctx.pos() != line.len() || !parser::is_incomplete(line) {
+            return None;
+        }
+        let depth = parser::brace_depth(line).max(0) as usize;
+        let padding = padding(self.continuation_width.load(Ordering::Relaxed));
+        Some(Cmd::Insert(
+            1,
+            format!("\n{}{}", padding, INDENT.repeat(depth)),
+        ))
+    }
+}
+
+/// `highlighted`, the coloured form of `line`, with `prompt` drawn over the
+/// `width` characters of padding that [`AutoIndent`] put at the start of each
+/// continuation line.
+pub fn show_continuation(line: &str, highlighted: &str, width: usize, prompt: &str) -> String {
+    if width == 0 || !line.contains('\n') {
+        return highlighted.to_string();
+    }
+    let mut out = String::with_capacity(highlighted.len() + prompt.len());
+    // Byte offset in `line` of the next character of `highlighted`.
+    let mut pos = 0;
+    let mut skip = 0;
+    let mut chars = highlighted.chars();
+    while let Some(ch) = chars.next() {
+        // Colour codes are not part of `line`.
+        if ch == '\x1b' {
+            out.push(ch);
+            for c in chars.by_ref() {
+                out.push(c);
+                if c == 'm' {
+                    break;
+                }
+            }
+            continue;
+        }
+        if skip == 0 && is_padded(line, pos, width) {
+            out.push_str(prompt);
+            skip = width;
+        }
+        if skip > 0 {
+            skip -= 1;
+        } else {
+            out.push(ch);
+        }
+        pos += ch.len_utf8();
+    }
+    out
+}
+
+/// `line` without the padding [`AutoIndent`] put at the start of each
+/// continuation line.
+pub fn strip_continuation(line: &str, width: usize) -> String {
+    if width == 0 {
+        return line.to_string();
+    }
+    let padding = padding(width);
+    let mut lines = line.split('\n');
+    let mut out = lines.next().unwrap_or_default().to_string();
+    for next in lines {
+        out.push('\n');
+        out.push_str(next.strip_prefix(padding.as_str()).unwrap_or(next));
+    }
+    out
+}
+
+/// `width` characters of padding.
+fn padding(width: usize) -> String {
+    std::iter::repeat_n(PADDING, width).collect()
+}
+
+/// Whether a continuation line starts at byte `start` of `line` with
+/// `width` characters of padding.
+fn is_padded(line: &str, start: usize, width: usize) -> bool {
+    width > 0 && line[..start].ends_with('\n') && line[start..].starts_with(&padding(width))
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(matches!(
+            validate("if true {\n    echo hi"),
+            ValidationResult::Incomplete
+        ));
+        assert!(matches!(validate("echo a,"), ValidationResult::Incomplete));
+        assert!(matches!(
+            validate("if true {\n    echo hi\n}"),
+            ValidationResult::Valid(None)
+        ));
+    }
+
+    #[test]
+    fn test_continuation_padding() {
+        let line = "if true {\n\u{a0}\u{a0}    echo hi\n\u{a0}\u{a0}}";
+        assert_eq!(strip_continuation(line, 2), "if true {\n    echo hi\n}");
+        assert_eq!(strip_continuation(line, 0), line);
+        assert_eq!(
+            show_continuation(line, line, 2, "> "),
+            "if true {\n>     echo hi\n> }"
+        );
+
+        let coloured = "\x1b[34mif\x1b[0m {\n\u{a0}\u{a0}x";
+        assert_eq!(
+            show_continuation("if {\n\u{a0}\u{a0}x", coloured, 2, "\x1b[1m>\x1b[0m "),
+            "\x1b[34mif\x1b[0m {\n\x1b[1m>\x1b[0m x"
+        );
+    }
+
+    #[test]
+    fn test_typed_spaces_are_not_padding() {
+        // Pasted, or recalled from history: the spaces are the user's.
+        let pasted = "echo \"a\n  b\"";
+        assert_eq!(strip_continuation(pasted, 2), pasted);
+        assert_eq!(show_continuation(pasted, pasted, 2, "> "), pasted);
+    }
+}
@@ -0,0 +1,606 @@
+//! Full-screen fuzzy picker over the command history, shown on Ctrl-R and
+//! by `sys.history --pick`.
+//!
+//! Typing narrows the list with fuzzy matching; Up/Down (or Ctrl-P/Ctrl-N)
+//! move the selection, Enter runs it, Tab inserts it for editing, Ctrl-D
+//! limits the list to commands run in the current directory, Ctrl-E cycles
+//! between all, succeeded and failed commands, and Esc cancels.
+
+// Only the matching is used where the picker can't be shown.
+#![cfg_attr(not(unix), allow(dead_code))]
+
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+
+use crate::engine::state::HistoryEntry;
+
+/// What was chosen in the picker.
+#[derive(Debug, PartialEq)]
+pub enum Pick {
+    /// Run the command now (Enter).
+    Run(String),
+    /// Put the command in the buffer for editing (Tab).
+    Insert(String),
+}
+
+/// Which exit statuses are listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StatusFilter {
+    #[default]
+    Any,
+    Succeeded,
+    Failed,
+}
+
+impl StatusFilter {
+    fn next(self) -> Self {
+        match self {
+            StatusFilter::Any => StatusFilter::Succeeded,
+            StatusFilter::Succeeded => StatusFilter::Failed,
+            StatusFilter::Failed => StatusFilter::Any,
+        }
+    }
+
+    fn accepts(self, entry: &HistoryEntry) -> bool {
+        match self {
+            StatusFilter::Any => true,
+            StatusFilter::Succeeded => entry.exit_code == Some(0),
+            StatusFilter::Failed => entry.exit_code.is_some_and(|c| c != 0),
+        }
+    }
+}
+
+/// Initial state of the picker.
+#[derive(Debug, Default)]
+pub struct PickOptions {
+    pub query: String,
+    pub cwd_only: bool,
+    pub status: StatusFilter,
+}
+
+/// Key handler that replaces Ctrl-R's incremental search with the picker.
+/// It reads a snapshot of the history, since key handlers can't share the
+/// shell state; the choice is left in `picked` and the line is accepted for
+/// the read-eval loop to run it or read it again for editing.
+pub struct HistoryPicker {
+    pub history: Arc<Mutex<Vec<HistoryEntry>>>,
+    pub picked: Arc<Mutex<Option<Pick>>>,
+}
+
+impl ConditionalEventHandler for HistoryPicker {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let options = PickOptions {
+            query: ctx.line().to_string(),
+            ..Default::default()
+        };
+        let result = {
+            let history = self.history.lock().ok()?;
+            pick(&history, options)
+        };
+        // Without a terminal, fall back to rustyline's own search.
+        match result.ok()? {
+            Some(pick) => {
+                *self.picked.lock().ok()? = Some(pick);
+                Some(Cmd::AcceptLine)
+            }
+            None => Some(Cmd::Repaint),
+        }
+    }
+}
+
+/// Score `text` against `query`, whose space-separated terms must each
+/// appear in order (not necessarily adjacent). Consecutive matches and
+/// matches at the start of a word score higher. Matching ignores case
+/// unless a term contains an upper-case letter. Returns the score and the
+/// byte offsets of the matched characters.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut total = 0;
+    let mut positions = Vec::new();
+    for term in query.split_whitespace() {
+        let (score, matched) = match_term(term, &chars)?;
+        total += score;
+        positions.extend(matched);
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    Some((total, positions))
+}
+
+fn match_term(term: &str, chars: &[(usize, char)]) -> Option<(i64, Vec<usize>)> {
+    let term: Vec<char> = term.chars().collect();
+    let case_sensitive = term.iter().any(|c| c.is_uppercase());
+    let eq = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+
+    let mut best: Option<(i64, Vec<usize>)> = None;
+    for start in 0..chars.len() {
+        if !eq(chars[start].1, term[0]) {
+            continue;
+        }
+        let mut score = 0;
+        let mut positions = Vec::with_capacity(term.len());
+        let mut prev: Option<usize> = None;
+        for (i, &(offset, c)) in chars.iter().enumerate().skip(start) {
+            if positions.len() == term.len() {
+                break;
+            }
+            if !eq(c, term[positions.len()]) {
+                continue;
+            }
+            score += 16;
+            match prev {
+                Some(p) if p + 1 == i => score += 15,
+                Some(p) => score -= (i - p - 1).min(10) as i64,
+                None => {}
+            }
+            if i == 0 || " /-_.:=".contains(chars[i - 1].1) {
+                score += 10;
+            }
+            positions.push(offset);
+            prev = Some(i);
+        }
+        if positions.len() == term.len() && best.as_ref().is_none_or(|(s, _)| score > *s) {
+            best = Some((score, positions));
+        }
+    }
+    best
+}
+
+/// A listed history entry: its index in the history and matched offsets.
+struct Candidate {
+    index: usize,
+    score: i64,
+    positions: Vec<usize>,
+}
+
+/// Unique entries matching the query and filters, best first; ties go to
+/// the more recent entry.
+fn candidates(
+    entries: &[HistoryEntry],
+    options: &PickOptions,
+    cwd: Option<&PathBuf>,
+) -> Vec<Candidate> {
+    let mut seen = HashSet::new();
+    let mut out: Vec<Candidate> = entries
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, entry)| !options.cwd_only || entry.cwd.is_some() && entry.cwd.as_ref() == cwd)
+        .filter(|(_, entry)| options.status.accepts(entry))
+        .filter(|(_, entry)| seen.insert(entry.line.as_str()))
+        .filter_map(|(index, entry)| {
+            let (score, positions) = fuzzy_match(&options.query, &entry.line)?;
+            Some(Candidate {
+                index,
+                score,
+                positions,
+            })
+        })
+        .collect();
+    // A stable sort keeps newer entries first among equal scores.
+    out.sort_by_key(|c| std::cmp::Reverse(c.score));
+    out
+}
+
+/// Show the picker on the controlling terminal. Returns `None` when it was
+/// cancelled, and an error when there is no terminal to draw on.
+#[cfg(unix)]
+pub fn pick(entries: &[HistoryEntry], mut options: PickOptions) -> io::Result<Option<Pick>> {
+    use std::io::{Read, Write};
+
+    let mut tty = terminal::Tty::open()?;
+    let cwd = std::env::current_dir().ok();
+    let mut selected = 0;
+    let mut scroll = 0;
+    let mut pending = Vec::new();
+
+    loop {
+        let list = candidates(entries, &options, cwd.as_ref());
+        selected = selected.min(list.len().saturating_sub(1));
+        let screen = render(entries, &list, &options, selected, &mut scroll, tty.size());
+        tty.file.write_all(screen.as_bytes())?;
+        tty.file.flush()?;
+
+        let mut buf = [0u8; 64];
+        let n = tty.file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        pending.extend_from_slice(&buf[..n]);
+        let keys = terminal::parse_keys(&mut pending);
+
+        let chosen = |i: usize| list.get(i).map(|c| entries[c.index].line.clone());
+        for key in keys {
+            use terminal::Key;
+            match key {
+                Key::Char(c) => {
+                    options.query.push(c);
+                    selected = 0;
+                }
+                Key::Backspace => {
+                    options.query.pop();
+                    selected = 0;
+                }
+                Key::Ctrl('u') => options.query.clear(),
+                Key::Ctrl('w') => {
+                    let trimmed = options.query.trim_end().len();
+                    let word = options.query[..trimmed].rfind(' ').map_or(0, |i| i + 1);
+                    options.query.truncate(word);
+                }
+                Key::Up | Key::Ctrl('p') => selected = selected.saturating_sub(1),
+                Key::Down | Key::Ctrl('n') => selected += 1,
+                Key::PageUp => selected = selected.saturating_sub(10),
+                Key::PageDown => selected += 10,
+                Key::Ctrl('d') => options.cwd_only = !options.cwd_only,
+                Key::Ctrl('e') => options.status = options.status.next(),
+                Key::Enter => return Ok(chosen(selected).map(Pick::Run)),
+                Key::Tab => return Ok(chosen(selected).map(Pick::Insert)),
+                Key::Escape | Key::Ctrl('c') | Key::Ctrl('g') => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn pick(_entries: &[HistoryEntry], _options: PickOptions) -> io::Result<Option<Pick>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the history picker needs a Unix terminal",
+    ))
+}
+
+/// Draw the whole screen: query line, list, preview of the selected entry
+/// and a key reminder.
+fn render(
+    entries: &[HistoryEntry],
+    list: &[Candidate],
+    options: &PickOptions,
+    selected: usize,
+    scroll: &mut usize,
+    (rows, cols): (usize, usize),
+) -> String {
+    let mut out = String::from("\x1b[H\x1b[2J");
+    let selection = list.get(selected).map(|c| &entries[c.index]);
+
+    let count = list.len().to_string();
+    let mut filters = Vec::new();
+    if options.cwd_only {
+        filters.push("[cwd]");
+    }
+    match options.status {
+        StatusFilter::Any => {}
+        StatusFilter::Succeeded => filters.push("[ok]"),
+        StatusFilter::Failed => filters.push("[failed]"),
+    }
+    filters.push(&count);
+    let info = filters.join(" ");
+    let query = truncate(
+        &format!("> {}", options.query),
+        cols.saturating_sub(info.chars().count() + 1),
+    );
+    let pad = cols.saturating_sub(query.chars().count() + info.chars().count());
+    out.push_str(&format!(
+        "{}{}\x1b[2m{}\x1b[0m\r\n",
+        query,
+        " ".repeat(pad),
+        info
+    ));
+
+    // Preview: metadata line plus as many command lines as fit.
+    let preview_lines: Vec<&str> = selection.map_or(Vec::new(), |e| e.line.lines().collect());
+    let preview_height = (preview_lines.len() + 1).min((rows / 3).max(2));
+    let list_height = rows.saturating_sub(preview_height + 3).max(1);
+
+    if selected < *scroll {
+        *scroll = selected;
+    } else if selected >= *scroll + list_height {
+        *scroll = selected + 1 - list_height;
+    }
+    for row in 0..list_height {
+        if let Some(candidate) = list.get(*scroll + row) {
+            let is_selected = *scroll + row == selected;
+            let line = &entries[candidate.index].line;
+            out.push_str(if is_selected { "\x1b[7m> " } else { "  " });
+            out.push_str(&highlight_matches(
+                line,
+                &candidate.positions,
+                cols.saturating_sub(2),
+                is_selected,
+            ));
+            out.push_str("\x1b[0m");
+        }
+        out.push_str("\r\n");
+    }
+
+    out.push_str(&format!("\x1b[2m{}\x1b[0m\r\n", "─".repeat(cols)));
+    if let Some(entry) = selection {
+        out.push_str(&format!(
+            "\x1b[2m{}\x1b[0m\r\n",
+            truncate(&describe(entry), cols)
+        ));
+        for line in preview_lines.iter().take(preview_height - 1) {
+            out.push_str(&truncate(line, cols));
+            out.push_str("\r\n");
+        }
+    }
+    out.push_str(&format!(
+        "\x1b[{};1H\x1b[2m{}\x1b[0m",
+        rows,
+        truncate(
+            "enter run · tab insert · ^D this directory · ^E exit status · esc cancel",
+            cols
+        )
+    ));
+    // Leave the cursor after the query.
+    out.push_str(&format!(
+        "\x1b[1;{}H",
+        (options.query.chars().count() + 3).min(cols.max(1))
+    ));
+    out
+}
+
+/// One list row: the first line of the command (`⏎` marks more lines), cut
+/// to `width` characters, with matched characters in bold.
+fn highlight_matches(line: &str, positions: &[usize], width: usize, selected: bool) -> String {
+    let mut out = String::new();
+    let restore = if selected { "\x1b[0;7m" } else { "\x1b[0m" };
+    for (shown, (offset, c)) in line.char_indices().enumerate() {
+        if shown >= width {
+            break;
+        }
+        if c == '\n' {
+            out.push_str(" ⏎");
+            break;
+        }
+        if positions.binary_search(&offset).is_ok() {
+            out.push_str("\x1b[1;32m");
+            out.push(c);
+            out.push_str(restore);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// When, where and how the entry ran.
+fn describe(entry: &HistoryEntry) -> String {
+    use chrono::TimeZone;
+
+    let mut parts = Vec::new();
+    if let Some(when) = entry
+        .timestamp
+        .and_then(|ts| chrono::Local.timestamp_opt(ts, 0).single())
+    {
+        parts.push(when.format("%Y-%m-%d %H:%M").to_string());
+    }
+    if let Some(cwd) = &entry.cwd {
+        parts.push(cwd.display().to_string());
+    }
+    if let Some(code) = entry.exit_code {
+        parts.push(format!("exit {}", code));
+    }
+    if let Some(duration) = entry.duration {
+        parts.push(crate::prompt::format_duration(duration));
+    }
+    parts.join(" · ")
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+#[cfg(unix)]
+mod terminal {
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::fd::AsFd;
+
+    use nix::sys::termios::{self, SetArg, Termios};
+
+    /// The controlling terminal in raw mode on the alternate screen; both
+    /// are undone on drop.
+    pub struct Tty {
+        pub file: File,
+        saved: Termios,
+    }
+
+    impl Tty {
+        pub fn open() -> io::Result<Self> {
+            let mut file = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+            let saved = termios::tcgetattr(file.as_fd())?;
+            let mut raw = saved.clone();
+            termios::cfmakeraw(&mut raw);
+            termios::tcsetattr(file.as_fd(), SetArg::TCSANOW, &raw)?;
+            file.write_all(b"\x1b[?1049h")?;
+            Ok(Tty { file, saved })
+        }
+
+        /// Terminal height and width, defaulting to 24x80.
+        pub fn size(&self) -> (usize, usize) {
+            use std::os::fd::AsRawFd;
+
+            let mut size: nix::libc::winsize = unsafe { std::mem::zeroed() };
+            let ok = unsafe {
+                nix::libc::ioctl(self.file.as_raw_fd(), nix::libc::TIOCGWINSZ, &mut size)
+            } == 0;
+            if ok && size.ws_row > 0 && size.ws_col > 0 {
+                (size.ws_row as usize, size.ws_col as usize)
+            } else {
+                (24, 80)
+            }
+        }
+    }
+
+    impl Drop for Tty {
+        fn drop(&mut self) {
+            let _ = self.file.write_all(b"\x1b[?1049l");
+            let _ = self.file.flush();
+            let _ = termios::tcsetattr(self.file.as_fd(), SetArg::TCSANOW, &self.saved);
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum Key {
+        Char(char),
+        Ctrl(char),
+        Backspace,
+        Enter,
+        Tab,
+        Escape,
+        Up,
+        Down,
+        PageUp,
+        PageDown,
+        Other,
+    }
+
+    /// Decode the complete keys at the start of `input`, leaving an
+    /// incomplete UTF-8 sequence for the next read.
+    pub fn parse_keys(input: &mut Vec<u8>) -> Vec<Key> {
+        let mut keys = Vec::new();
+        let mut i = 0;
+        while i < input.len() {
+            let rest = &input[i..];
+            let (key, len) = match rest[0] {
+                0x1b if rest.len() == 1 => (Key::Escape, 1),
+                0x1b if rest[1] == b'O' => {
+                    let key = match rest.get(2) {
+                        Some(b'A') => Key::Up,
+                        Some(b'B') => Key::Down,
+                        _ => Key::Other,
+                    };
+                    (key, rest.len().min(3))
+                }
+                0x1b => {
+                    let end = rest[1..]
+                        .iter()
+                        .position(|b| b.is_ascii_alphabetic() || *b == b'~')
+                        .map_or(rest.len(), |p| p + 2);
+                    let key = match &rest[..end] {
+                        b"\x1b[A" | b"\x1bOA" => Key::Up,
+                        b"\x1b[B" | b"\x1bOB" => Key::Down,
+                        b"\x1b[5~" => Key::PageUp,
+                        b"\x1b[6~" => Key::PageDown,
+                        _ => Key::Other,
+                    };
+                    (key, end)
+                }
+                b'\r' | b'\n' => (Key::Enter, 1),
+                b'\t' => (Key::Tab, 1),
+                0x7f | 0x08 => (Key::Backspace, 1),
+                b @ 0x01..=0x1a => (Key::Ctrl((b'a' + b - 1) as char), 1),
+                b if b < 0x20 => (Key::Other, 1),
+                _ => {
+                    let len = utf8_len(rest[0]);
+                    if rest.len() < len {
+                        break;
+                    }
+                    match std::str::from_utf8(&rest[..len]) {
+                        Ok(s) => (Key::Char(s.chars().next().unwrap_or(' ')), len),
+                        Err(_) => (Key::Other, 1),
+                    }
+                }
+            };
+            keys.push(key);
+            i += len;
+        }
+        input.drain(..i);
+        keys
+    }
+
+    fn utf8_len(first: u8) -> usize {
+        match first {
+            0xf0..=0xf7 => 4,
+            0xe0..=0xef => 3,
+            0xc0..=0xdf => 2,
+            _ => 1,
+        }
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: &str, cwd: &str, exit_code: i32) -> HistoryEntry {
+        HistoryEntry {
+            line: line.to_string(),
+            cwd: Some(cwd.into()),
+            exit_code: Some(exit_code),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        let (score, positions) = fuzzy_match("gco", "git checkout main").unwrap();
+        assert_eq!(positions, [0, 4, 9]);
+        let (tight, _) = fuzzy_match("check", "git checkout main").unwrap();
+        assert!(tight > score);
+        assert!(fuzzy_match("main git", "git checkout main").is_some());
+        assert!(fuzzy_match("xyz", "git checkout main").is_none());
+        assert!(fuzzy_match("Git", "git status").is_none());
+    }
+
+    #[test]
+    fn test_candidates_filters() {
+        let entries = vec![
+            entry("make test", "/a", 2),
+            entry("make build", "/b", 0),
+            entry("make test", "/a", 0),
+        ];
+        let lines = |options: &PickOptions, cwd: &str| -> Vec<&str> {
+            candidates(&entries, options, Some(&PathBuf::from(cwd)))
+                .iter()
+                .map(|c| entries[c.index].line.as_str())
+                .collect()
+        };
+
+        let mut options = PickOptions::default();
+        assert_eq!(lines(&options, "/a"), ["make test", "make build"]);
+        options.query = "mkb".into();
+        assert_eq!(lines(&options, "/a"), ["make build"]);
+        options.query.clear();
+        options.cwd_only = true;
+        assert_eq!(lines(&options, "/b"), ["make build"]);
+        options.cwd_only = false;
+        options.status = StatusFilter::Failed;
+        assert_eq!(lines(&options, "/a"), ["make test"]);
+        options.status = StatusFilter::Succeeded;
+        options.query = "test".into();
+        assert_eq!(lines(&options, "/a"), ["make test"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_keys() {
+        use terminal::{Key, parse_keys};
+        let mut input = b"a\x1b[A\r\x04\xc3".to_vec();
+        assert_eq!(
+            parse_keys(&mut input),
+            [Key::Char('a'), Key::Up, Key::Enter, Key::Ctrl('d')]
+        );
+        input.push(0xa9);
+        assert_eq!(parse_keys(&mut input), [Key::Char('é')]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+
+use crate::engine::ShellState;
+
+/// The rest of the most recent history entry that starts with `line`.
+///
+/// Entries run in `cwd` win over newer ones from elsewhere. Entries that
+/// failed and multi-line entries are never suggested.
+pub fn suggest(line: &str, cwd: Option<&Path>, state: &ShellState) -> Option<String> {
+    if line.trim().is_empty() || !state.set_options.contains("autosuggest") {
+        return None;
+    }
+
+    let mut candidates = state.history.iter().rev().filter(|entry| {
+        entry.line.len() > line.len()
+            && entry.line.starts_with(line)
+            && !entry.line.contains('\n')
+            && entry.exit_code.is_none_or(|code| code == 0)
+    });
+
+    let mut fallback = None;
+    let best = candidates.find(|entry| {
+        fallback.get_or_insert(*entry);
+        cwd.is_some() && entry.cwd.as_deref() == cwd
+    });
+    best.or(fallback)
+        .map(|entry| entry.line[line.len()..].to_string())
+}
+
+/// Key handler that accepts the suggestion shown after the cursor, either
+/// all of it (End) or up to the end of its next word (Alt-F). Without a
+/// suggestion the key keeps its usual binding.
+pub struct AcceptSuggestion {
+    pub one_word: bool,
+}
+
+impl ConditionalEventHandler for AcceptSuggestion {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let hint = ctx.hint_text().filter(|h| !h.is_empty())?;
+        if ctx.pos() != ctx.line().len() {
+            return None;
+        }
+        if !self.one_word {
+            return Some(Cmd::CompleteHint);
+        }
+        Some(Cmd::Insert(1, next_word(hint).to_string()))
+    }
+}
+
+/// Leading whitespace plus the following word of `text`.
+fn next_word(text: &str) -> &str {
+    let start = text.len() - text.trim_start().len();
+    let end = text[start..]
+        .find(char::is_whitespace)
+        .map_or(text.len(), |i| start + i);
+    &text[..end]
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::state::HistoryEntry;
+
+    fn entry(line: &str, cwd: &str, exit_code: Option<i32>) -> HistoryEntry {
+        HistoryEntry {
+            line: line.to_string(),
+            cwd: Some(cwd.into()),
+            exit_code,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prefers_current_directory_and_skips_failures() {
+        let mut state = ShellState::new();
+        state.history = vec![
+            entry("cargo build --release", "/src/app", Some(0)),
+            entry("cargo bench", "/src/other", Some(0)),
+            entry("cargo test --all", "/src/app", Some(101)),
+        ];
+
+        let app = Path::new("/src/app");
+        assert_eq!(
+            suggest("cargo b", Some(app), &state).as_deref(),
+            Some("uild --release")
+        );
+        assert_eq!(suggest("cargo b", None, &state).as_deref(), Some("ench"));
+        assert_eq!(suggest("cargo t", Some(app), &state), None);
+
+        state.set_options.remove("autosuggest");
+        assert_eq!(suggest("cargo b", Some(app), &state), None);
+    }
+
+    #[test]
+    fn test_next_word() {
+        assert_eq!(next_word(" --release -v"), " --release");
+        assert_eq!(next_word("uild"), "uild");
+    }
+}
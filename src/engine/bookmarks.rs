@@ -0,0 +1,146 @@
+//! Directory bookmarks made with `dir.mark`.
+//!
+//! The bookmarks file has one `name<TAB>path` line per bookmark. The shell
+//! keeps the loaded bookmarks in a process-wide table so that `~name`
+//! expansion in [`super::path::expand_tilde`], which has no access to the
+//! shell state, can see them. The table is read again whenever the file's
+//! size or modification time changes, e.g. after `dir.mark` in another shell.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use super::history::{replace_file, with_lock};
+
+/// The bookmarks file's size and modification time when it was loaded.
+type Stamp = Option<(u64, SystemTime)>;
+
+struct Table {
+    file: Option<PathBuf>,
+    stamp: Stamp,
+    bookmarks: BTreeMap<String, PathBuf>,
+}
+
+static BOOKMARKS: RwLock<Table> = RwLock::new(Table {
+    file: None,
+    stamp: None,
+    bookmarks: BTreeMap::new(),
+});
+
+fn stamp(path: &Path) -> Stamp {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+/// The directory a bookmark points to.
+pub fn get(name: &str) -> Option<PathBuf> {
+    refresh();
+    BOOKMARKS.read().ok()?.bookmarks.get(name).cloned()
+}
+
+/// All bookmarks, sorted by name.
+pub fn all() -> BTreeMap<String, PathBuf> {
+    refresh();
+    BOOKMARKS
+        .read()
+        .map(|table| table.bookmarks.clone())
+        .unwrap_or_default()
+}
+
+/// Use the bookmarks file at `path` for `~name` expansion, loading it now.
+pub fn watch(path: &Path) {
+    if let Ok(mut table) = BOOKMARKS.write() {
+        table.file = Some(path.to_path_buf());
+        table.stamp = stamp(path);
+        table.bookmarks = load(path);
+    }
+}
+
+/// Load the watched file again if it changed since it was last read.
+fn refresh() {
+    let file = match BOOKMARKS.read() {
+        Ok(table) => match &table.file {
+            Some(file) if stamp(file) != table.stamp => file.clone(),
+            _ => return,
+        },
+        Err(_) => return,
+    };
+    watch(&file);
+}
+
+/// Whether `name` can be used as a bookmark, i.e. in `~name/...`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Parse the contents of a bookmarks file, skipping malformed lines.
+pub fn parse(contents: &str) -> BTreeMap<String, PathBuf> {
+    contents
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(name, path)| is_valid_name(name) && !path.is_empty())
+        .map(|(name, path)| (name.to_string(), PathBuf::from(path)))
+        .collect()
+}
+
+/// Format bookmarks for the bookmarks file.
+pub fn format(bookmarks: &BTreeMap<String, PathBuf>) -> String {
+    bookmarks
+        .iter()
+        .map(|(name, path)| format!("{}\t{}\n", name, path.display()))
+        .collect()
+}
+
+/// Read the bookmarks file at `path`; a missing file has no bookmarks.
+pub fn load(path: &Path) -> BTreeMap<String, PathBuf> {
+    fs::read_to_string(path)
+        .map(|contents| parse(&contents))
+        .unwrap_or_default()
+}
+
+/// Load the bookmarks file at `path`, change it with `f` and write it back
+/// under the lock. The file is then the one used for expansion.
+pub fn update<T>(
+    path: &Path,
+    f: impl FnOnce(&mut BTreeMap<String, PathBuf>) -> T,
+) -> io::Result<T> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    with_lock(path, || {
+        let mut bookmarks = load(path);
+        let result = f(&mut bookmarks);
+        replace_file(path, &format(&bookmarks))?;
+        watch(path);
+        Ok(result)
+    })
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let bookmarks = parse("web\t/src/web\nbad name\t/x\nnotab\napi\t/src/my api\n");
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks["api"], PathBuf::from("/src/my api"));
+        assert_eq!(parse(&format(&bookmarks)), bookmarks);
+    }
+
+    #[test]
+    fn test_valid_names() {
+        assert!(is_valid_name("web-2.0_x"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("a/b"));
+        assert!(!is_valid_name("a b"));
+    }
+}
@@ -0,0 +1,258 @@
+//! Per-directory environments: `.cerfenv` files.
+//!
+//! When an interactive shell enters a directory, the nearest `.cerfenv` at
+//! or above it is sourced, provided it has been trusted with `dir.allow`.
+//! The variables it sets are recorded through [`ShellState::set_var`] and
+//! put back as they were when the shell leaves that directory tree.
+//!
+//! Trust is kept in the trust file, one `allow|deny<TAB>hash<TAB>path` line
+//! per `.cerfenv`. The hash covers the file's path and contents, so editing
+//! or moving a trusted file revokes the trust until it is allowed again.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::history::{replace_file, with_lock};
+use super::state::{ShellState, Variable};
+
+pub const FILE_NAME: &str = ".cerfenv";
+
+/// A loaded `.cerfenv` and the previous values of the variables it set.
+pub struct Loaded {
+    pub file: PathBuf,
+    pub hash: String,
+    saved: HashMap<String, Option<Variable>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trust {
+    Allowed,
+    Denied,
+    Unknown,
+}
+
+/// The nearest `.cerfenv` in `dir` or one of its parents.
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(FILE_NAME))
+        .find(|f| f.is_file())
+}
+
+/// The hash that trust is given to: SHA-256 over the path and contents.
+pub fn hash(file: &Path) -> io::Result<String> {
+    let contents = fs::read(file)?;
+    let mut hasher = Sha256::new();
+    hasher.update(file.as_os_str().as_encoded_bytes());
+    hasher.update([0]);
+    hasher.update(&contents);
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Parse the trust file into the latest decision for each path.
+pub fn parse(contents: &str) -> BTreeMap<PathBuf, (bool, String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let allow = match fields.next()? {
+                "allow" => true,
+                "deny" => false,
+                _ => return None,
+            };
+            let hash = fields.next()?.to_string();
+            let path = fields.next().filter(|p| !p.is_empty())?;
+            Some((PathBuf::from(path), (allow, hash)))
+        })
+        .collect()
+}
+
+/// Format trust decisions for the trust file.
+pub fn format(entries: &BTreeMap<PathBuf, (bool, String)>) -> String {
+    entries
+        .iter()
+        .map(|(path, (allow, hash))| {
+            let verdict = if *allow { "allow" } else { "deny" };
+            format!("{}\t{}\t{}\n", verdict, hash, path.display())
+        })
+        .collect()
+}
+
+/// Whether `file`, whose current hash is `hash`, may be loaded.
+pub fn trust(store: &Path, file: &Path, hash: &str) -> Trust {
+    let entries = fs::read_to_string(store)
+        .map(|contents| parse(&contents))
+        .unwrap_or_default();
+    match entries.get(file) {
+        Some((true, h)) if h == hash => Trust::Allowed,
+        Some((false, h)) if h == hash => Trust::Denied,
+        _ => Trust::Unknown,
+    }
+}
+
+/// Record in the trust file at `store` that `file` is allowed or denied in
+/// its current state.
+pub fn set_trust(store: &Path, file: &Path, allow: bool) -> io::Result<()> {
+    let hash = hash(file)?;
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    with_lock(store, || {
+        let mut entries = match fs::read_to_string(store) {
+            Ok(contents) => parse(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        entries.insert(file.to_path_buf(), (allow, hash));
+        replace_file(store, &format(&entries))
+    })
+}
+
+/// Load or unload `.cerfenv` files for the current directory. Called
+/// whenever the directory changes; does nothing in scripts.
+pub fn update(state: &mut ShellState) {
+    // A `.cerfenv` that changes directory itself is still being loaded.
+    if !state.interactive || state.var_journal.is_some() {
+        return;
+    }
+    let found = crate::builtins::cd::logical_cwd(state)
+        .ok()
+        .and_then(|cwd| find(&cwd))
+        .and_then(|file| Some((hash(&file).ok()?, file)));
+
+    if let (Some(loaded), Some((hash, file))) = (&state.cerfenv, &found)
+        && loaded.file == *file
+        && loaded.hash == *hash
+    {
+        return;
+    }
+    unload(state);
+
+    let Some((hash, file)) = found else {
+        return;
+    };
+    let Some(store) = state.cerfenv_trust_file() else {
+        return;
+    };
+    match trust(&store, &file, &hash) {
+        Trust::Allowed => load(state, file, hash),
+        Trust::Denied => {}
+        Trust::Unknown if first_warning(state, &file, &hash) => eprintln!(
+            "cerf: {} is not trusted; run `dir.allow` to load it or `dir.deny` to ignore it",
+            file.display()
+        ),
+        Trust::Unknown => {}
+    }
+}
+
+/// Whether `file` with `hash` has not been warned about yet, so that moving
+/// around inside its tree doesn't repeat the warning. Editing the file warns
+/// again.
+fn first_warning(state: &mut ShellState, file: &Path, hash: &str) -> bool {
+    if let Some((warned, warned_hash)) = &state.cerfenv_warned
+        && warned == file
+        && warned_hash == hash
+    {
+        return false;
+    }
+    state.cerfenv_warned = Some((file.to_path_buf(), hash.to_string()));
+    true
+}
+
+/// Source `file`, recording the variables it sets.
+fn load(state: &mut ShellState, file: PathBuf, hash: String) {
+    state.var_journal = Some(HashMap::new());
+    let path = file.to_string_lossy().into_owned();
+    crate::builtins::source::run(&[path], state);
+    let saved = state.var_journal.take().unwrap_or_default();
+    state.cerfenv = Some(Loaded { file, hash, saved });
+}
+
+/// Put back the variables set by the loaded `.cerfenv`, if any.
+pub fn unload(state: &mut ShellState) {
+    let Some(loaded) = state.cerfenv.take() else {
+        return;
+    };
+    for (name, previous) in loaded.saved {
+        match previous {
+            // Put the old variable back as it was, attributes and all.
+            Some(var) => match state.lookup_var_mut(&name) {
+                Some(current) => *current = var,
+                None => {
+                    let _ = state.set_var(&name, var);
+                }
+            },
+            None => {
+                state.variables.remove(&name);
+                for scope in &mut state.scopes {
+                    scope.remove(&name);
+                }
+            }
+        }
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trust_follows_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(FILE_NAME);
+        let store = dir.path().join("config").join("trust");
+        fs::write(&file, "PROJECT=one\n").unwrap();
+
+        assert_eq!(find(&dir.path().join("a").join("b")), Some(file.clone()));
+        assert_eq!(trust(&store, &file, &hash(&file).unwrap()), Trust::Unknown);
+        set_trust(&store, &file, true).unwrap();
+        assert_eq!(trust(&store, &file, &hash(&file).unwrap()), Trust::Allowed);
+
+        fs::write(&file, "PROJECT=two\n").unwrap();
+        assert_eq!(trust(&store, &file, &hash(&file).unwrap()), Trust::Unknown);
+        set_trust(&store, &file, false).unwrap();
+        assert_eq!(trust(&store, &file, &hash(&file).unwrap()), Trust::Denied);
+        assert_eq!(parse(&fs::read_to_string(&store).unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn test_untrusted_warned_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(FILE_NAME);
+        let mut state = ShellState::new();
+
+        assert!(first_warning(&mut state, &file, "one"));
+        assert!(!first_warning(&mut state, &file, "one"));
+        assert!(first_warning(&mut state, &file, "two"));
+        assert!(first_warning(&mut state, &dir.path().join("a"), "two"));
+    }
+
+    #[test]
+    fn test_unload_restores_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(FILE_NAME);
+        fs::write(&file, "KEPT=changed\nexport CERFENV_ADDED=new\n").unwrap();
+
+        let mut state = ShellState::new();
+        state.set_var("KEPT", Variable::new_string("original".into())).unwrap();
+        load(&mut state, file.clone(), hash(&file).unwrap());
+        assert_eq!(state.get_var_string("KEPT").as_deref(), Some("changed"));
+        assert_eq!(state.get_var_string("CERFENV_ADDED").as_deref(), Some("new"));
+        assert!(state.var_journal.is_none());
+
+        unload(&mut state);
+        assert_eq!(state.get_var_string("KEPT").as_deref(), Some("original"));
+        assert_eq!(state.get_var_string("CERFENV_ADDED"), None);
+        assert!(!state.exported_env().contains_key("CERFENV_ADDED"));
+        assert!(state.cerfenv.is_none());
+    }
+}
@@ -27,20 +27,18 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
 
     if cmd.name.is_none() {
         // Just assignments
+        let mut status = 0;
         for (key, val) in &cmd.assignments {
-            let expanded_val = expand_vars(val, &state.variables);
-            state.set_var(key, Variable::new_string(expanded_val.clone()));
-            // If already in env, update it there too
-            if std::env::var(key).is_ok() {
-                unsafe {
-                    std::env::set_var(key, &expanded_val);
-                }
+            let expanded_val = expand_vars(val, state);
+            if let Err(e) = state.set_var(key, Variable::new_string(expanded_val)) {
+                eprintln!("cerf: {}", e);
+                status = 1;
             }
         }
         // Handle residuals like redirects (e.g., VAR=val > file)
         if let Some(redir) = stdin_redir {
             let mut expanded_redir = redir.clone();
-            expanded_redir.file = expand_vars(&redir.file, &state.variables);
+            expanded_redir.file = expand_vars(&redir.file, state);
             if let Err(e) = open_stdin_redirect(&expanded_redir) {
                 eprintln!("{}", e);
                 return (ExecutionResult::KeepRunning, 1);
@@ -48,24 +46,24 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
         }
         if let Some(redir) = stdout_redir {
             let mut expanded_redir = redir.clone();
-            expanded_redir.file = expand_vars(&redir.file, &state.variables);
+            expanded_redir.file = expand_vars(&redir.file, state);
             if let Err(e) = open_stdout_redirect(&expanded_redir) {
                 eprintln!("{}", e);
                 return (ExecutionResult::KeepRunning, 1);
             }
         }
-        return (ExecutionResult::KeepRunning, 0);
+        return (ExecutionResult::KeepRunning, status);
     }
 
     let raw_name = cmd.name.as_ref().unwrap();
-    let name = expand_vars(raw_name, &state.variables);
+    let name = expand_vars(raw_name, state);
 
     // Expand variables in the argument list BEFORE glob expansion.
     let expanded_args: Vec<Arg> = cmd
         .args
         .iter()
         .map(|a| Arg {
-            value: expand_vars(&a.value, &state.variables),
+            value: expand_vars(&a.value, state),
             quoted: a.quoted,
         })
         .collect();
@@ -87,12 +85,15 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
             );
             command
                 .arg("-c")
-                .arg(crate::engine::job_control::format_command(pipeline));
+                .arg(crate::engine::job_control::format_command(pipeline))
+                .env_clear()
+                .envs(state.exported_env())
+                .env("CERF_SUBSHELL", (state.specials.subshell + 1).to_string());
 
             // Redirects for subshell
             if let Some(redir) = stdin_redir {
                 let mut expanded_redir = redir.clone();
-                expanded_redir.file = expand_vars(&redir.file, &state.variables);
+                expanded_redir.file = expand_vars(&redir.file, state);
                 if let Ok(f) = open_stdin_redirect(&expanded_redir) {
                     command.stdin(Stdio::from(f));
                 }
@@ -101,7 +102,7 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
             }
             if let Some(redir) = stdout_redir {
                 let mut expanded_redir = redir.clone();
-                expanded_redir.file = expand_vars(&redir.file, &state.variables);
+                expanded_redir.file = expand_vars(&redir.file, state);
                 if let Ok(f) = open_stdout_redirect(&expanded_redir) {
                     command.stdout(Stdio::from(f));
                 }
@@ -140,7 +141,6 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
                         command: crate::engine::job_control::format_command(pipeline),
                         processes: vec![crate::engine::state::ProcessInfo {
                             pid,
-                            name: name.to_string(),
                             state: crate::engine::state::JobState::Running,
                         }],
                         reported_done: false,
@@ -215,7 +215,16 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
             }
         }
     } else {
-        let resolved = find_executable(&name).unwrap_or_else(|| expand_home(&name));
+        // With `autocd`, naming a directory on its own changes into it.
+        if state.set_options.contains("autocd")
+            && args.is_empty()
+            && find_executable(&name, state).is_none()
+            && expand_home(&name).is_dir()
+        {
+            return builtins::cd::cd_runner(&[name], state);
+        }
+
+        let resolved = find_executable(&name, state).unwrap_or_else(|| expand_home(&name));
 
         #[cfg(windows)]
         let mut command = {
@@ -236,12 +245,13 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
         let mut command = Command::new(&resolved);
 
         command.args(&args);
+        command.env_clear().envs(state.exported_env());
         command.envs(cmd.assignments.iter().map(|(k, v)| (k, v)));
 
         // Apply stdin redirect
         if let Some(redir) = stdin_redir {
             let mut expanded_redir = redir.clone();
-            expanded_redir.file = expand_vars(&redir.file, &state.variables);
+            expanded_redir.file = expand_vars(&redir.file, state);
             match open_stdin_redirect(&expanded_redir) {
                 Ok(f) => {
                     command.stdin(Stdio::from(f));
@@ -258,7 +268,7 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
         // Apply stdout redirect
         if let Some(redir) = stdout_redir {
             let mut expanded_redir = redir.clone();
-            expanded_redir.file = expand_vars(&redir.file, &state.variables);
+            expanded_redir.file = expand_vars(&redir.file, state);
             match open_stdout_redirect(&expanded_redir) {
                 Ok(f) => {
                     command.stdout(Stdio::from(f));
@@ -297,6 +307,7 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
         let result = command.spawn();
 
         let code = match result {
+            #[cfg_attr(unix, allow(unused_mut))]
             Ok(mut child) => {
                 let pid = child.id();
 
@@ -345,7 +356,6 @@ fn execute_simple(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResu
                     command: crate::engine::job_control::format_command(pipeline),
                     processes: vec![crate::engine::state::ProcessInfo {
                         pid,
-                        name: name.to_string(),
                         state: crate::engine::state::JobState::Running,
                     }],
                     reported_done: false,
@@ -467,7 +477,7 @@ pub fn execute(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResult,
                 let expanded_items_vars: Vec<Arg> = items
                     .iter()
                     .map(|a| Arg {
-                        value: expand_vars(&a.value, &state.variables),
+                        value: expand_vars(&a.value, state),
                         quoted: a.quoted,
                     })
                     .collect();
@@ -475,7 +485,10 @@ pub fn execute(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResult,
                 let expanded_items = expand_globs(&expanded_items_vars);
                 let mut final_code = 0;
                 for item in expanded_items {
-                    state.set_var(var, Variable::new_string(item.clone()));
+                    if let Err(e) = state.set_var(var, Variable::new_string(item.clone())) {
+                        eprintln!("cerf: {}", e);
+                        return (ExecutionResult::KeepRunning, 1);
+                    }
                     let (res, code) = execute_list(body.clone(), state);
                     match res {
                         ExecutionResult::Exit => return (res, code),
@@ -548,9 +561,11 @@ pub fn execute(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResult,
                 return (ExecutionResult::KeepRunning, final_code);
             }
             crate::parser::CommandNode::Simple(cmd) => {
-                let name = expand_vars(cmd.name.as_deref().unwrap_or(""), &state.variables);
+                let name = expand_vars(cmd.name.as_deref().unwrap_or(""), state);
                 if let Some(func_body) = state.functions.get(&name).cloned() {
+                    state.specials.call_stack.push(name);
                     let (res, code) = execute_list(func_body, state);
+                    state.specials.call_stack.pop();
                     let final_code = if pipeline.negated {
                         if code == 0 { 1 } else { 0 }
                     } else {
@@ -634,7 +649,7 @@ pub fn execute(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResult,
             return (ExecutionResult::Exit, 0);
         }
 
-        let resolved = find_executable(name).unwrap_or_else(|| expand_home(name));
+        let resolved = find_executable(name, state).unwrap_or_else(|| expand_home(name));
 
         // Expand globs on the argument list.
         let args = expand_globs(cmd.args());
@@ -658,6 +673,7 @@ pub fn execute(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResult,
         let mut command = Command::new(&resolved);
 
         command.args(&args);
+        command.env_clear().envs(state.exported_env());
         command.envs(cmd.assignments().iter().map(|(k, v)| (k, v)));
 
         // Stdin: first command may have < redirect, others get previous pipe
@@ -764,7 +780,6 @@ pub fn execute(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResult,
 
                 processes.push(crate::engine::state::ProcessInfo {
                     pid,
-                    name: name.to_string(),
                     state: crate::engine::state::JobState::Running,
                 });
 
@@ -841,9 +856,9 @@ pub fn execute(pipeline: &Pipeline, state: &mut ShellState) -> (ExecutionResult,
 /// Semantics follow POSIX sh:
 /// - **`;`**  — always run the next pipeline regardless of the previous exit code.
 /// - **`&&`** — run the next pipeline only if the previous returned exit
-///              code `0` (success).
+///   code `0` (success).
 /// - **`||`** — run the next pipeline only if the previous returned a
-///              non-zero exit code (failure).
+///   non-zero exit code (failure).
 pub fn execute_list(entries: Vec<CommandEntry>, state: &mut ShellState) -> (ExecutionResult, i32) {
     let mut last_code: i32 = 0;
 
@@ -864,6 +879,7 @@ pub fn execute_list(entries: Vec<CommandEntry>, state: &mut ShellState) -> (Exec
 
         let (result, code) = execute(&entry.pipeline, state);
         last_code = code;
+        state.last_exit_code = code;
 
         if let ExecutionResult::Exit = result {
             return (ExecutionResult::Exit, last_code);
@@ -873,6 +889,28 @@ pub fn execute_list(entries: Vec<CommandEntry>, state: &mut ShellState) -> (Exec
     (ExecutionResult::KeepRunning, last_code)
 }
 
+/// Call the shell function `name` with `args` as its positional
+/// parameters, putting the caller's back afterwards. Returns `None` if
+/// there is no such function.
+pub fn call_function(
+    name: &str,
+    args: &[String],
+    state: &mut ShellState,
+) -> Option<(ExecutionResult, i32)> {
+    let body = state.functions.get(name).cloned()?;
+    let saved_args = std::mem::replace(&mut state.positional_args, args.to_vec());
+    let saved_params: Vec<String> = (1..)
+        .map_while(|i| state.get_var_string(&i.to_string()))
+        .collect();
+    builtins::set::set_positional_params(args, state);
+    state.specials.call_stack.push(name.to_string());
+    let result = execute_list(body, state);
+    state.specials.call_stack.pop();
+    builtins::set::set_positional_params(&saved_params, state);
+    state.positional_args = saved_args;
+    Some(result)
+}
+
 fn execute_block_with_redirects(
     pipeline: &Pipeline,
     node: &crate::parser::CommandNode,
@@ -886,20 +924,23 @@ fn execute_block_with_redirects(
     // We need to format the specific command node.
     command
         .arg("-c")
-        .arg(crate::engine::job_control::format_node_full(node));
+        .arg(crate::engine::job_control::format_node_full(node))
+        .env_clear()
+        .envs(state.exported_env())
+        .env("CERF_SUBSHELL", (state.specials.subshell + 1).to_string());
 
     let (stdin_redir, stdout_redir) = resolve_redirects(redirects);
 
     if let Some(redir) = stdin_redir {
         let mut expanded_redir = redir.clone();
-        expanded_redir.file = expand_vars(&redir.file, &state.variables);
+        expanded_redir.file = expand_vars(&redir.file, state);
         if let Ok(f) = open_stdin_redirect(&expanded_redir) {
             command.stdin(Stdio::from(f));
         }
     }
     if let Some(redir) = stdout_redir {
         let mut expanded_redir = redir.clone();
-        expanded_redir.file = expand_vars(&redir.file, &state.variables);
+        expanded_redir.file = expand_vars(&redir.file, state);
         if let Ok(f) = open_stdout_redirect(&expanded_redir) {
             command.stdout(Stdio::from(f));
         }
@@ -939,7 +980,6 @@ fn execute_block_with_redirects(
                     command: crate::engine::job_control::format_command(pipeline),
                     processes: vec![crate::engine::state::ProcessInfo {
                         pid,
-                        name: "block".to_string(),
                         state: crate::engine::state::JobState::Running,
                     }],
                     reported_done: false,
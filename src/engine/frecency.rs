@@ -0,0 +1,229 @@
+//! The database of visited directories behind `dir.jump`.
+//!
+//! Each line of the file is `path|rank|last-visit`, as in z. A visit adds
+//! one to a directory's rank; when the ranks add up to more than
+//! [`MAX_TOTAL_RANK`] they are all scaled down and the smallest dropped, so
+//! directories that are no longer visited are eventually forgotten.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::history::{replace_file, with_lock};
+
+/// Ranks are aged once they add up to more than this.
+const MAX_TOTAL_RANK: f64 = 9000.0;
+
+/// A visited directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirEntry {
+    pub path: PathBuf,
+    /// How often it was visited, aged over time.
+    pub rank: f64,
+    /// Unix time of the last visit.
+    pub last: i64,
+}
+
+impl DirEntry {
+    /// Rank weighted by how recently the directory was visited.
+    pub fn frecency(&self, now: i64) -> f64 {
+        let age = now - self.last;
+        if age < 3600 {
+            self.rank * 4.0
+        } else if age < 86400 {
+            self.rank * 2.0
+        } else if age < 7 * 86400 {
+            self.rank / 2.0
+        } else {
+            self.rank / 4.0
+        }
+    }
+}
+
+/// Parse the contents of a database file, skipping malformed lines.
+pub fn parse(contents: &str) -> Vec<DirEntry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            // The path may itself contain `|`.
+            let mut fields = line.rsplitn(3, '|');
+            let last = fields.next()?.parse().ok()?;
+            let rank = fields.next()?.parse().ok()?;
+            let path = fields.next().filter(|p| !p.is_empty())?;
+            Some(DirEntry {
+                path: PathBuf::from(path),
+                rank,
+                last,
+            })
+        })
+        .collect()
+}
+
+/// Format entries for the database file.
+pub fn format(entries: &[DirEntry]) -> String {
+    entries
+        .iter()
+        .map(|e| format!("{}|{}|{}\n", e.path.display(), e.rank, e.last))
+        .collect()
+}
+
+/// Read the database at `path`; a missing file is an empty database.
+pub fn load(path: &Path) -> Vec<DirEntry> {
+    fs::read_to_string(path)
+        .map(|contents| parse(&contents))
+        .unwrap_or_default()
+}
+
+/// Load the database at `path`, change it with `f` and write it back,
+/// holding the lock so that concurrent shells don't lose visits.
+pub fn update(path: &Path, f: impl FnOnce(&mut Vec<DirEntry>)) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    with_lock(path, || {
+        let mut entries = load(path);
+        f(&mut entries);
+        replace_file(path, &format(&entries))
+    })
+}
+
+/// Record a visit to `dir` at time `now`.
+pub fn visit(entries: &mut Vec<DirEntry>, dir: &Path, now: i64) {
+    match entries.iter_mut().find(|e| e.path == dir) {
+        Some(entry) => {
+            entry.rank += 1.0;
+            entry.last = now;
+        }
+        None => entries.push(DirEntry {
+            path: dir.to_path_buf(),
+            rank: 1.0,
+            last: now,
+        }),
+    }
+
+    if entries.iter().map(|e| e.rank).sum::<f64>() > MAX_TOTAL_RANK {
+        for entry in entries.iter_mut() {
+            entry.rank *= 0.99;
+        }
+        entries.retain(|e| e.rank >= 1.0);
+    }
+}
+
+/// Whether `path` matches every fragment, in order. The last fragment must
+/// match in the final component, so `proj` finds `~/projects` but not
+/// `~/projects/cerf/src`. Matching ignores case unless a fragment has an
+/// upper-case letter.
+pub fn matches(path: &Path, fragments: &[String]) -> bool {
+    let text = path.to_string_lossy();
+    let ignore_case = !fragments.iter().any(|f| f.chars().any(char::is_uppercase));
+    let text = if ignore_case {
+        text.to_lowercase()
+    } else {
+        text.into_owned()
+    };
+    let fragments: Vec<String> = if ignore_case {
+        fragments.iter().map(|f| f.to_lowercase()).collect()
+    } else {
+        fragments.to_vec()
+    };
+
+    let Some((last, rest)) = fragments.split_last() else {
+        return true;
+    };
+    let mut pos = 0;
+    for fragment in rest {
+        match text[pos..].find(fragment.as_str()) {
+            Some(i) => pos += i + fragment.len(),
+            None => return false,
+        }
+    }
+    let base = text.rfind(std::path::is_separator).map_or(0, |i| i + 1);
+    text[pos.max(base)..].contains(last.as_str())
+}
+
+/// The entries matching `fragments`, best first.
+pub fn ranked<'a>(
+    entries: &'a [DirEntry],
+    fragments: &[String],
+    now: i64,
+) -> Vec<(f64, &'a DirEntry)> {
+    let mut found: Vec<(f64, &DirEntry)> = entries
+        .iter()
+        .filter(|e| matches(&e.path, fragments))
+        .map(|e| (e.frecency(now), e))
+        .collect();
+    found.sort_by(|a, b| b.0.total_cmp(&a.0));
+    found
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frags(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let contents = "/home/a|3|1700000000\n/tmp/x|y|1.5|1700000100\nbroken\n";
+        let entries = parse(contents);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].path, PathBuf::from("/tmp/x|y"));
+        assert_eq!(entries[1].rank, 1.5);
+        assert_eq!(parse(&format(&entries)), entries);
+    }
+
+    #[test]
+    fn test_visit_and_aging() {
+        let mut entries = Vec::new();
+        visit(&mut entries, Path::new("/a"), 10);
+        visit(&mut entries, Path::new("/a"), 20);
+        visit(&mut entries, Path::new("/b"), 30);
+        assert_eq!(entries[0].rank, 2.0);
+        assert_eq!(entries[0].last, 20);
+
+        entries[0].rank = MAX_TOTAL_RANK;
+        visit(&mut entries, Path::new("/a"), 40);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].rank < MAX_TOTAL_RANK);
+    }
+
+    #[test]
+    fn test_matches() {
+        let path = Path::new("/home/ada/projects/Cerf");
+        assert!(matches(path, &frags("cerf")));
+        assert!(matches(path, &frags("proj cerf")));
+        assert!(matches(path, &frags("Cerf")));
+        assert!(!matches(path, &frags("CERF")));
+        assert!(!matches(path, &frags("cerf proj")));
+        assert!(!matches(path, &frags("proj")));
+    }
+
+    #[test]
+    fn test_ranked_prefers_recent() {
+        let now = 1_000_000;
+        let entries = vec![
+            DirEntry {
+                path: PathBuf::from("/old/src"),
+                rank: 10.0,
+                last: now - 30 * 86400,
+            },
+            DirEntry {
+                path: PathBuf::from("/new/src"),
+                rank: 2.0,
+                last: now - 60,
+            },
+            DirEntry {
+                path: PathBuf::from("/other"),
+                rank: 50.0,
+                last: now,
+            },
+        ];
+        let found = ranked(&entries, &frags("src"), now);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].1.path, PathBuf::from("/new/src"));
+    }
+}
@@ -0,0 +1,382 @@
+//! Command history entries and the history file format.
+//!
+//! Each entry is written as an optional metadata comment followed by the
+//! command itself:
+//!
+//! ```text
+//! #1718000000;status=0;ms=42;session=66a1b2c3-1f2e;cwd=/home/me/src
+//! cargo build
+//! ```
+//!
+//! The comment starts with the Unix timestamp, as in bash history files, so
+//! plain files written by older versions (or by bash) still load. `cwd` is
+//! always the last field so that it may contain `;`. Lines of a multi-line
+//! command end in `\`.
+//!
+//! Several shells may share one file. Every access holds an exclusive lock
+//! on `<file>.lock`, entries are only ever appended whole, and the file is
+//! rewritten by renaming a complete copy over it.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// One line of command history.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HistoryEntry {
+    pub line: String,
+    /// When the command was entered, in seconds since the Unix epoch.
+    pub timestamp: Option<i64>,
+    /// Working directory the command was run in, when known.
+    pub cwd: Option<PathBuf>,
+    /// Exit status, once the command has finished.
+    pub exit_code: Option<i32>,
+    /// How long the command took to run.
+    pub duration: Option<Duration>,
+    /// Identifier of the shell session that ran the command.
+    pub session: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(line: &str) -> Self {
+        Self {
+            line: line.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Parse the contents of a history file.
+pub fn parse(contents: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut meta: Option<HistoryEntry> = None;
+    let mut pending: Option<String> = None;
+
+    for l in contents.lines() {
+        if pending.is_none()
+            && let Some(entry) = parse_meta(l)
+        {
+            meta = Some(entry);
+            continue;
+        }
+        let (text, continued) = match l.strip_suffix('\\') {
+            Some(text) => (text, true),
+            None => (l, false),
+        };
+        let line = match pending.take() {
+            Some(mut prev) => {
+                prev.push('\n');
+                prev.push_str(text);
+                prev
+            }
+            None => text.to_string(),
+        };
+        if continued {
+            pending = Some(line);
+        } else if !line.is_empty() {
+            let mut entry = meta.take().unwrap_or_default();
+            entry.line = line;
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+/// Parse a `#<timestamp>[;key=value…]` metadata line.
+fn parse_meta(l: &str) -> Option<HistoryEntry> {
+    let rest = l.strip_prefix('#')?;
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    if digits == 0 {
+        return None;
+    }
+    let mut entry = HistoryEntry {
+        timestamp: rest[..digits].parse().ok(),
+        ..Default::default()
+    };
+    let mut fields = match &rest[digits..] {
+        "" => "",
+        f => f.strip_prefix(';')?,
+    };
+    while !fields.is_empty() {
+        if let Some(cwd) = fields.strip_prefix("cwd=") {
+            entry.cwd = Some(PathBuf::from(cwd));
+            break;
+        }
+        let (field, rest) = fields.split_once(';').unwrap_or((fields, ""));
+        match field.split_once('=') {
+            Some(("status", v)) => entry.exit_code = v.parse().ok(),
+            Some(("ms", v)) => entry.duration = v.parse().ok().map(Duration::from_millis),
+            Some(("session", v)) => entry.session = Some(v.to_string()),
+            _ => {}
+        }
+        fields = rest;
+    }
+    Some(entry)
+}
+
+/// Format an entry for the history file, including the trailing newline.
+pub fn format(entry: &HistoryEntry) -> String {
+    let mut out = String::new();
+    if let Some(ts) = entry.timestamp {
+        out.push_str(&format!("#{}", ts));
+        if let Some(code) = entry.exit_code {
+            out.push_str(&format!(";status={}", code));
+        }
+        if let Some(duration) = entry.duration {
+            out.push_str(&format!(";ms={}", duration.as_millis()));
+        }
+        if let Some(session) = &entry.session {
+            out.push_str(&format!(";session={}", session));
+        }
+        if let Some(cwd) = &entry.cwd {
+            out.push_str(&format!(
+                ";cwd={}",
+                cwd.to_string_lossy().replace('\n', "?")
+            ));
+        }
+        out.push('\n');
+    }
+    out.push_str(&entry.line.replace('\n', "\\\n"));
+    out.push('\n');
+    out
+}
+
+/// How much of the history file a shell has seen: the file's identity and
+/// length at the last read or write, and how many entries it held.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FileMark {
+    id: u64,
+    len: u64,
+    pub entries: usize,
+}
+
+/// Run `f` holding an exclusive lock on `<path>.lock`. The lock lives in a
+/// separate file because rewrites replace `path` itself.
+pub fn with_lock<T>(path: &Path, f: impl FnOnce() -> T) -> T {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let Ok(file) = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(PathBuf::from(lock_path))
+    else {
+        return f();
+    };
+    let mut lock = fd_lock::RwLock::new(file);
+    let _guard = lock.write();
+    f()
+}
+
+/// Replace the contents of `path` with `text`: it is written to a temporary
+/// file beside it, which is then renamed over it so readers never see a
+/// partial file.
+pub fn replace_file(path: &Path, text: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".tmp{}", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    let result = fs::write(&tmp, text)
+        .and_then(|()| match fs::metadata(path) {
+            Ok(meta) => fs::set_permissions(&tmp, meta.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Read the entries added to `path` since `mark` and move `mark` to the
+/// end of the file. When the file was replaced (e.g. compacted by another
+/// shell) every entry is returned, along with `true`.
+pub fn read_since(path: &Path, mark: &mut FileMark) -> io::Result<(Vec<HistoryEntry>, bool)> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let replaced = *mark != FileMark::default();
+            *mark = FileMark::default();
+            return Ok((Vec::new(), replaced));
+        }
+        Err(e) => return Err(e),
+    };
+    let meta = file.metadata()?;
+    let id = file_id(&meta);
+    let replaced = id != mark.id || meta.len() < mark.len;
+    let start = if replaced { 0 } else { mark.len };
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let entries = parse(&String::from_utf8_lossy(&bytes));
+
+    mark.id = id;
+    mark.len = start + bytes.len() as u64;
+    mark.entries = if replaced { 0 } else { mark.entries } + entries.len();
+    Ok((entries, replaced))
+}
+
+/// Append `entries` to `path`; `mark` must be up to date.
+pub fn append(path: &Path, entries: &[HistoryEntry], mark: &mut FileMark) -> io::Result<()> {
+    let text: String = entries.iter().map(format).collect();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    let meta = file.metadata()?;
+    mark.id = file_id(&meta);
+    mark.len = meta.len();
+    mark.entries += entries.len();
+    Ok(())
+}
+
+/// Replace `path` with `entries`, see [`replace_file`].
+pub fn rewrite(path: &Path, entries: &[HistoryEntry], mark: &mut FileMark) -> io::Result<()> {
+    let text: String = entries.iter().map(format).collect();
+    replace_file(path, &text)?;
+
+    let meta = fs::metadata(path)?;
+    *mark = FileMark {
+        id: file_id(&meta),
+        len: meta.len(),
+        entries: entries.len(),
+    };
+    Ok(())
+}
+
+/// Identifies a file across renames over its path.
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(windows)]
+fn file_id(meta: &fs::Metadata) -> u64 {
+    use std::os::windows::fs::MetadataExt;
+    meta.creation_time()
+}
+
+/// Which lines are kept in the history, from `HISTCONTROL` and `HISTIGNORE`.
+#[derive(Debug, Default)]
+pub struct HistoryControl {
+    /// Skip lines starting with a space.
+    pub ignore_space: bool,
+    /// Skip lines equal to the previous entry.
+    pub ignore_dups: bool,
+    /// Remove earlier copies of a line before adding it.
+    pub erase_dups: bool,
+    /// Glob patterns of lines to skip; `&` stands for the previous entry.
+    pub ignore: Vec<String>,
+}
+
+impl HistoryControl {
+    /// Build from the colon-separated `HISTCONTROL` and `HISTIGNORE` values.
+    pub fn new(histcontrol: &str, histignore: &str) -> Self {
+        let mut control = HistoryControl::default();
+        for word in histcontrol.split(':') {
+            match word {
+                "ignorespace" => control.ignore_space = true,
+                "ignoredups" => control.ignore_dups = true,
+                "ignoreboth" => {
+                    control.ignore_space = true;
+                    control.ignore_dups = true;
+                }
+                "erasedups" => control.erase_dups = true,
+                _ => {}
+            }
+        }
+        control.ignore = histignore
+            .split(':')
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect();
+        control
+    }
+
+    /// Whether `raw` (the line as typed) should be added after `previous`.
+    pub fn accepts(&self, raw: &str, previous: Option<&str>) -> bool {
+        let line = raw.trim();
+        if self.ignore_space && raw.starts_with(' ') {
+            return false;
+        }
+        if self.ignore_dups && previous == Some(line) {
+            return false;
+        }
+        !self.ignore.iter().any(|pattern| {
+            if pattern == "&" {
+                return previous == Some(line);
+            }
+            glob::Pattern::new(pattern).is_ok_and(|p| p.matches(line))
+        })
+    }
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let entry = HistoryEntry {
+            line: "if true {\n    echo hi\n}".to_string(),
+            timestamp: Some(1718000000),
+            cwd: Some(PathBuf::from("/tmp/a;b")),
+            exit_code: Some(1),
+            duration: Some(Duration::from_millis(42)),
+            session: Some("abc".to_string()),
+        };
+        let text = format!("{}{}", format(&entry), format(&HistoryEntry::new("ls")));
+        assert_eq!(parse(&text), vec![entry, HistoryEntry::new("ls")]);
+    }
+
+    #[test]
+    fn test_parse_plain_and_bash_files() {
+        let entries = parse("ls -l\n#1700000000\ncd /tmp\n# a comment\n");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], HistoryEntry::new("ls -l"));
+        assert_eq!(entries[1].timestamp, Some(1700000000));
+        assert_eq!(entries[1].line, "cd /tmp");
+        assert_eq!(entries[2].line, "# a comment");
+    }
+
+    #[test]
+    fn test_file_sharing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let (mut a, mut b) = (FileMark::default(), FileMark::default());
+
+        append(&path, &[HistoryEntry::new("from a")], &mut a).unwrap();
+        let (entries, replaced) = read_since(&path, &mut b).unwrap();
+        assert_eq!((entries.len(), replaced), (1, true));
+
+        read_since(&path, &mut b).unwrap();
+        append(&path, &[HistoryEntry::new("from b")], &mut b).unwrap();
+        let (entries, replaced) = read_since(&path, &mut a).unwrap();
+        assert_eq!(
+            (entries, replaced),
+            (vec![HistoryEntry::new("from b")], false)
+        );
+        assert_eq!(a.entries, 2);
+
+        rewrite(&path, &[HistoryEntry::new("compacted")], &mut b).unwrap();
+        let (entries, replaced) = read_since(&path, &mut a).unwrap();
+        assert_eq!((entries.len(), replaced), (1, true));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_control() {
+        let control = HistoryControl::new("ignoreboth", "ls:&:exit*");
+        assert!(control.accepts("make", Some("ls")));
+        assert!(!control.accepts(" make", None));
+        assert!(!control.accepts("make", Some("make")));
+        assert!(!control.accepts("ls", None));
+        assert!(!control.accepts("exit 1", None));
+        assert!(control.accepts("ls -l", None));
+    }
+}
@@ -0,0 +1,420 @@
+//! Bang history expansion for interactive input, as in csh and bash.
+//!
+//! A reference is an event designator, an optional word designator and any
+//! number of modifiers:
+//!
+//! - events: `!!` previous line, `!n` entry n, `!-n` n lines back,
+//!   `!prefix` newest line starting with prefix, `!?text?` newest line
+//!   containing text
+//! - words: `:0` command, `:n`, `:^` first argument, `:$` last, `:n-m`,
+//!   `:n*`, `:*` all arguments; the `:` may be left out before `^ $ *`
+//! - modifiers: `:h` dirname, `:t` basename, `:r` drop the suffix, `:e`
+//!   keep only the suffix, `:s/old/new/` (`:gs` for every match), `:p`
+//!   print without running
+//!
+//! `^old^new^` at the start of a line repeats the previous line with `old`
+//! replaced by `new`. Nothing is expanded inside single quotes, after a
+//! backslash, or when `!` is followed by a blank, `=`, `(` or `"`.
+
+use super::history::HistoryEntry;
+
+/// A line in which history references were replaced.
+#[derive(Debug, PartialEq)]
+pub struct Expansion {
+    pub line: String,
+    /// `:p` was given: show the line and record it, but don't run it.
+    pub print_only: bool,
+}
+
+/// Expand the history references in `line`. Returns `None` when there are
+/// none, and an error message (e.g. `!foo: event not found`) when a
+/// reference can't be resolved.
+pub fn expand(line: &str, history: &[HistoryEntry]) -> Result<Option<Expansion>, String> {
+    if let Some(rest) = line.strip_prefix('^') {
+        let previous = previous(history, "^")?;
+        let (old, new, tail) = split_substitution(rest, '^');
+        let replaced = substitute(previous, old, new, false)
+            .ok_or_else(|| format!("^{}: substitution failed", old))?;
+        return Ok(Some(Expansion {
+            line: replaced + tail,
+            print_only: false,
+        }));
+    }
+
+    let bytes = line.as_bytes();
+    let mut out = String::new();
+    let mut print_only = false;
+    let mut copied = 0;
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if !in_single => i += 1,
+            b'\'' if !in_double => in_single = !in_single,
+            b'"' if !in_single => in_double = !in_double,
+            b'!' if !in_single && is_reference(line, i) => {
+                let (text, len, print) = reference(&line[i..], history)?;
+                out.push_str(&line[copied..i]);
+                out.push_str(&text);
+                print_only |= print;
+                i += len;
+                copied = i;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if copied == 0 {
+        return Ok(None);
+    }
+    out.push_str(&line[copied..]);
+    Ok(Some(Expansion {
+        line: out,
+        print_only,
+    }))
+}
+
+/// Whether the `!` at byte `i` starts a history reference rather than
+/// being a literal (`! cmd`, `!=`, `$!`, `${!name}` …).
+fn is_reference(line: &str, i: usize) -> bool {
+    let before = &line[..i];
+    if before.ends_with('$') || before.ends_with("${") {
+        return false;
+    }
+    !matches!(
+        line.as_bytes().get(i + 1),
+        None | Some(b' ' | b'\t' | b'\n' | b'=' | b'(' | b'"')
+    )
+}
+
+/// Expand the reference at the start of `text` (which begins with `!`).
+/// Returns the replacement, the number of bytes consumed, and whether `:p`
+/// was given.
+fn reference(text: &str, history: &[HistoryEntry]) -> Result<(String, usize, bool), String> {
+    let bytes = text.as_bytes();
+    let mut i = 1;
+
+    let event = match bytes[1] {
+        b'!' => {
+            i = 2;
+            previous(history, "!!")?
+        }
+        b'$' | b'^' | b'*' | b':' => previous(history, "!")?,
+        b'-' | b'0'..=b'9' => {
+            let end = text[2..]
+                .find(|c: char| !c.is_ascii_digit())
+                .map_or(text.len(), |e| e + 2);
+            i = end;
+            let spec = &text[..end];
+            let n: i64 = text[1..end]
+                .parse()
+                .map_err(|_| format!("{}: event not found", spec))?;
+            let index = if n < 0 {
+                history.len() as i64 + n
+            } else {
+                n - 1
+            };
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| history.get(index))
+                .map(|entry| entry.line.as_str())
+                .ok_or_else(|| format!("{}: event not found", spec))?
+        }
+        b'?' => {
+            let end = text[2..].find(['?', '\n']).map_or(text.len(), |e| e + 2);
+            let needle = &text[2..end];
+            i = if text[end..].starts_with('?') {
+                end + 1
+            } else {
+                end
+            };
+            search(history, |line| line.contains(needle))
+                .ok_or_else(|| format!("!?{}: event not found", needle))?
+        }
+        _ => {
+            let end = text[1..]
+                .find(|c: char| c.is_whitespace() || ":;|&<>()".contains(c))
+                .map_or(text.len(), |e| e + 1);
+            let prefix = &text[1..end];
+            i = end;
+            search(history, |line| line.starts_with(prefix))
+                .ok_or_else(|| format!("!{}: event not found", prefix))?
+        }
+    };
+
+    // Word designator.
+    let mut result = event.to_string();
+    let designator = match bytes.get(i) {
+        Some(b'^' | b'$' | b'*') => Some(i),
+        Some(b':')
+            if bytes
+                .get(i + 1)
+                .is_some_and(|b| b"^$*-0123456789".contains(b)) =>
+        {
+            Some(i + 1)
+        }
+        _ => None,
+    };
+    if let Some(start) = designator {
+        let words = split_words(event);
+        let (selected, len) = select_words(&text[start..], &words)
+            .ok_or_else(|| format!("{}: bad word specifier", &text[..start + 1]))?;
+        result = selected;
+        i = start + len;
+    }
+
+    // Modifiers.
+    let mut print_only = false;
+    while bytes.get(i) == Some(&b':') {
+        let global = bytes.get(i + 1) == Some(&b'g');
+        let op = if global { i + 2 } else { i + 1 };
+        match bytes.get(op) {
+            Some(b'h') if !global => {
+                if let Some(slash) = result.rfind('/') {
+                    result.truncate(slash.max(1));
+                }
+            }
+            Some(b't') if !global => {
+                if let Some(slash) = result.rfind('/') {
+                    result.drain(..=slash);
+                }
+            }
+            Some(b'r') if !global => {
+                if let Some(dot) = suffix_start(&result) {
+                    result.truncate(dot);
+                }
+            }
+            Some(b'e') if !global => {
+                result = suffix_start(&result).map_or(String::new(), |dot| result[dot..].into());
+            }
+            Some(b'p') if !global => print_only = true,
+            Some(b's') => {
+                let Some(delim) = text[op + 1..].chars().next() else {
+                    return Err(format!("{}: substitution failed", &text[i..]));
+                };
+                let body = &text[op + 1 + delim.len_utf8()..];
+                let (old, new, rest) = split_substitution(body, delim);
+                result = substitute(&result, old, new, global)
+                    .ok_or_else(|| format!(":s{}{}{}: substitution failed", delim, old, delim))?;
+                i = text.len() - rest.len();
+                continue;
+            }
+            _ => {
+                let spec = text[i..].chars().take(3).collect::<String>();
+                return Err(format!("{}: unrecognized history modifier", spec));
+            }
+        }
+        i = op + 1;
+    }
+
+    Ok((result, i, print_only))
+}
+
+/// The previous history line, or an event-not-found error for `spec`.
+fn previous<'a>(history: &'a [HistoryEntry], spec: &str) -> Result<&'a str, String> {
+    history
+        .last()
+        .map(|entry| entry.line.as_str())
+        .ok_or_else(|| format!("{}: event not found", spec))
+}
+
+/// The newest history line matching `pred`.
+fn search(history: &[HistoryEntry], pred: impl Fn(&str) -> bool) -> Option<&str> {
+    history
+        .iter()
+        .rev()
+        .map(|entry| entry.line.as_str())
+        .find(|line| pred(line))
+}
+
+/// Split a history line into words: blank-separated, keeping quoted text
+/// together, with runs of `| & ; < > ( )` as words of their own.
+fn split_words(line: &str) -> Vec<&str> {
+    const OPERATORS: &str = "|&;<>()";
+    let bytes = line.as_bytes();
+    let mut words = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i].is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        if OPERATORS.as_bytes().contains(&bytes[i]) {
+            while i < bytes.len() && OPERATORS.as_bytes().contains(&bytes[i]) {
+                i += 1;
+            }
+        } else {
+            let mut quote = None;
+            while i < bytes.len() {
+                let b = bytes[i];
+                match quote {
+                    Some(q) if b == q => quote = None,
+                    Some(_) => {}
+                    None if b == b'\'' || b == b'"' => quote = Some(b),
+                    None if b == b'\\' => i += 1,
+                    None if b.is_ascii_whitespace() || OPERATORS.as_bytes().contains(&b) => break,
+                    None => {}
+                }
+                i += 1;
+            }
+        }
+        words.push(&line[start..i.min(line.len())]);
+    }
+    words
+}
+
+/// Parse a word designator at the start of `spec` and return the selected
+/// words joined by spaces, with the length of the designator.
+fn select_words(spec: &str, words: &[&str]) -> Option<(String, usize)> {
+    let last = words.len().checked_sub(1)?;
+    let number = |s: &str| -> Option<(usize, usize)> {
+        let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        Some((s[..len].parse().ok()?, len))
+    };
+
+    let (first, mut len) = match spec.as_bytes().first()? {
+        b'*' => {
+            return Some((words.get(1..).map_or(String::new(), |w| w.join(" ")), 1));
+        }
+        b'^' => (1, 1),
+        b'$' => (last, 1),
+        b'-' => (0, 0),
+        _ => number(spec)?,
+    };
+    let end = match spec.as_bytes().get(len) {
+        Some(b'*') => {
+            len += 1;
+            last
+        }
+        Some(b'-') => {
+            len += 1;
+            match spec.as_bytes().get(len) {
+                Some(b'$') => {
+                    len += 1;
+                    last
+                }
+                Some(b'0'..=b'9') => {
+                    let (n, l) = number(&spec[len..])?;
+                    len += l;
+                    n
+                }
+                _ => last.checked_sub(1)?,
+            }
+        }
+        _ => first,
+    };
+    if first > end || end > last {
+        return None;
+    }
+    Some((words[first..=end].join(" "), len))
+}
+
+/// Byte offset of the `.` starting the suffix of the last path component.
+fn suffix_start(path: &str) -> Option<usize> {
+    let dot = path.rfind('.')?;
+    let slash = path.rfind('/').map_or(0, |s| s + 1);
+    (dot > slash).then_some(dot)
+}
+
+/// Split `old<delim>new<delim>rest`; the final delimiter may be left out at
+/// the end of the line.
+fn split_substitution(text: &str, delim: char) -> (&str, &str, &str) {
+    let (old, after) = text.split_once(delim).unwrap_or((text, ""));
+    match after.split_once(delim) {
+        Some((new, rest)) => (old, new, rest),
+        None => {
+            // Without a closing delimiter the replacement ends at a blank.
+            let end = after.find(char::is_whitespace).unwrap_or(after.len());
+            (old, &after[..end], &after[end..])
+        }
+    }
+}
+
+/// Replace the first (or every) `old` in `text` with `new`, where `&` in
+/// `new` stands for `old`. `None` when `old` is empty or not found.
+fn substitute(text: &str, old: &str, new: &str, global: bool) -> Option<String> {
+    if old.is_empty() || !text.contains(old) {
+        return None;
+    }
+    let new = new.replace('&', old);
+    Some(if global {
+        text.replace(old, &new)
+    } else {
+        text.replacen(old, &new, 1)
+    })
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<HistoryEntry> {
+        [
+            "ls -l /usr/src",
+            "vim src/main.rs 'a b.txt'",
+            "cargo build --release",
+        ]
+        .into_iter()
+        .map(HistoryEntry::new)
+        .collect()
+    }
+
+    fn exp(line: &str) -> Result<String, String> {
+        expand(line, &history()).map(|e| e.map_or(line.to_string(), |e| e.line))
+    }
+
+    #[test]
+    fn test_events() {
+        assert_eq!(exp("sudo !!").unwrap(), "sudo cargo build --release");
+        assert_eq!(exp("!1").unwrap(), "ls -l /usr/src");
+        assert_eq!(exp("!-2").unwrap(), "vim src/main.rs 'a b.txt'");
+        assert_eq!(exp("!vi").unwrap(), "vim src/main.rs 'a b.txt'");
+        assert_eq!(exp("!?usr?; ls").unwrap(), "ls -l /usr/src; ls");
+        assert_eq!(exp("!nope").unwrap_err(), "!nope: event not found");
+        assert_eq!(exp("!9").unwrap_err(), "!9: event not found");
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(exp("vim !$").unwrap(), "vim --release");
+        assert_eq!(exp("echo !vim:2").unwrap(), "echo 'a b.txt'");
+        assert_eq!(exp("echo !vim:0 !vim:^").unwrap(), "echo vim src/main.rs");
+        assert_eq!(exp("echo !-3:1-2").unwrap(), "echo -l /usr/src");
+        assert_eq!(exp("echo !!:*").unwrap(), "echo build --release");
+        assert_eq!(exp("echo !!:5").unwrap_err(), "!!:5: bad word specifier");
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(exp("cd !ls:$:h").unwrap(), "cd /usr");
+        assert_eq!(exp("echo !vim:1:t:r").unwrap(), "echo main");
+        assert_eq!(exp("echo !vim:1:e").unwrap(), "echo .rs");
+        assert_eq!(exp("!!:s/build/test/").unwrap(), "cargo test --release");
+        assert_eq!(exp("!ls:gs/s/S/").unwrap(), "lS -l /uSr/Src");
+        assert_eq!(exp("^build^check").unwrap(), "cargo check --release");
+        assert_eq!(exp("!!:s").unwrap_err(), ":s: substitution failed");
+        let printed = expand("!!:p", &history()).unwrap().unwrap();
+        assert!(printed.print_only);
+    }
+
+    #[test]
+    fn test_literal_bangs() {
+        for line in [
+            "echo hi!",
+            "! true",
+            "test a != b",
+            "echo $! '!!' \\!!",
+            "echo \"wow!\"",
+        ] {
+            assert_eq!(expand(line, &history()), Ok(None), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Running the functions registered with `sys.hook`.
+
+use super::execution::call_function;
+use super::state::{HookEvent, ShellState};
+
+/// Run the functions hooked to `event`, in the order they were added, with
+/// `args` as their positional parameters. Like `PROMPT_COMMAND`, hooks
+/// leave `$?` alone. Hooks for an event don't fire again while they are
+/// running, so a `chpwd` hook may itself change directory.
+pub fn run_hooks(state: &mut ShellState, event: HookEvent, args: &[String]) {
+    let Some(functions) = state.hooks.get(&event).filter(|f| !f.is_empty()) else {
+        return;
+    };
+    let functions = functions.clone();
+    if !state.running_hooks.insert(event) {
+        return;
+    }
+
+    let saved_code = state.last_exit_code;
+    for name in functions {
+        // A hooked function that has since been removed is skipped.
+        let _ = call_function(&name, args, state);
+    }
+    state.last_exit_code = saved_code;
+    state.running_hooks.remove(&event);
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::set::set_positional_params;
+    use crate::engine::execute_list;
+    use crate::parser;
+
+    fn run(line: &str, state: &mut ShellState) {
+        let entries = parser::parse_pipeline(line, &state.variables).unwrap();
+        execute_list(entries, state);
+    }
+
+    #[test]
+    fn test_hooks_get_args_and_keep_status() {
+        let mut state = ShellState::new();
+        run("func record { SEEN=$$1; false; }", &mut state);
+        run("func again { COUNT=x$$COUNT; }", &mut state);
+        state.hooks.insert(
+            HookEvent::Preexec,
+            vec!["record".into(), "missing".into(), "again".into()],
+        );
+        state.positional_args = vec!["outer".into()];
+        set_positional_params(&["outer".into()], &mut state);
+        state.last_exit_code = 3;
+
+        run_hooks(&mut state, HookEvent::Preexec, &["ls -l".into()]);
+        assert_eq!(state.get_var_string("SEEN").as_deref(), Some("ls -l"));
+        assert_eq!(state.get_var_string("COUNT").as_deref(), Some("x"));
+        assert_eq!(state.last_exit_code, 3);
+        assert_eq!(state.positional_args, ["outer"]);
+        assert_eq!(state.get_var_string("1").as_deref(), Some("outer"));
+        assert_eq!(state.get_var_string("2"), None);
+        assert!(state.running_hooks.is_empty());
+
+        // Other events have no hooks.
+        run_hooks(&mut state, HookEvent::Precmd, &[]);
+        assert_eq!(state.get_var_string("COUNT").as_deref(), Some("x"));
+    }
+}
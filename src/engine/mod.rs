@@ -1,13 +1,20 @@
 mod alias;
+pub mod bookmarks;
+pub mod cerfenv;
 mod execution;
+pub mod frecency;
 mod glob;
+pub mod history;
+pub mod history_expand;
+pub mod hooks;
 pub mod job_control;
 pub mod path;
 mod redirect;
+pub mod specials;
 pub mod state;
 
 // Re-export the public API so that external code (`main.rs`, `builtins/`)
 // can continue to use `engine::ShellState`, `engine::ExecutionResult`, etc.
-pub use execution::execute_list;
+pub use execution::{call_function, execute_list};
 pub use path::{expand_home, find_executable};
 pub use state::{ExecutionResult, JobState, ShellState};
@@ -1,5 +1,7 @@
 use std::path::{Component, Path, PathBuf};
 
+use super::state::ShellState;
+
 /// Normalize a path logically (resolving . and ..) without hitting the disk.
 /// This also ensures the use of native path separators.
 pub fn normalize_path(path: &Path) -> PathBuf {
@@ -33,18 +35,51 @@ pub fn normalize_path(path: &Path) -> PathBuf {
 
 /// Expand `~` to the home directory and normalize the resulting path.
 pub fn expand_home(path_str: &str) -> PathBuf {
-    if path_str == "~" {
-        if let Some(home) = dirs::home_dir() {
-            return home;
-        }
-    } else if (path_str.starts_with("~/") || path_str.starts_with("~\\"))
-        && let Some(home) = dirs::home_dir() {
-            return normalize_path(&home.join(&path_str[2..]));
-        }
-    normalize_path(Path::new(path_str))
+    match expand_home_tilde(path_str) {
+        Some(expanded) => normalize_path(Path::new(&expanded)),
+        None => normalize_path(Path::new(path_str)),
+    }
+}
+
+/// Expand a leading `~` in `word` to the home directory. Unlike
+/// [`expand_tilde`] this leaves `~name` alone: bookmarks are expanded by the
+/// parser before variables, so that one never comes from a variable's value.
+pub fn expand_home_tilde(word: &str) -> Option<String> {
+    let (name, tail) = split_tilde(word)?;
+    if !name.is_empty() {
+        return None;
+    }
+    Some(format!("{}{}", dirs::home_dir()?.display(), tail))
+}
+
+/// Expand a leading `~` or `~name` in `word` to the home directory or the
+/// bookmark NAME, leaving the rest of the word as it is. Returns `None` if
+/// the word does not start with `~` or names no bookmark.
+pub fn expand_tilde(word: &str) -> Option<String> {
+    let (name, tail) = split_tilde(word)?;
+    let base = if name.is_empty() {
+        dirs::home_dir()?
+    } else {
+        super::bookmarks::get(name)?
+    };
+    Some(format!("{}{}", base.display(), tail))
+}
+
+/// Split `~name/rest` into `name` and `/rest`.
+fn split_tilde(word: &str) -> Option<(&str, &str)> {
+    let rest = word.strip_prefix('~')?;
+    let end = rest.find(std::path::is_separator).unwrap_or(rest.len());
+    Some(rest.split_at(end))
+}
+
+/// Find `cmd` the way the shell runs it: directly if it contains a path
+/// separator, otherwise in the directories of the shell's `PATH`.
+pub fn find_executable(cmd: &str, state: &ShellState) -> Option<PathBuf> {
+    find_in_path(cmd, state.get_var_string("PATH").as_deref())
 }
 
-pub fn find_executable(cmd: &str) -> Option<PathBuf> {
+/// Like [`find_executable`], searching the directories in `path`.
+pub fn find_in_path(cmd: &str, path: Option<&str>) -> Option<PathBuf> {
     let cmd_path = expand_home(cmd);
 
     // 1. If it has a separator, check it directly
@@ -53,8 +88,8 @@ pub fn find_executable(cmd: &str) -> Option<PathBuf> {
     }
 
     // 2. Search PATH
-    if let Ok(paths) = std::env::var("PATH") {
-        for path in std::env::split_paths(&paths) {
+    if let Some(paths) = path {
+        for path in std::env::split_paths(paths) {
             if let Some(found) = check_path(path.join(cmd)) {
                 return Some(found);
             }
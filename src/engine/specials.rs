@@ -0,0 +1,193 @@
+//! Special variables whose values the shell works out when they are read.
+//!
+//! `RANDOM` and `SECONDS` can be assigned to, which seeds the generator and
+//! restarts the count; `LINENO` can be assigned a line to count from. The
+//! rest are readonly.
+
+use std::cell::Cell;
+use std::time::Instant;
+
+use super::state::Variable;
+
+/// The special variables, in the order `help` would list them.
+const NAMES: [&str; 9] = [
+    "RANDOM",
+    "SECONDS",
+    "LINENO",
+    "EPOCHSECONDS",
+    "EPOCHREALTIME",
+    "CERF_PID",
+    "CERF_SUBSHELL",
+    "FUNCNAME",
+    "CERF_SOURCE",
+];
+
+/// What the special variables are computed from.
+pub struct Specials {
+    /// The line being run, counted from 1 in scripts and sourced files and
+    /// from the first command line in an interactive shell.
+    pub lineno: usize,
+    /// How many subshells deep this shell is; 0 for the top-level shell.
+    pub subshell: usize,
+    /// The functions being run, outermost first.
+    pub call_stack: Vec<String>,
+    /// The scripts and sourced files being read, outermost first.
+    pub source_stack: Vec<String>,
+    seconds_start: Instant,
+    seconds_offset: u64,
+    random: Cell<u32>,
+}
+
+impl Specials {
+    pub fn new(subshell: usize) -> Self {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            lineno: 0,
+            subshell,
+            call_stack: Vec::new(),
+            source_stack: Vec::new(),
+            seconds_start: Instant::now(),
+            seconds_offset: 0,
+            random: Cell::new(now.subsec_nanos() ^ std::process::id()),
+        }
+    }
+
+    pub fn is_special(name: &str) -> bool {
+        NAMES.contains(&name)
+    }
+
+    /// Whether `name` is a special variable that can't be assigned to.
+    pub fn is_readonly(name: &str) -> bool {
+        Self::is_special(name) && !matches!(name, "RANDOM" | "SECONDS" | "LINENO")
+    }
+
+    /// The current value of the special variable `name`, if it is one
+    /// and has a value. Reading `RANDOM` moves to the next number.
+    pub fn get(&self, name: &str) -> Option<Variable> {
+        let value = match name {
+            "RANDOM" => self.next_random().to_string(),
+            "SECONDS" => (self.seconds_offset + self.seconds_start.elapsed().as_secs()).to_string(),
+            "LINENO" => self.lineno.to_string(),
+            "EPOCHSECONDS" => chrono::Utc::now().timestamp().to_string(),
+            "EPOCHREALTIME" => {
+                let now = chrono::Utc::now();
+                format!("{}.{:06}", now.timestamp(), now.timestamp_subsec_micros())
+            }
+            "CERF_PID" => std::process::id().to_string(),
+            "CERF_SUBSHELL" => self.subshell.to_string(),
+            "FUNCNAME" => return stack(&self.call_stack),
+            "CERF_SOURCE" => return stack(&self.source_stack),
+            _ => return None,
+        };
+        let mut var = Variable::new_string(value);
+        var.readonly = Self::is_readonly(name);
+        Some(var)
+    }
+
+    /// Assign `value` to the special variable `name`. Returns false if
+    /// `name` is not one that can be assigned to.
+    pub fn assign(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "RANDOM" => self.random.set(value.trim().parse().unwrap_or(0)),
+            "SECONDS" => {
+                self.seconds_start = Instant::now();
+                self.seconds_offset = value.trim().parse().unwrap_or(0);
+            }
+            "LINENO" => self.lineno = value.trim().parse().unwrap_or(0),
+            _ => return false,
+        }
+        true
+    }
+
+    /// The next number from 0 to 32767, from a linear congruential
+    /// generator so that a seed always gives the same sequence.
+    fn next_random(&self) -> u32 {
+        let next = self
+            .random
+            .get()
+            .wrapping_mul(1_103_515_245)
+            .wrapping_add(12_345);
+        self.random.set(next);
+        (next >> 16) & 0x7fff
+    }
+}
+
+/// A call stack as an array, innermost first; unset when empty.
+fn stack(frames: &[String]) -> Option<Variable> {
+    if frames.is_empty() {
+        return None;
+    }
+    let mut var = Variable::new_array(frames.iter().rev().cloned().collect());
+    var.readonly = true;
+    Some(var)
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(specials: &Specials, name: &str) -> String {
+        specials.get(name).unwrap().value.as_string()
+    }
+
+    #[test]
+    fn test_random_is_seeded() {
+        let mut specials = Specials::new(0);
+        assert!(specials.assign("RANDOM", "42"));
+        let first: Vec<String> = (0..5).map(|_| value(&specials, "RANDOM")).collect();
+        specials.assign("RANDOM", "42");
+        let again: Vec<String> = (0..5).map(|_| value(&specials, "RANDOM")).collect();
+        assert_eq!(first, again);
+        assert!(first.iter().all(|n| n.parse::<u32>().unwrap() < 32768));
+        assert_ne!(first[0], first[1]);
+    }
+
+    #[test]
+    fn test_seconds_and_stacks() {
+        let mut specials = Specials::new(2);
+        specials.assign("SECONDS", "100");
+        assert!(value(&specials, "SECONDS").parse::<u64>().unwrap() >= 100);
+        assert_eq!(value(&specials, "CERF_SUBSHELL"), "2");
+        assert!(value(&specials, "EPOCHREALTIME").contains('.'));
+
+        assert!(specials.get("FUNCNAME").is_none());
+        specials.call_stack = vec!["outer".into(), "inner".into()];
+        assert_eq!(value(&specials, "FUNCNAME"), "inner outer");
+        assert!(Specials::is_readonly("FUNCNAME"));
+        assert!(!specials.assign("EPOCHSECONDS", "1"));
+        assert!(specials.get("HOME").is_none());
+    }
+
+    #[test]
+    fn test_shell_reads_specials() {
+        use crate::engine::{ShellState, call_function, execute_list};
+        use crate::parser;
+
+        let mut state = ShellState::new();
+        let define = "func who { WHO=$$FUNCNAME; }";
+        execute_list(parser::parse_pipeline(define, &state).unwrap(), &mut state);
+        call_function("who", &[], &mut state);
+        assert_eq!(state.get_var_string("WHO").as_deref(), Some("who"));
+        assert!(state.get_var("FUNCNAME").is_none());
+
+        state.specials.lineno = 1;
+        let entries = parser::parse_pipeline("FIRST=$LINENO\n\nTHIRD=${LINENO}", &state);
+        execute_list(entries.unwrap(), &mut state);
+        assert_eq!(state.get_var_string("FIRST").as_deref(), Some("1"));
+        assert_eq!(state.get_var_string("THIRD").as_deref(), Some("3"));
+
+        let seed = Variable::new_string("7".into());
+        state.set_var("RANDOM", seed).unwrap();
+        assert!(state.lookup_var("RANDOM").is_none());
+        assert!(
+            state
+                .set_var("CERF_PID", Variable::new_string("1".into()))
+                .is_err()
+        );
+        assert!(state.unset_var("EPOCHSECONDS").is_err());
+    }
+}
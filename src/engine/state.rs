@@ -1,7 +1,12 @@
-use std::collections::{HashMap, HashSet};
-use std::io::Write;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 
+pub use super::history::HistoryEntry;
+use super::specials::Specials;
+use super::{bookmarks, cerfenv, frecency};
+use super::history::{self, HistoryControl};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum JobState {
     Running,
@@ -22,7 +27,6 @@ impl std::fmt::Display for JobState {
 #[derive(Debug, Clone)]
 pub struct ProcessInfo {
     pub pid: u32,
-    pub name: String,
     pub state: JobState,
 }
 
@@ -58,6 +62,13 @@ pub struct Variable {
     pub readonly: bool,
     pub integer: bool,
     pub exported: bool,
+    /// `declare -n`: the value names another variable, which reads and
+    /// assignments go to instead.
+    pub nameref: bool,
+    /// `declare -l`: assigned values are lowercased.
+    pub lowercase: bool,
+    /// `declare -u`: assigned values are uppercased.
+    pub uppercase: bool,
 }
 
 impl Variable {
@@ -67,6 +78,9 @@ impl Variable {
             readonly: false,
             integer: false,
             exported: false,
+            nameref: false,
+            lowercase: false,
+            uppercase: false,
         }
     }
 
@@ -76,10 +90,96 @@ impl Variable {
             readonly: false,
             integer: false,
             exported: false,
+            nameref: false,
+            lowercase: false,
+            uppercase: false,
+        }
+    }
+
+    /// Apply the `-l`/`-u` attributes to the value.
+    pub fn fold_case(&mut self) {
+        let (lowercase, uppercase) = (self.lowercase, self.uppercase);
+        let fold = |s: &mut String| {
+            if lowercase {
+                *s = s.to_lowercase();
+            } else if uppercase {
+                *s = s.to_uppercase();
+            }
+        };
+        match &mut self.value {
+            VarValue::String(s) => fold(s),
+            VarValue::Array(a) => a.iter_mut().for_each(fold),
         }
     }
 }
 
+/// How to complete the arguments of a command, registered with `sys.complete`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompletionSpec {
+    /// `-W`: whitespace-separated words, expanded when completing.
+    pub words: Option<String>,
+    /// `-f`: file names.
+    pub files: bool,
+    /// `-d`: directory names.
+    pub dirs: bool,
+    /// `-X`: glob of candidates to drop (`!pattern` keeps only matches).
+    pub filter: Option<String>,
+    /// `-F`: function that fills `COMPREPLY`.
+    pub function: Option<String>,
+}
+
+/// A key binding made with `sys.bind`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBinding {
+    /// The key sequence in `sys.bind` notation, e.g. `\C-g`.
+    pub keys: String,
+    pub action: BindAction,
+}
+
+/// What a bound key sequence does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindAction {
+    /// An editor command such as `beginning-of-line`.
+    Function(String),
+    /// `-x`: a shell command, run with the line in `READLINE_LINE`.
+    Shell(String),
+}
+
+/// An event that functions registered with `sys.hook` run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookEvent {
+    /// The working directory changed.
+    Chpwd,
+    /// A command line is about to run; `$1` is its text.
+    Preexec,
+    /// The prompt is about to be shown.
+    Precmd,
+    /// The shell is exiting.
+    Exit,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 4] = [
+        HookEvent::Chpwd,
+        HookEvent::Preexec,
+        HookEvent::Precmd,
+        HookEvent::Exit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::Chpwd => "chpwd",
+            HookEvent::Preexec => "preexec",
+            HookEvent::Precmd => "precmd",
+            HookEvent::Exit => "exit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.name() == name)
+    }
+}
+
 impl Job {
     pub fn is_stopped(&self) -> bool {
         let all_suspended = self
@@ -119,6 +219,30 @@ impl Job {
     }
 }
 
+/// How many namerefs a name is followed through.
+const NAMEREF_DEPTH: usize = 8;
+
+/// The variable that `name` refers to once namerefs are followed, looking
+/// names up with `lookup`. A chain that is too long, such as a cycle, stops
+/// where it got to.
+pub fn resolve_nameref<'a>(name: &str, lookup: impl Fn(&str) -> Option<&'a Variable>) -> String {
+    let mut name = name.to_string();
+    for _ in 0..NAMEREF_DEPTH {
+        match lookup(&name) {
+            Some(Variable {
+                value: VarValue::String(target),
+                nameref: true,
+                ..
+            }) if !target.is_empty() => name = target.clone(),
+            _ => break,
+        }
+    }
+    name
+}
+
+/// Shell-internal variables that scripts can read but not change.
+const PROTECTED_VARS: [&str; 2] = ["CERF_VERSION", "PPID"];
+
 pub struct ShellState {
     pub previous_dir: Option<PathBuf>,
     pub dir_stack: Vec<PathBuf>,
@@ -134,8 +258,40 @@ pub struct ShellState {
     pub positional_args: Vec<String>,
     /// Shell options enabled via `set -o` / `set -e` etc.
     pub set_options: HashSet<String>,
+    /// Completion specs registered with `sys.complete`, by command name.
+    pub completions: HashMap<String, CompletionSpec>,
+    /// Key bindings made with `sys.bind`, in the order they were made.
+    pub key_bindings: Vec<KeyBinding>,
+    /// Functions registered with `sys.hook`, in the order they were added.
+    pub hooks: HashMap<HookEvent, Vec<String>>,
+    /// Events whose hooks are running, so that a hook can't retrigger itself.
+    pub running_hooks: HashSet<HookEvent>,
+    /// Whether the shell reads commands from a terminal.
+    pub interactive: bool,
+    /// The `.cerfenv` file in effect, with what it changed.
+    pub cerfenv: Option<cerfenv::Loaded>,
+    /// The untrusted `.cerfenv` last warned about, with its hash.
+    pub cerfenv_warned: Option<(PathBuf, String)>,
+    /// While recording, the value each variable had before `set_var` or
+    /// `unset_var` first changed it (`None` if it was unset).
+    pub var_journal: Option<HashMap<String, Option<Variable>>>,
+    /// What `RANDOM`, `LINENO`, `FUNCNAME` and the other special
+    /// variables are computed from.
+    pub specials: Specials,
     /// Command history (persisted to `~/.cerf_history`).
-    pub history: Vec<String>,
+    pub history: Vec<HistoryEntry>,
+    /// Number of leading `history` entries already written to `$HISTFILE`.
+    pub history_saved: usize,
+    /// How much of `$HISTFILE` this shell has read or written.
+    pub history_mark: history::FileMark,
+    /// The `$HISTFILE` and `$HISTSIZE` the history was loaded with.
+    history_loaded: (Option<PathBuf>, Option<usize>),
+    /// Identifies this shell in history entries.
+    pub session_id: String,
+    /// Exit status of the most recently executed pipeline (`\?` in prompts).
+    pub last_exit_code: i32,
+    /// Wall-clock time taken by the last interactive command line.
+    pub last_command_duration: Option<std::time::Duration>,
 
     // Job control
     pub jobs: HashMap<usize, Job>,
@@ -152,6 +308,13 @@ pub struct ShellState {
     pub iocp_receiver: Option<std::sync::mpsc::Receiver<crate::engine::job_control::IocpMessage>>,
 }
 
+/// Expansion sees local variables, namerefs and special variables.
+impl crate::parser::VarLookup for ShellState {
+    fn lookup_value(&self, name: &str) -> Option<String> {
+        self.get_var_string(name)
+    }
+}
+
 impl ShellState {
     pub fn new() -> Self {
         let mut variables = HashMap::new();
@@ -160,6 +323,21 @@ impl ShellState {
             var.exported = true;
             variables.insert(k, var);
         }
+        #[cfg(unix)]
+        variables.insert(
+            "PPID".to_string(),
+            Variable::new_string(std::os::unix::process::parent_id().to_string()),
+        );
+        for name in PROTECTED_VARS {
+            if let Some(var) = variables.get_mut(name) {
+                var.readonly = true;
+            }
+        }
+        // A subshell is told its depth through the environment.
+        let subshell = variables
+            .remove("CERF_SUBSHELL")
+            .and_then(|v| v.value.as_string().parse().ok())
+            .unwrap_or(0);
 
         let mut state = ShellState {
             previous_dir: None,
@@ -169,8 +347,27 @@ impl ShellState {
             scopes: Vec::new(),
             functions: HashMap::new(),
             positional_args: Vec::new(),
-            set_options: HashSet::new(),
+            set_options: HashSet::from([
+                "autosuggest".to_string(),
+                "emacs".to_string(),
+                "histexpand".to_string(),
+            ]),
+            completions: HashMap::new(),
+            key_bindings: Vec::new(),
+            hooks: HashMap::new(),
+            running_hooks: HashSet::new(),
+            interactive: false,
+            cerfenv: None,
+            cerfenv_warned: None,
+            var_journal: None,
+            specials: Specials::new(subshell),
             history: Vec::new(),
+            history_saved: 0,
+            history_mark: history::FileMark::default(),
+            history_loaded: (None, None),
+            session_id: new_session_id(),
+            last_exit_code: 0,
+            last_command_duration: None,
             jobs: HashMap::new(),
             next_job_id: 1,
             current_job: None,
@@ -192,11 +389,33 @@ impl ShellState {
             iocp_receiver: None,
         };
         state.load_history();
+        state.sync_dir_stack();
         state
     }
 
-    /// Set a variable in the current scope.
-    pub fn set_var(&mut self, name: &str, value: Variable) {
+    /// Set a variable in the current scope, or the variable a nameref
+    /// points to. Fails, changing nothing, if the variable is readonly.
+    ///
+    /// A `value` with the nameref attribute replaces `name` itself.
+    pub fn set_var(&mut self, name: &str, mut value: Variable) -> Result<(), String> {
+        let name = if value.nameref {
+            name.to_string()
+        } else {
+            self.resolve_name(name)
+        };
+        let name = name.as_str();
+        self.check_stored_writable(name)?;
+        if self.specials.assign(name, &value.value.as_string()) {
+            return Ok(());
+        }
+        self.record_previous(name);
+        // Assigning keeps the attributes that apply to every value.
+        if let Some(old) = self.lookup_var(name) {
+            value.exported |= old.exported;
+            value.lowercase |= old.lowercase;
+            value.uppercase |= old.uppercase;
+        }
+        value.fold_case();
         if self.scopes.is_empty() {
             self.variables.insert(name.to_string(), value);
         } else {
@@ -230,19 +449,87 @@ impl ShellState {
                 }
             }
         }
+        Ok(())
     }
 
-    /// Set a variable strictly in the current local scope (for `env.local`).
-    pub fn set_local_var(&mut self, name: &str, value: Variable) {
-        if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name.to_string(), value);
-        } else {
-            self.variables.insert(name.to_string(), value);
+    /// Remove a variable, or the variable a nameref points to, from the
+    /// innermost scope that has it, unless it is readonly.
+    pub fn unset_var(&mut self, name: &str) -> Result<(), String> {
+        let name = self.resolve_name(name);
+        let name = name.as_str();
+        self.check_stored_writable(name)?;
+        if Specials::is_special(name) {
+            // The shell keeps computing it.
+            return Ok(());
+        }
+        self.record_previous(name);
+        match self.scopes.iter_mut().rev().find(|s| s.contains_key(name)) {
+            Some(scope) => scope.remove(name),
+            None => self.variables.remove(name),
+        };
+        Ok(())
+    }
+
+    /// Fail with the shell's error message if `name` is readonly.
+    pub fn check_writable(&self, name: &str) -> Result<(), String> {
+        let target = self.resolve_name(name);
+        self.check_stored_writable(&target)
+            .map_err(|_| format!("{}: readonly variable", name))
+    }
+
+    /// Like [`Self::check_writable`], without following namerefs.
+    fn check_stored_writable(&self, name: &str) -> Result<(), String> {
+        if Specials::is_readonly(name) || self.lookup_var(name).is_some_and(|v| v.readonly) {
+            return Err(format!("{}: readonly variable", name));
+        }
+        Ok(())
+    }
+
+    /// Note the value of `name` in `var_journal`, if recording and it has
+    /// not been noted yet.
+    fn record_previous(&mut self, name: &str) {
+        if self
+            .var_journal
+            .as_ref()
+            .is_some_and(|j| !j.contains_key(name))
+        {
+            let previous = self.lookup_var(name).cloned();
+            if let Some(journal) = self.var_journal.as_mut() {
+                journal.insert(name.to_string(), previous);
+            }
+        }
+    }
+
+    /// Set `name` itself to exactly `value`, attributes and all, for
+    /// `env.declare` and `env.local`: in the current local scope, or with
+    /// `global`, outside any function. A readonly variable can't be
+    /// changed or shadowed.
+    pub fn declare_var(&mut self, name: &str, value: Variable, global: bool) -> Result<(), String> {
+        self.check_stored_writable(name)?;
+        if self.specials.assign(name, &value.value.as_string()) {
+            return Ok(());
+        }
+        self.record_previous(name);
+        match self.scopes.last_mut() {
+            Some(scope) if !global => scope.insert(name.to_string(), value),
+            _ => self.variables.insert(name.to_string(), value),
+        };
+        Ok(())
+    }
+
+    /// Get a variable looking up through scopes, following namerefs.
+    /// Special variables such as `RANDOM` are computed.
+    pub fn get_var(&self, name: &str) -> Option<Cow<'_, Variable>> {
+        let name = self.resolve_name(name);
+        if let Some(var) = self.specials.get(&name) {
+            return Some(Cow::Owned(var));
         }
+        self.lookup_var(&name).map(Cow::Borrowed)
     }
 
-    /// Get a variable looking up through scopes.
-    pub fn get_var(&self, name: &str) -> Option<&Variable> {
+    /// Get a variable looking up through scopes, without following
+    /// namerefs.
+    pub fn lookup_var(&self, name: &str) -> Option<&Variable> {
         for scope in self.scopes.iter().rev() {
             if let Some(v) = scope.get(name) {
                 return Some(v);
@@ -251,7 +538,18 @@ impl ShellState {
         self.variables.get(name)
     }
 
+    /// The variable that `name` refers to once namerefs are followed.
+    pub fn resolve_name(&self, name: &str) -> String {
+        resolve_nameref(name, |n| self.lookup_var(n))
+    }
+
     pub fn get_var_mut(&mut self, name: &str) -> Option<&mut Variable> {
+        let name = self.resolve_name(name);
+        self.lookup_var_mut(&name)
+    }
+
+    /// Like [`Self::get_var_mut`], without following namerefs.
+    pub fn lookup_var_mut(&mut self, name: &str) -> Option<&mut Variable> {
         for scope in self.scopes.iter_mut().rev() {
             if scope.contains_key(name) {
                 return scope.get_mut(name);
@@ -260,6 +558,26 @@ impl ShellState {
         self.variables.get_mut(name)
     }
 
+    /// The environment for child processes: the exported variables, with
+    /// a variable in an inner scope hiding any outer one of the same name.
+    /// Arrays are not exported.
+    pub fn exported_env(&self) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+        for scope in std::iter::once(&self.variables).chain(&self.scopes) {
+            for (name, var) in scope {
+                match &var.value {
+                    VarValue::String(value) if var.exported => {
+                        env.insert(name.clone(), value.clone());
+                    }
+                    _ => {
+                        env.remove(name);
+                    }
+                }
+            }
+        }
+        env
+    }
+
     /// Get a variable's string value.
     pub fn get_var_string(&self, name: &str) -> Option<String> {
         self.get_var(name).map(|v| v.value.as_string())
@@ -275,38 +593,333 @@ impl ShellState {
         self.scopes.pop();
     }
 
-    /// Load history entries from `~/.cerf_history` (if it exists).
+    /// Return cerf's configuration directory, `$XDG_CONFIG_HOME/cerf`.
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        self.get_var_string("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(dirs::config_dir)
+            .map(|d| d.join("cerf"))
+    }
+
+    /// Return the directory database used by `dir.jump`: `$CERF_DIRS_FILE`,
+    /// or `$XDG_DATA_HOME/cerf/dirs`. An empty `CERF_DIRS_FILE` turns it off.
+    pub fn dirs_file(&self) -> Option<PathBuf> {
+        if let Some(file) = self.get_var_string("CERF_DIRS_FILE") {
+            return Some(file)
+                .filter(|f| !f.is_empty())
+                .map(|f| crate::engine::expand_home(&f));
+        }
+        self.get_var_string("XDG_DATA_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(dirs::data_dir)
+            .map(|d| d.join("cerf").join("dirs"))
+    }
+
+    /// Return the file `dir.mark` keeps bookmarks in,
+    /// `$XDG_CONFIG_HOME/cerf/bookmarks`.
+    pub fn bookmarks_file(&self) -> Option<PathBuf> {
+        self.config_dir().map(|d| d.join("bookmarks"))
+    }
+
+    /// Return the file recording which `.cerfenv` files are trusted,
+    /// `$XDG_CONFIG_HOME/cerf/trust`.
+    pub fn cerfenv_trust_file(&self) -> Option<PathBuf> {
+        self.config_dir().map(|d| d.join("trust"))
+    }
+
+    /// Load the bookmarks file for `~name` expansion.
+    pub fn load_bookmarks(&self) {
+        if let Some(file) = self.bookmarks_file() {
+            bookmarks::watch(&file);
+        }
+    }
+
+    /// Record a visit to `dir` in the directory database.
+    pub fn record_dir_visit(&self, dir: &std::path::Path) {
+        if let Some(file) = self.dirs_file() {
+            let now = chrono::Local::now().timestamp();
+            let _ = frecency::update(&file, |entries| frecency::visit(entries, dir, now));
+        }
+    }
+
+    /// Set `DIRSTACK` to the directory stack as `dirs` shows it: `$PWD`,
+    /// then `dir_stack` from the top down.
+    pub fn sync_dir_stack(&mut self) {
+        let mut dirs = vec![self.get_var_string("PWD").unwrap_or_default()];
+        dirs.extend(
+            self.dir_stack
+                .iter()
+                .rev()
+                .map(|d| d.to_string_lossy().into_owned()),
+        );
+        let _ = self.set_var("DIRSTACK", Variable::new_array(dirs));
+    }
+
+    /// Load history entries from `$HISTFILE` (if it exists), keeping the
+    /// newest `$HISTSIZE`.
     pub fn load_history(&mut self) {
-        if let Some(path) = Self::history_path()
-            && path.exists()
-                && let Ok(contents) = std::fs::read_to_string(&path) {
-                    self.history = contents
-                        .lines()
-                        .filter(|l| !l.is_empty())
-                        .map(|l| l.to_string())
-                        .collect();
+        self.history.clear();
+        self.history_mark = history::FileMark::default();
+        if let Some(path) = self.history_path() {
+            let mark = &mut self.history_mark;
+            if let Ok((entries, _)) = history::with_lock(&path, || history::read_since(&path, mark))
+            {
+                self.history = entries;
+            }
+        }
+        self.history_saved = self.history.len();
+        self.trim_history();
+        self.history_loaded = (self.history_path(), self.history_limit("HISTSIZE"));
+    }
+
+    /// Load the history again if `HISTFILE` or `HISTSIZE` changed since it
+    /// was loaded, e.g. in a startup file.
+    pub fn reload_history_if_changed(&mut self) {
+        if self.history_loaded != (self.history_path(), self.history_limit("HISTSIZE")) {
+            self.load_history();
+        }
+    }
+
+    /// Add a command line to the in-memory history, subject to
+    /// `HISTCONTROL` and `HISTIGNORE`. `raw` is the line as typed, before
+    /// trimming. Returns whether an entry was added.
+    ///
+    /// The entry is written to `$HISTFILE` by [`Self::finish_history_entry`]
+    /// once the command has run.
+    pub fn add_history(&mut self, raw: &str) -> bool {
+        let control = self.history_control();
+        let line = raw.trim();
+        let previous = self.history.last().map(|e| e.line.as_str());
+        if line.is_empty() || !control.accepts(raw, previous) {
+            return false;
+        }
+
+        if control.erase_dups {
+            // The file's copies go when the entry is appended.
+            let saved = self.history_saved;
+            let mut index = 0;
+            self.history.retain(|entry| {
+                index += 1;
+                if entry.line != line {
+                    return true;
                 }
+                if index <= saved {
+                    self.history_saved -= 1;
+                }
+                false
+            });
+        }
+
+        self.history.push(HistoryEntry {
+            timestamp: Some(chrono::Local::now().timestamp()),
+            cwd: std::env::current_dir().ok(),
+            session: Some(self.session_id.clone()),
+            ..HistoryEntry::new(line)
+        });
+        self.trim_history();
+        true
     }
 
-    /// Append a single line to the in-memory history and to `~/.cerf_history`.
-    pub fn add_history(&mut self, line: &str) {
-        self.history.push(line.to_string());
-        if let Some(path) = Self::history_path()
-            && let Ok(mut f) = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)
-            {
-                let _ = writeln!(f, "{}", line);
+    /// `HISTCONTROL` and `HISTIGNORE`.
+    fn history_control(&self) -> HistoryControl {
+        HistoryControl::new(
+            &self.get_var_string("HISTCONTROL").unwrap_or_default(),
+            &self.get_var_string("HISTIGNORE").unwrap_or_default(),
+        )
+    }
+
+    /// Record how the most recently added history entry ended, then append
+    /// the unsaved entries to `$HISTFILE`.
+    pub fn finish_history_entry(&mut self, code: i32, duration: std::time::Duration) {
+        if let Some(entry) = self.history.get_mut(self.history_saved..).and_then(|e| e.last_mut())
+        {
+            entry.exit_code = Some(code);
+            entry.duration = Some(duration);
+        }
+        self.append_history();
+    }
+
+    /// Append the entries not yet saved to `$HISTFILE`. With the
+    /// `share_history` option, lines other shells have written are picked
+    /// up first. With `erasedups` in `HISTCONTROL`, earlier copies of the
+    /// new lines are taken out of the file.
+    ///
+    /// The file is compacted to `$HISTFILESIZE` entries only once it has
+    /// grown a tenth past that, so that it isn't rewritten after every
+    /// command; [`Self::compact_history`] trims it exactly on exit.
+    pub fn append_history(&mut self) {
+        let Some(path) = self.history_path() else {
+            return;
+        };
+        let share = self.set_options.contains("share_history");
+        let erase_dups = self.history_control().erase_dups;
+        history::with_lock(&path, || {
+            self.merge_history_file(&path, share);
+            if erase_dups {
+                self.erase_history_file_dups(&path);
+            }
+
+            let new = &self.history[self.history_saved.min(self.history.len())..];
+            if !new.is_empty() && history::append(&path, new, &mut self.history_mark).is_ok() {
+                self.history_saved = self.history.len();
             }
+            self.compact_history_file(&path, 10);
+        });
+    }
+
+    /// Rewrite `$HISTFILE` (the caller holds its lock) without the entries
+    /// repeated by an unsaved one, if it has any.
+    fn erase_history_file_dups(&mut self, path: &std::path::Path) {
+        let new: HashSet<&str> = self.history[self.history_saved.min(self.history.len())..]
+            .iter()
+            .map(|e| e.line.as_str())
+            .collect();
+        if new.is_empty() {
+            return;
+        }
+        let Ok((entries, _)) = history::read_since(path, &mut history::FileMark::default()) else {
+            return;
+        };
+        if entries.iter().any(|e| new.contains(e.line.as_str())) {
+            let kept: Vec<HistoryEntry> = entries
+                .into_iter()
+                .filter(|e| !new.contains(e.line.as_str()))
+                .collect();
+            let _ = history::rewrite(path, &kept, &mut self.history_mark);
+        }
+    }
+
+    /// Cut `$HISTFILE` down to its newest `$HISTFILESIZE` entries if it
+    /// holds more.
+    pub fn compact_history(&mut self) {
+        let Some(path) = self.history_path() else {
+            return;
+        };
+        history::with_lock(&path, || self.compact_history_file(&path, 0));
+    }
+
+    /// Rewrite `$HISTFILE` (the caller holds its lock) with its newest
+    /// `$HISTFILESIZE` entries once it holds more than that plus
+    /// `slack_percent` percent.
+    fn compact_history_file(&mut self, path: &std::path::Path, slack_percent: usize) {
+        let Some(max) = self.history_limit("HISTFILESIZE") else {
+            return;
+        };
+        let slack = max.saturating_mul(slack_percent) / 100;
+        if self.history_mark.entries <= max.saturating_add(slack) {
+            return;
+        }
+        if let Ok((entries, _)) = history::read_since(path, &mut history::FileMark::default()) {
+            let skip = entries.len().saturating_sub(max);
+            let _ = history::rewrite(path, &entries[skip..], &mut self.history_mark);
+        }
+    }
+
+    /// With the `share_history` option, add the lines other shells have
+    /// written to `$HISTFILE` since it was last read. Run before each prompt.
+    pub fn sync_history(&mut self) {
+        if self.set_options.contains("share_history") {
+            self.read_new_history();
+        }
+    }
+
+    /// Replace `$HISTFILE` with the newest `$HISTFILESIZE` in-memory entries.
+    pub fn write_history(&mut self) -> std::io::Result<()> {
+        let Some(path) = self.history_path() else {
+            return Ok(());
+        };
+        let skip = match self.history_limit("HISTFILESIZE") {
+            Some(max) => self.history.len().saturating_sub(max),
+            None => 0,
+        };
+        let entries = &self.history[skip..];
+        let mark = &mut self.history_mark;
+        history::with_lock(&path, || history::rewrite(&path, entries, mark))?;
+        self.history_saved = self.history.len();
+        Ok(())
+    }
+
+    /// Read the entries added to `$HISTFILE` since it was last read or
+    /// written (e.g. by other sessions). Returns how many were added.
+    pub fn read_new_history(&mut self) -> usize {
+        let Some(path) = self.history_path() else {
+            return 0;
+        };
+        history::with_lock(&path, || self.merge_history_file(&path, true))
+    }
+
+    /// Read what other shells have added to `$HISTFILE` (the caller holds
+    /// its lock) and, if `keep`, add it to the in-memory list ahead of the
+    /// unsaved entries. Returns how many entries were added.
+    fn merge_history_file(&mut self, path: &std::path::Path, keep: bool) -> usize {
+        let Ok((entries, replaced)) = history::read_since(path, &mut self.history_mark) else {
+            return 0;
+        };
+        if !keep {
+            return 0;
+        }
+        let at = self.history_saved.min(self.history.len());
+        let count = entries.len();
+        if replaced {
+            // Rewritten by another shell: it holds everything saved so far.
+            let unsaved = self.history.split_off(at);
+            self.history = entries;
+            self.history_saved = self.history.len();
+            self.history.extend(unsaved);
+        } else {
+            self.history.splice(at..at, entries);
+            self.history_saved = at + count;
+        }
+        self.trim_history();
+        count
+    }
+
+    /// Remove the entry at `index` from the in-memory history.
+    pub fn delete_history(&mut self, index: usize) {
+        self.history.remove(index);
+        if index < self.history_saved {
+            self.history_saved -= 1;
+        }
+    }
+
+    /// Drop the oldest in-memory entries beyond `$HISTSIZE`.
+    fn trim_history(&mut self) {
+        if let Some(max) = self.history_limit("HISTSIZE")
+            && self.history.len() > max
+        {
+            let excess = self.history.len() - max;
+            self.history.drain(..excess);
+            self.history_saved = self.history_saved.saturating_sub(excess);
+        }
     }
 
-    /// Return the path to `~/.cerf_history`.
-    fn history_path() -> Option<PathBuf> {
-        dirs::home_dir().map(|h| h.join(".cerf_history"))
+    /// A `HISTSIZE`-style limit; unset, empty, negative or non-numeric
+    /// values mean no limit.
+    fn history_limit(&self, name: &str) -> Option<usize> {
+        self.get_var_string(name)?.trim().parse().ok()
+    }
+
+    /// The history file, `$HISTFILE`; history is not saved when it is
+    /// unset or empty.
+    fn history_path(&self) -> Option<PathBuf> {
+        self.get_var_string("HISTFILE")
+            .filter(|p| !p.is_empty())
+            .map(|p| crate::engine::expand_home(&p))
     }
 }
 
+/// A session identifier unique enough to tell concurrent shells apart:
+/// the start time and process id, in hex.
+fn new_session_id() -> String {
+    format!(
+        "{:x}-{:x}",
+        chrono::Local::now().timestamp(),
+        std::process::id()
+    )
+}
+
 pub enum ExecutionResult {
     KeepRunning,
     Exit,
@@ -332,8 +945,7 @@ fn init_env_vars() -> HashMap<String, String> {
         }
         #[cfg(not(windows))]
         {
-            let user = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
-            return user;
+            std::env::var("USER").unwrap_or_else(|_| "unknown".to_string())
         }
     }
     // 1. Ensure HOME is set
@@ -382,11 +994,16 @@ fn init_env_vars() -> HashMap<String, String> {
         vars.insert("SHELL".to_string(), path.to_string_lossy().to_string());
     }
 
-    // 5. Ensure PWD is set
-    if !vars.contains_key("PWD")
-        && let Ok(cwd) = std::env::current_dir() {
+    // 5. Ensure PWD is set, keeping an inherited PWD only if it still names
+    //    the current directory (it may go through symlinks).
+    if let Ok(cwd) = std::env::current_dir() {
+        let inherited = vars.get("PWD").map(std::path::Path::new).is_some_and(|pwd| {
+            pwd.is_absolute() && pwd.canonicalize().ok() == cwd.canonicalize().ok()
+        });
+        if !inherited {
             vars.insert("PWD".to_string(), cwd.to_string_lossy().to_string());
         }
+    }
 
     // 6. Ensure XDG_CONFIG_HOME is set
     if !vars.contains_key("XDG_CONFIG_HOME") {
@@ -480,19 +1097,8 @@ fn init_env_vars() -> HashMap<String, String> {
             vars.insert("UID".to_string(), uid);
         }
     }
-    // 20. Ensure CERF_VERSION is set
-    if !vars.contains_key("CERF_VERSION") {
-        vars.insert("CERF_VERSION".to_string(), env!("CARGO_PKG_VERSION").to_string());
-    }
-    // Sync environment variables that we just added defaults for
-    for (key, val) in &vars {
-        if std::env::var(key).is_err() {
-            unsafe {
-                std::env::set_var(key, val);
-            }
-        }
-    }
-
+    // 20. CERF_VERSION is always this shell's version, even when inherited
+    vars.insert("CERF_VERSION".to_string(), env!("CARGO_PKG_VERSION").to_string());
     vars
 }
 
@@ -505,6 +1111,7 @@ fn init_default_aliases() -> HashMap<String, String> {
         ("pushd", "dir.pushd"),
         ("popd", "dir.popd"),
         ("dirs", "dir.dirs"),
+        ("z", "dir.jump"),
         ("jobs", "job.list"),
         ("fg", "job.fg"),
         ("bg", "job.bg"),
@@ -514,6 +1121,7 @@ fn init_default_aliases() -> HashMap<String, String> {
         ("untether", "job.untether"),
         ("export", "env.export"),
         ("unset", "env.unset"),
+        ("readonly", "env.readonly"),
         ("set", "env.set"),
         ("source", "env.source"),
         (".", "env.source"),
@@ -523,6 +1131,8 @@ fn init_default_aliases() -> HashMap<String, String> {
         ("clear", "sys.clear"),
         ("exec", "sys.exec"),
         ("history", "sys.history"),
+        ("complete", "sys.complete"),
+        ("bind", "sys.bind"),
         ("help", "sys.help"),
         ("type", "sys.type"),
         ("echo", "io.echo"),
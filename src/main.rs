@@ -1,41 +1,51 @@
 mod builtins;
+mod cli;
+mod editor;
 mod engine;
 mod parser;
+mod prompt;
 mod signals;
+mod startup;
 
 use engine::ShellState;
-use rustyline::DefaultEditor;
+use engine::hooks::run_hooks;
+use engine::state::HookEvent;
+use editor::CerfHelper;
+use rustyline::{CompletionType, Editor};
+use rustyline::history::DefaultHistory;
+#[cfg(windows)]
 use rustyline::ExternalPrinter;
 use rustyline::error::ReadlineError;
+use std::cell::RefCell;
 use std::env;
+use std::rc::Rc;
 use std::sync::atomic::AtomicUsize;
 
 pub static FG_JOB: AtomicUsize = AtomicUsize::new(0);
 
-fn get_prompt() -> String {
-    let cwd = env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
-    let home = dirs::home_dir();
-
-    let path_str = if let Some(home) = home {
-        if cwd.starts_with(&home) {
-            let relative = cwd.strip_prefix(&home).unwrap();
-            if relative.as_os_str().is_empty() {
-                "~".to_string()
-            } else {
-                let sep = std::path::MAIN_SEPARATOR;
-                format!("~{}{}", sep, relative.display())
-            }
-        } else {
-            cwd.display().to_string()
+fn main() -> rustyline::Result<()> {
+    let argv: Vec<String> = env::args().collect();
+    let mut cli = match cli::parse(argv.get(1..).unwrap_or_default()) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("cerf: {}", e);
+            eprintln!("Try 'cerf --help' for more information.");
+            std::process::exit(2);
         }
-    } else {
-        cwd.display().to_string()
     };
+    if cli.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if cli.version {
+        println!("{}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    // `login(1)` marks login shells with a leading dash in argv[0].
+    if argv.first().is_some_and(|a| a.starts_with('-')) {
+        cli.login = true;
+    }
 
-    format!("cf {} > ", path_str)
-}
-
-fn main() -> rustyline::Result<()> {
     signals::init();
 
     // Initialize job control
@@ -56,28 +66,36 @@ fn main() -> rustyline::Result<()> {
         state.shell_pgid = Some(nix::unistd::Pid::from_raw(nix::unistd::getpid().as_raw()));
     }
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() >= 3 && args[1] == "-c" {
-        let input = &args[2];
-        if let Some(entries) = parser::parse_pipeline(input, &state.variables) {
-            let _ = engine::execute_list(entries, &mut state);
-        }
-        return Ok(());
-    }
-    else if args.len() == 2 && args[1] == "--version" {
-        println!("{}", env!("CARGO_PKG_VERSION"));
-        return Ok(());
-    }
+    // Options from the command line apply before any startup file is read.
+    cli.apply_options(&mut state);
+    state.interactive = cli.is_interactive();
 
-    // Source the user profile (~/.cerfrc) for interactive sessions.
-    source_profile(&mut state);
+    // Read the system and user startup files (see `help startup`).
+    startup::source_startup_files(&mut state, &cli);
+    // They may have moved or resized the history.
+    state.reload_history_if_changed();
+
+    if !cli.is_interactive() {
+        let code = run_non_interactive(&cli, &mut state);
+        run_hooks(&mut state, HookEvent::Exit, &[]);
+        startup::source_logout_file(&mut state, &cli);
+        std::process::exit(code);
+    }
 
-    let config = rustyline::Config::builder().bracketed_paste(true).build();
-    let mut rl = DefaultEditor::with_config(config)?;
-    let mut printer_opt = rl.create_external_printer().ok();
+    // Load the `.cerfenv` of the directory the shell starts in.
+    engine::cerfenv::update(&mut state);
 
+    let config = rustyline::Config::builder()
+        .bracketed_paste(true)
+        .completion_type(CompletionType::List)
+        // Size limits and duplicates are handled by the shell's own history.
+        .max_history_size(usize::MAX)?
+        .history_ignore_dups(false)?
+        .build();
+    let mut rl: Editor<CerfHelper, DefaultHistory> = Editor::with_config(config)?;
     #[cfg(windows)]
     {
+        let mut printer_opt = rl.create_external_printer().ok();
         let (tx, rx) = std::sync::mpsc::channel::<engine::job_control::IocpMessage>();
         state.iocp_receiver = Some(rx);
         let handle = state.iocp_handle;
@@ -130,74 +148,134 @@ fn main() -> rustyline::Result<()> {
         });
     }
 
-    let mut input_buffer = String::new();
+    // The line editor's helper reads the shell state (e.g. for completion)
+    // while `readline` runs; the loop below holds it the rest of the time.
+    let shell = Rc::new(RefCell::new(state));
+    rl.set_helper(Some(CerfHelper::new(Rc::clone(&shell))));
+    editor::bind_keys(&mut rl);
+
+    let mut git_cache = prompt::GitCache::default();
+    // Text to start the next line with, split at the cursor: a command
+    // picked for editing in the Ctrl-R picker, or the line a `bind -x`
+    // command was run from.
+    let mut initial: Option<(String, String)> = None;
     loop {
-        // Poll for any background jobs that have finished
-        #[cfg(unix)]
-        engine::job_control::update_jobs(&mut state);
-
-        // Ensure shell owns the terminal
-        #[cfg(unix)]
-        engine::job_control::restore_terminal(&state);
-
-        let prompt = if input_buffer.is_empty() {
-            get_prompt()
-        } else {
-            state
-                .get_var_string("PS2")
-                .unwrap_or_else(|| "> ".to_string())
+        let (prompt, continuation) = {
+            let mut state = shell.borrow_mut();
+
+            // Poll for any background jobs that have finished
+            #[cfg(unix)]
+            engine::job_control::update_jobs(&mut state);
+
+            // Ensure shell owns the terminal
+            #[cfg(unix)]
+            engine::job_control::restore_terminal(&state);
+
+            run_hooks(&mut state, HookEvent::Precmd, &[]);
+            prompt::run_prompt_command(&mut state);
+            state.sync_history();
+            editor::sync_history(&mut rl, &state);
+            editor::sync_keymap(&mut rl, &state);
+            let continuation = prompt::render_ps2(&state, &mut git_cache);
+            (prompt::render_ps1(&state, &mut git_cache), continuation)
         };
+        if let Some(helper) = rl.helper_mut() {
+            helper.set_prompt(&prompt, &continuation);
+        }
 
-        let readline = rl.readline(&prompt);
+        // Incomplete input (open braces, trailing `|`, `,` …) is kept in the
+        // editor by the helper's validator, so `line` may span several lines,
+        // each after the first starting with room for `PS2`.
+        let readline = match initial.take() {
+            Some((left, right)) => rl.readline_with_initial(&prompt.plain, (&left, &right)),
+            None => rl.readline(&prompt.plain),
+        };
+        let mut state = shell.borrow_mut();
         match readline {
             Ok(line) => {
-                let trimmed = line.trim_end();
-
-                // Explicit comma continuation (kept for backwards compat).
-                if trimmed.ends_with(',') {
-                    input_buffer.push_str(&trimmed[..trimmed.len() - 1]);
-                    continue;
-                }
-
-                if input_buffer.is_empty() {
-                    input_buffer.push_str(&line);
-                } else {
-                    // Separate accumulated lines with a real newline so
-                    // the parser sees them as distinct commands.
-                    input_buffer.push('\n');
-                    input_buffer.push_str(&line);
+                let mut line = match rl.helper() {
+                    Some(helper) => helper.strip_continuation(&line),
+                    None => line,
+                };
+                // A key bound with `bind -x` was pressed: run its command,
+                // then go on editing the line.
+                if let Some(call) = rl.helper().and_then(|h| h.take_shell_call()) {
+                    match editor::keymap::run_shell_call(call, &mut state) {
+                        Some(edit) => {
+                            initial = Some(edit);
+                            continue;
+                        }
+                        None => break,
+                    }
                 }
-
-                // If the input looks incomplete (unbalanced braces, trailing
-                // operator, etc.), keep reading the next line.
-                if parser::is_incomplete(&input_buffer) {
-                    continue;
+                let picked = rl.helper().and_then(|h| h.take_picked());
+                if let Some(picked) = picked {
+                    // Take the accepted line off the screen; the command
+                    // chosen in the Ctrl-R picker is run as recorded or
+                    // read again for editing.
+                    print!("\x1b[1A\r\x1b[J");
+                    match picked {
+                        editor::picker::Pick::Run(picked) => {
+                            let display = rl.helper().map_or("", |h| h.display_prompt.as_str());
+                            println!("{}{}", display, picked);
+                            line = picked;
+                        }
+                        editor::picker::Pick::Insert(picked) => {
+                            let _ = std::io::Write::flush(&mut std::io::stdout());
+                            initial = Some((picked, String::new()));
+                            continue;
+                        }
+                    }
+                } else if state.set_options.contains("histexpand") {
+                    match engine::history_expand::expand(&line, &state.history) {
+                        Ok(Some(expansion)) => {
+                            // Show what `!!`, `!$` … turned into.
+                            println!("{}", expansion.line);
+                            line = expansion.line;
+                            if expansion.print_only {
+                                if state.add_history(&line) {
+                                    let _ = rl.add_history_entry(line.trim());
+                                }
+                                continue;
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            eprintln!("cerf: {}", e);
+                            state.last_exit_code = 1;
+                            continue;
+                        }
+                    }
                 }
 
-                let input = input_buffer.trim().to_string();
-                input_buffer.clear();
-
+                let input = line.trim();
                 if input.is_empty() {
                     continue;
                 }
-                let _ = rl.add_history_entry(&input);
-                state.add_history(&input);
+                if state.add_history(&line) {
+                    let _ = rl.add_history_entry(input);
+                }
 
-                if let Some(entries) = parser::parse_pipeline(&input, &state.variables)
-                    && let (engine::ExecutionResult::Exit, _) = engine::execute_list(entries, &mut state) { break }
+                state.specials.lineno += 1;
+                if let Some(entries) = parser::parse_pipeline(input, &*state) {
+                    run_hooks(&mut state, HookEvent::Preexec, &[input.to_string()]);
+                    let started = std::time::Instant::now();
+                    let (result, code) = engine::execute_list(entries, &mut state);
+                    let elapsed = started.elapsed();
+                    state.last_command_duration = Some(elapsed);
+                    state.finish_history_entry(code, elapsed);
+                    state.specials.lineno += input.matches('\n').count();
+                    git_cache.mark_stale();
+                    if let engine::ExecutionResult::Exit = result {
+                        break;
+                    }
+                }
             }
             Err(ReadlineError::Interrupted) => {
-                input_buffer.clear();
+                state.last_exit_code = 130;
                 continue;
             }
             Err(ReadlineError::Eof) => {
-                if !input_buffer.is_empty() {
-                    let input = input_buffer.trim().to_string();
-                    input_buffer.clear();
-                    if let Some(entries) = parser::parse_pipeline(&input, &state.variables) {
-                        let _ = engine::execute_list(entries, &mut state);
-                    }
-                }
                 println!("exit");
                 break;
             }
@@ -207,16 +285,55 @@ fn main() -> rustyline::Result<()> {
             }
         }
     }
+
+    run_hooks(&mut shell.borrow_mut(), HookEvent::Exit, &[]);
+    startup::source_logout_file(&mut shell.borrow_mut(), &cli);
+    shell.borrow_mut().compact_history();
     Ok(())
 }
 
-/// Source `~/.cerfrc` if it exists.
-fn source_profile(state: &mut ShellState) {
-    if let Some(home) = dirs::home_dir() {
-        let rc_path = home.join(".cerfrc");
-        if rc_path.exists() {
-            let path_str = rc_path.to_string_lossy().to_string();
-            builtins::source::run(&[path_str], state);
-        }
+/// Run the `-c` command or the script file and return its exit status.
+///
+/// With `-n` (`noexec`) the input is only checked for syntax errors.
+fn run_non_interactive(cli: &cli::Cli, state: &mut ShellState) -> i32 {
+    let mut first_line = 1;
+    let input = match (&cli.command, &cli.script) {
+        (Some(command), _) => command.clone(),
+        (None, Some(script)) => match std::fs::read_to_string(engine::expand_home(script)) {
+            // Skip a `#!` interpreter line so it isn't parsed as a command.
+            Ok(contents) if contents.starts_with("#!") => {
+                first_line = 2;
+                contents.split_once('\n').map(|(_, rest)| rest).unwrap_or("").to_string()
+            }
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("cerf: {}: {}", script, e);
+                return 127;
+            }
+        },
+        (None, None) => return 0,
+    };
+    state.specials.lineno = first_line;
+    if let (None, Some(script)) = (&cli.command, &cli.script) {
+        state.specials.source_stack.push(script.clone());
+    }
+
+    if !cli.args.is_empty() {
+        builtins::set::set_positional_params(&cli.args, state);
+    }
+
+    if state.set_options.contains("noexec") {
+        return match parser::check_syntax(&input) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("cerf: {}", e);
+                2
+            }
+        };
+    }
+
+    match parser::parse_pipeline(&input, state) {
+        Some(entries) => engine::execute_list(entries, state).1,
+        None => 0,
     }
 }
@@ -3,18 +3,30 @@
 /// Substitution rules (mirrors POSIX sh behaviour):
 /// - `$$`        → a literal `$`
 /// - `$VAR`      → the value of the variable `VAR`
-///                 (identifier chars: ASCII alphanumeric + `_`)
+///   (identifier chars: ASCII alphanumeric + `_`)
 /// - `${VAR}`    → same, with brace delimiters
+/// - `$1` … `$9`, `$#` → positional parameters and their count; as in
+///   POSIX sh, `$10` is `$1` followed by `0` and `${10}` is the tenth
 /// - Bare `$` with no following identifier or `{` → kept as-is
-pub fn expand_vars(
-    input: &str,
-    shell_vars: &std::collections::HashMap<String, crate::engine::state::Variable>,
-) -> String {
+///
+/// A nameref expands to the variable it points to. `$LINENO` counts on
+/// from the value of `LINENO` with each line of `input`.
+pub fn expand_vars<V: VarLookup + ?Sized>(input: &str, shell_vars: &V) -> String {
     let mut result = String::with_capacity(input.len());
     let mut chars = input.chars().peekable();
+    let mut line = 0;
+    let lookup = |name: &str, line: usize| match name {
+        "LINENO" => shell_vars
+            .lookup_value(name)
+            .and_then(|n| n.parse::<usize>().ok())
+            .map(|n| (n + line).to_string())
+            .unwrap_or_default(),
+        _ => shell_vars.lookup_value(name).unwrap_or_default(),
+    };
 
     while let Some(ch) = chars.next() {
         if ch != '$' {
+            line += usize::from(ch == '\n');
             result.push(ch);
             continue;
         }
@@ -29,10 +41,7 @@ pub fn expand_vars(
             Some('{') => {
                 chars.next(); // consume '{'
                 let var_name: String = chars.by_ref().take_while(|&c| c != '}').collect();
-                let value = shell_vars
-                    .get(&var_name)
-                    .map(|v| v.value.as_string())
-                    .unwrap_or_default();
+                let value = lookup(&var_name, line);
                 result.push_str(&value);
             }
             // $VAR style — identifier starts with alpha or '_'
@@ -42,10 +51,13 @@ pub fn expand_vars(
                         chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_')
                     }))
                     .collect();
-                let value = shell_vars
-                    .get(&var_name)
-                    .map(|v| v.value.as_string())
-                    .unwrap_or_default();
+                let value = lookup(&var_name, line);
+                result.push_str(&value);
+            }
+            // $1 … $9 and $# — single-character special parameters
+            Some(&c) if c.is_ascii_digit() || c == '#' => {
+                chars.next();
+                let value = lookup(c.to_string().as_str(), line);
                 result.push_str(&value);
             }
             // Bare $ with no following identifier → keep as-is
@@ -58,12 +70,76 @@ pub fn expand_vars(
     result
 }
 
+/// Expand a leading `~` or `~name` of each unquoted word in `input` (see
+/// [`expand_tilde`](crate::engine::path::expand_tilde)).
+///
+/// This runs on the line as typed, before [`expand_vars`], so that a `~`
+/// in a variable's value is left alone. The directory is put in as a quoted
+/// word, so that the parser keeps a path with spaces in one argument.
+pub fn expand_tildes(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut quote = None;
+    let mut word_start = true;
+    let mut rest = input;
+
+    while let Some(ch) = rest.chars().next() {
+        if ch == '~' && word_start && quote.is_none() {
+            let end = rest
+                .find(|c: char| {
+                    std::path::is_separator(c) || c.is_whitespace() || "\"'$;&|<>()".contains(c)
+                })
+                .unwrap_or(rest.len());
+            if let Some(dir) = crate::engine::path::expand_tilde(&rest[..end]) {
+                result.push_str(&super::format::quote_word(&dir));
+                rest = &rest[end..];
+                word_start = false;
+                continue;
+            }
+        }
+        match quote {
+            None if ch == '\'' || ch == '"' => quote = Some(ch),
+            Some(q) if ch == q => quote = None,
+            _ => {}
+        }
+        word_start = quote.is_none() && (ch.is_whitespace() || ";&|<>(".contains(ch));
+        result.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    result
+}
+
+/// Where [`expand_vars`] finds the values of variables.
+pub trait VarLookup {
+    /// The value of `name`, or `None` if it is unset.
+    fn lookup_value(&self, name: &str) -> Option<String>;
+}
+
+/// A plain table of variables, following namerefs within it.
+impl VarLookup for std::collections::HashMap<String, crate::engine::state::Variable> {
+    fn lookup_value(&self, name: &str) -> Option<String> {
+        let name = crate::engine::state::resolve_nameref(name, |n| self.get(n));
+        self.get(&name).map(|v| v.value.as_string())
+    }
+}
+
 // ── Tests ──────────────────────────────────────────────────────────────────
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_expand_tildes() {
+        let home = dirs::home_dir().unwrap().display().to_string();
+        let home = crate::parser::quote_word(&home);
+        assert_eq!(
+            expand_tildes("ls ~/src '~/src' \"~\" a~ ~;cd ~"),
+            format!("ls {home}/src '~/src' \"~\" a~ {home};cd {home}")
+        );
+        assert_eq!(expand_tildes("ls ~nosuchmark/x"), "ls ~nosuchmark/x");
+    }
+
     #[test]
     fn test_expand_known_var() {
         let mut vars = std::collections::HashMap::new();
@@ -121,10 +197,55 @@ mod tests {
         assert_eq!(expand_vars("$CERF_A/$CERF_B", &vars), "foo/bar");
     }
 
+    #[test]
+    fn test_expand_positional_params() {
+        let mut vars = std::collections::HashMap::new();
+        vars.insert(
+            "1".to_string(),
+            crate::engine::state::Variable::new_string("first".to_string()),
+        );
+        vars.insert(
+            "#".to_string(),
+            crate::engine::state::Variable::new_string("1".to_string()),
+        );
+        assert_eq!(expand_vars("$1 ($#) $2", &vars), "first (1) ");
+
+        vars.insert(
+            "10".to_string(),
+            crate::engine::state::Variable::new_string("tenth".to_string()),
+        );
+        assert_eq!(expand_vars("${10} $10", &vars), "tenth first0");
+    }
+
     #[test]
     fn test_expand_no_dollar_unchanged() {
         let vars = std::collections::HashMap::new();
         assert_eq!(expand_vars("ls -la", &vars), "ls -la");
         assert_eq!(expand_vars("", &vars), "");
     }
+
+    #[test]
+    fn test_expand_follows_nameref() {
+        let mut vars = std::collections::HashMap::new();
+        let mut reference = crate::engine::state::Variable::new_string("TARGET".to_string());
+        reference.nameref = true;
+        vars.insert("REF".to_string(), reference);
+        assert_eq!(expand_vars("[$REF]", &vars), "[]");
+        vars.insert(
+            "TARGET".to_string(),
+            crate::engine::state::Variable::new_string("value".to_string()),
+        );
+        assert_eq!(expand_vars("$REF ${REF}", &vars), "value value");
+    }
+
+    #[test]
+    fn test_lineno_counts_lines() {
+        let mut vars = std::collections::HashMap::new();
+        assert_eq!(expand_vars("[$LINENO]", &vars), "[]");
+        vars.insert(
+            "LINENO".to_string(),
+            crate::engine::state::Variable::new_string("10".to_string()),
+        );
+        assert_eq!(expand_vars("$LINENO\n\n${LINENO}", &vars), "10\n\n12");
+    }
 }
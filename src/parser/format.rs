@@ -0,0 +1,218 @@
+// ── Formatting commands back into source ──────────────────────────────────
+
+use super::ast::{Arg, CommandEntry, CommandNode, Connector, Pipeline, Redirect, RedirectKind};
+
+const INDENT: &str = "    ";
+
+/// Format a function definition so that sourcing it defines the function
+/// again.
+pub fn format_function(name: &str, body: &[CommandEntry]) -> String {
+    format!("func {} {}", name, format_block(body, 0))
+}
+
+/// Quote `word`, if needed, so that the shell reads it back unchanged.
+/// `$` is doubled, as variables are expanded before parsing, and a word
+/// with glob characters is quoted so that it isn't matched against files.
+pub fn quote_word(word: &str) -> String {
+    quote(&word.replace('$', "$$"), word.contains(['*', '?', '[']))
+}
+
+/// The value of an array assignment, `(one 'two words')`, quoted as a word
+/// so that `NAME=` followed by it gives the same elements back.
+pub fn quote_array(values: &[String]) -> String {
+    let elements: Vec<String> = values.iter().map(|v| quote(v, false)).collect();
+    quote_word(&format!("({})", elements.join(" ")))
+}
+
+/// Quote `word` if it needs it, or always with `force`.
+fn quote(word: &str, force: bool) -> String {
+    let special = |c: char| c.is_whitespace() || "\"';&|><{}".contains(c);
+    if word.is_empty() {
+        return "''".to_string();
+    }
+    if !force && !word.contains(special) {
+        return word.to_string();
+    }
+    // A `'` can't go inside single quotes, so it is double-quoted instead.
+    word.split('\'')
+        .map(|part| {
+            if part.is_empty() {
+                String::new()
+            } else {
+                format!("'{}'", part)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\"'\"")
+}
+
+/// `{`, one command line per line at `depth + 1`, and `}`. Lines end in
+/// `;`, which the parser needs after a nested block.
+fn format_block(entries: &[CommandEntry], depth: usize) -> String {
+    let inner = INDENT.repeat(depth + 1);
+    let mut out = String::from("{\n");
+    for line in format_lines(entries, depth + 1) {
+        out.push_str(&inner);
+        out.push_str(&line);
+        if !line.ends_with('&') {
+            out.push(';');
+        }
+        out.push('\n');
+    }
+    out.push_str(&INDENT.repeat(depth));
+    out.push('}');
+    out
+}
+
+/// The command lines of a list: `;` and `&` start a new line, `&&` and
+/// `||` continue the current one.
+fn format_lines(entries: &[CommandEntry], depth: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for entry in entries {
+        let pipeline = format_pipeline(&entry.pipeline, depth);
+        match (entry.connector, lines.last_mut()) {
+            (Some(Connector::And), Some(line)) => *line = format!("{} && {}", line, pipeline),
+            (Some(Connector::Or), Some(line)) => *line = format!("{} || {}", line, pipeline),
+            _ => lines.push(pipeline),
+        }
+    }
+    lines
+}
+
+/// A list on one line, as in the condition of an `if` or `while`.
+fn format_inline(entries: &[CommandEntry], depth: usize) -> String {
+    format_lines(entries, depth).join("; ")
+}
+
+fn format_pipeline(pipeline: &Pipeline, depth: usize) -> String {
+    let commands: Vec<String> = pipeline
+        .commands
+        .iter()
+        .map(|node| format_node(node, depth))
+        .collect();
+    let mut out = commands.join(" | ");
+    if pipeline.negated {
+        out.insert_str(0, "! ");
+    }
+    if pipeline.background {
+        out.push_str(" &");
+    }
+    out
+}
+
+fn format_node(node: &CommandNode, depth: usize) -> String {
+    let mut out = match node {
+        CommandNode::Simple(cmd) => {
+            let mut words: Vec<String> = cmd
+                .assignments
+                .iter()
+                .map(|(name, value)| match value.as_str() {
+                    "" => format!("{}=", name),
+                    value => format!("{}={}", name, quote_word(value)),
+                })
+                .collect();
+            words.extend(cmd.name.as_deref().map(quote_word));
+            words.extend(cmd.args.iter().map(format_arg));
+            words.join(" ")
+        }
+        CommandNode::If {
+            branches,
+            else_branch,
+            ..
+        } => {
+            let mut out = String::new();
+            for (i, (cond, body)) in branches.iter().enumerate() {
+                let keyword = if i == 0 { "if" } else { " elif" };
+                out.push_str(&format!(
+                    "{} {} {}",
+                    keyword,
+                    format_inline(cond, depth),
+                    format_block(body, depth)
+                ));
+            }
+            if let Some(body) = else_branch {
+                out.push_str(&format!(" else {}", format_block(body, depth)));
+            }
+            out
+        }
+        CommandNode::FuncDecl { name, body } => {
+            format!("func {} {}", name, format_block(body, depth))
+        }
+        CommandNode::For {
+            var, items, body, ..
+        } => {
+            let items: Vec<String> = items.iter().map(format_arg).collect();
+            format!(
+                "for {} in {} {}",
+                var,
+                items.join(" "),
+                format_block(body, depth)
+            )
+        }
+        CommandNode::While { cond, body, .. } => format!(
+            "while {} {}",
+            format_inline(cond, depth),
+            format_block(body, depth)
+        ),
+        CommandNode::Loop { body, .. } => format!("loop {}", format_block(body, depth)),
+        CommandNode::Break => "break".to_string(),
+        CommandNode::Continue => "continue".to_string(),
+    };
+    for redirect in node.redirects() {
+        out.push(' ');
+        out.push_str(&format_redirect(redirect));
+    }
+    out
+}
+
+fn format_arg(arg: &Arg) -> String {
+    quote(&arg.value.replace('$', "$$"), arg.quoted)
+}
+
+fn format_redirect(redirect: &Redirect) -> String {
+    let op = match redirect.kind {
+        RedirectKind::StdoutOverwrite => ">",
+        RedirectKind::StdoutAppend => ">>",
+        RedirectKind::StdinFrom => "<",
+    };
+    format!("{} {}", op, quote_word(&redirect.file))
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_line;
+
+    fn function_body(node: CommandNode) -> Vec<CommandEntry> {
+        match node {
+            CommandNode::FuncDecl { body, .. } => body,
+            other => panic!("not a function: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_quote_word() {
+        assert_eq!(quote_word("plain"), "plain");
+        assert_eq!(quote_word("two words"), "'two words'");
+        assert_eq!(quote_word("it's"), "'it'\"'\"'s'");
+        assert_eq!(quote_word("$HOME"), "$$HOME");
+        assert_eq!(quote_word(""), "''");
+        let values = ["a".to_string(), "b c".to_string(), "$x".to_string()];
+        assert_eq!(quote_array(&values), "'(a '\"'\"'b c'\"'\"' $$x)'");
+    }
+
+    #[test]
+    fn test_function_round_trips() {
+        let source = "func f { X=1 echo 'a b' \"*\" > out; if test.check $$1 = y && true { \
+                      echo yes | cat; } elif false { loop { break; }; } else { false & }; \
+                      for i in 1 2 { echo $$i; }; }";
+        let body = function_body(parse_line(source).unwrap());
+        let formatted = format_function("f", &body);
+        assert!(formatted.starts_with("func f {\n    X=1 echo 'a b' '*' > out;\n    if "));
+
+        let reparsed = parse_line(&formatted).unwrap();
+        assert_eq!(function_body(reparsed), body);
+    }
+}
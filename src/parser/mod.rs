@@ -1,12 +1,14 @@
 mod ast;
 mod combinators;
 mod expand;
+mod format;
 
 // Re-export the public surface so that `crate::parser::*` keeps working
 // for all existing callers (engine.rs, main.rs, etc.).
 pub use ast::{Arg, CommandEntry, CommandNode, Connector, Pipeline, Redirect, RedirectKind};
-pub use combinators::is_reserved_word;
-pub use expand::expand_vars;
+pub use combinators::{is_identifier, is_reserved_word, parse_arg};
+pub use expand::{VarLookup, expand_tildes, expand_vars};
+pub use format::{format_function, quote_array, quote_word};
 
 // ── Public API ────────────────────────────────────────────────────────────
 
@@ -56,9 +58,15 @@ pub fn is_incomplete(input: &str) -> bool {
         return true;
     }
 
-    // 3. Unbalanced braces (skip characters inside quotes).
+    // 3. Unbalanced braces.
+    brace_depth(s) > 0
+}
+
+/// Number of `{` not yet closed by a `}` in `input`, ignoring braces inside
+/// quotes and comments. Negative when there are more closing braces.
+pub fn brace_depth(input: &str) -> i32 {
     let mut depth: i32 = 0;
-    let mut chars = s.chars().peekable();
+    let mut chars = input.chars().peekable();
     while let Some(ch) = chars.next() {
         match ch {
             '"' => {
@@ -88,19 +96,16 @@ pub fn is_incomplete(input: &str) -> bool {
             _ => {}
         }
     }
-    depth > 0
+    depth
 }
 
 /// Parse an entire input line into a list of [`CommandEntry`] items.
 ///
 /// Returns `None` if the line is empty or a comment.
 /// Returns `Some(entries)` where `entries` has at least one element.
-pub fn parse_input(
-    input: &str,
-    shell_vars: &std::collections::HashMap<String, crate::engine::state::Variable>,
-) -> Option<Vec<CommandEntry>> {
+pub fn parse_input(input: &str, shell_vars: &(impl VarLookup + ?Sized)) -> Option<Vec<CommandEntry>> {
     let preprocessed = join_continuations(input);
-    let expanded = expand_vars(&preprocessed, shell_vars);
+    let expanded = expand_vars(&expand_tildes(&preprocessed), shell_vars);
     let s = expanded.trim();
     if s.is_empty() || s.starts_with('#') {
         return None;
@@ -125,18 +130,36 @@ pub fn parse_input(
     }
 }
 
+/// Parse `input` without executing or expanding it, returning the first
+/// syntax error (used by `cerf -n`).
+pub fn check_syntax(input: &str) -> Result<(), String> {
+    let preprocessed = join_continuations(input);
+    let s = preprocessed.trim();
+    if s.is_empty() || s.starts_with('#') {
+        return Ok(());
+    }
+
+    match combinators::parse_command_list(s) {
+        Ok((rem, _)) if !rem.trim().is_empty() => Err(format!(
+            "syntax error near unexpected token '{}'",
+            rem.trim()
+        )),
+        Ok(_) => Ok(()),
+        Err(_) => Err("syntax error: incomplete or invalid command".to_string()),
+    }
+}
+
 /// Backwards-compatible alias — kept so call-sites in main.rs don't break.
-pub fn parse_pipeline(
-    input: &str,
-    shell_vars: &std::collections::HashMap<String, crate::engine::state::Variable>,
-) -> Option<Vec<CommandEntry>> {
+pub fn parse_pipeline(input: &str, shell_vars: &(impl VarLookup + ?Sized)) -> Option<Vec<CommandEntry>> {
     parse_input(input, shell_vars)
 }
 
+#[cfg(test)]
 pub fn parse_line(input: &str) -> Option<CommandNode> {
     parse_line_with_vars(input, &std::collections::HashMap::new())
 }
 
+#[cfg(test)]
 pub fn parse_line_with_vars(
     input: &str,
     vars: &std::collections::HashMap<String, crate::engine::state::Variable>,
@@ -671,4 +694,11 @@ mod tests {
         let entries = parse_pipeline(input, &vars).unwrap();
         assert_eq!(entries.len(), 2);
     }
+
+    #[test]
+    fn test_check_syntax() {
+        assert!(check_syntax("echo hello\nif true { echo yes }").is_ok());
+        assert!(check_syntax("").is_ok());
+        assert!(check_syntax("if true { echo yes").is_err());
+    }
 }
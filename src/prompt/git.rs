@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Git metadata for prompt segments, cached per working directory.
+///
+/// Everything is read straight from `.git/HEAD` and `.git/index`; no `git`
+/// process is spawned. Results are reused until the size or modification
+/// time of either file changes, so redrawing the prompt or using several
+/// `\g{…}` segments only scans the repository once. Editing a tracked file
+/// doesn't touch either, so the dirty state is also dropped by
+/// [`GitCache::mark_stale`], which the REPL calls after every command.
+#[derive(Default)]
+pub struct GitCache {
+    dirs: HashMap<PathBuf, CachedRepo>,
+}
+
+#[derive(Default)]
+struct CachedRepo {
+    repo: Option<Repo>,
+    branch: Option<String>,
+    dirty: Option<bool>,
+    /// The size and modification time of `HEAD` and `index` that `branch`
+    /// and `dirty` were worked out from.
+    stamp: Stamp,
+}
+
+type Stamp = [Option<(u64, SystemTime)>; 2];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Repo {
+    git_dir: PathBuf,
+    work_tree: PathBuf,
+}
+
+impl GitCache {
+    /// Forget whether each repository is dirty; it is worked out again on
+    /// the next lookup.
+    pub fn mark_stale(&mut self) {
+        for entry in self.dirs.values_mut() {
+            entry.dirty = None;
+        }
+    }
+
+    /// Branch name (or short commit id when detached) for `cwd`.
+    pub fn branch(&mut self, cwd: &Path) -> Option<String> {
+        let entry = self.entry(cwd);
+        if entry.branch.is_none() {
+            entry.branch = entry.repo.as_ref().and_then(read_branch);
+        }
+        entry.branch.clone()
+    }
+
+    /// Whether a tracked file in the repository containing `cwd` differs from
+    /// the index. Returns `false` outside a repository.
+    pub fn is_dirty(&mut self, cwd: &Path) -> bool {
+        let entry = self.entry(cwd);
+        if entry.dirty.is_none() {
+            entry.dirty = Some(entry.repo.as_ref().is_some_and(index_is_dirty));
+        }
+        entry.dirty.unwrap_or(false)
+    }
+
+    fn entry(&mut self, cwd: &Path) -> &mut CachedRepo {
+        let entry = self.dirs.entry(cwd.to_path_buf()).or_default();
+        // A repository may have been created since the last look.
+        if entry.repo.is_none() {
+            entry.repo = discover(cwd);
+        }
+        let stamp = entry.repo.as_ref().map(stamp).unwrap_or_default();
+        if stamp != entry.stamp {
+            entry.branch = None;
+            entry.dirty = None;
+            entry.stamp = stamp;
+        }
+        entry
+    }
+}
+
+/// The size and modification time of `HEAD` and `index` in `repo`.
+fn stamp(repo: &Repo) -> Stamp {
+    ["HEAD", "index"].map(|name| {
+        let meta = fs::metadata(repo.git_dir.join(name)).ok()?;
+        Some((meta.len(), meta.modified().ok()?))
+    })
+}
+
+/// Walk up from `start` looking for a `.git` directory, or a `.git` file
+/// pointing elsewhere (worktrees and submodules).
+fn discover(start: &Path) -> Option<Repo> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(Repo {
+                git_dir: dot_git,
+                work_tree: dir.to_path_buf(),
+            });
+        }
+        if dot_git.is_file() {
+            let contents = fs::read_to_string(&dot_git).ok()?;
+            let target = contents.strip_prefix("gitdir:")?.trim();
+            return Some(Repo {
+                git_dir: dir.join(target),
+                work_tree: dir.to_path_buf(),
+            });
+        }
+    }
+    None
+}
+
+/// Read the current branch from `HEAD`.
+fn read_branch(repo: &Repo) -> Option<String> {
+    let head = fs::read_to_string(repo.git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    if let Some(reference) = head.strip_prefix("ref:") {
+        let reference = reference.trim();
+        let name = reference
+            .strip_prefix("refs/heads/")
+            .or_else(|| reference.strip_prefix("refs/"))
+            .unwrap_or(reference);
+        Some(name.to_string())
+    } else {
+        Some(head.chars().take(7).collect())
+    }
+}
+
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_GITLINK: u32 = 0o160000;
+const FLAG_EXTENDED: u16 = 0x4000;
+const FLAG_STAGE_MASK: u16 = 0x3000;
+const EXT_FLAG_SKIP_WORKTREE: u16 = 0x4000;
+const EXT_FLAG_INTENT_TO_ADD: u16 = 0x2000;
+
+/// Stat every tracked file and compare its size and mtime with the index.
+///
+/// Stops at the first difference. Unmerged entries, deleted files and
+/// intent-to-add entries count as dirty; untracked files are not considered.
+fn index_is_dirty(repo: &Repo) -> bool {
+    let Ok(data) = fs::read(repo.git_dir.join("index")) else {
+        return false;
+    };
+    let Some(entries) = IndexReader::new(&data) else {
+        return false;
+    };
+
+    for entry in entries {
+        let Some(entry) = entry else {
+            // A truncated or unreadable index: don't claim anything.
+            return false;
+        };
+        if entry.stage != 0 || entry.intent_to_add {
+            return true;
+        }
+        if entry.skip_worktree || entry.mode & MODE_TYPE_MASK == MODE_GITLINK {
+            continue;
+        }
+        let Ok(meta) = fs::symlink_metadata(repo.work_tree.join(&entry.path)) else {
+            return true;
+        };
+        if meta.len() as u32 != entry.size {
+            return true;
+        }
+        let Some(mtime) = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        else {
+            continue;
+        };
+        // Git may be built without nanosecond timestamps; those store 0.
+        if mtime.as_secs() as u32 != entry.mtime_secs
+            || (entry.mtime_nanos != 0 && mtime.subsec_nanos() != entry.mtime_nanos)
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// The fields of an index entry that the dirty check needs.
+struct IndexEntry {
+    path: String,
+    mtime_secs: u32,
+    mtime_nanos: u32,
+    mode: u32,
+    size: u32,
+    stage: u16,
+    skip_worktree: bool,
+    intent_to_add: bool,
+}
+
+/// Iterator over the entries of a version 2, 3 or 4 `.git/index` file.
+///
+/// Yields `None` once if the data turns out to be malformed.
+struct IndexReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    version: u32,
+    remaining: u32,
+    prev_path: Vec<u8>,
+}
+
+impl<'a> IndexReader<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        if data.len() < 12 || &data[..4] != b"DIRC" {
+            return None;
+        }
+        let version = be_u32(data, 4)?;
+        if !(2..=4).contains(&version) {
+            return None;
+        }
+        Some(Self {
+            data,
+            pos: 12,
+            version,
+            remaining: be_u32(data, 8)?,
+            prev_path: Vec::new(),
+        })
+    }
+
+    fn read_entry(&mut self) -> Option<IndexEntry> {
+        let start = self.pos;
+        let data = self.data;
+        let mtime_secs = be_u32(data, start + 8)?;
+        let mtime_nanos = be_u32(data, start + 12)?;
+        let mode = be_u32(data, start + 24)?;
+        let size = be_u32(data, start + 36)?;
+        let flags = be_u16(data, start + 60)?;
+
+        let mut pos = start + 62;
+        let mut ext_flags = 0;
+        if self.version >= 3 && flags & FLAG_EXTENDED != 0 {
+            ext_flags = be_u16(data, pos)?;
+            pos += 2;
+        }
+
+        let path = if self.version == 4 {
+            // Prefix-compressed: drop N bytes of the previous path, then
+            // append a NUL-terminated suffix. No padding follows.
+            let (strip, after) = read_offset(data, pos)?;
+            let keep = self.prev_path.len().checked_sub(strip)?;
+            let len = data.get(after..)?.iter().position(|&b| b == 0)?;
+            let mut path = self.prev_path[..keep].to_vec();
+            path.extend_from_slice(&data[after..after + len]);
+            self.pos = after + len + 1;
+            path
+        } else {
+            let len = data.get(pos..)?.iter().position(|&b| b == 0)?;
+            let path = data[pos..pos + len].to_vec();
+            // Entries are NUL-padded to a multiple of eight bytes.
+            let entry_len = pos - start + len;
+            self.pos = start + (entry_len + 8) / 8 * 8;
+            path
+        };
+
+        let entry = IndexEntry {
+            path: String::from_utf8_lossy(&path).into_owned(),
+            mtime_secs,
+            mtime_nanos,
+            mode,
+            size,
+            stage: (flags & FLAG_STAGE_MASK) >> 12,
+            skip_worktree: ext_flags & EXT_FLAG_SKIP_WORKTREE != 0,
+            intent_to_add: ext_flags & EXT_FLAG_INTENT_TO_ADD != 0,
+        };
+        self.prev_path = path;
+        Some(entry)
+    }
+}
+
+impl Iterator for IndexReader<'_> {
+    type Item = Option<IndexEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.read_entry();
+        self.remaining = if entry.is_some() {
+            self.remaining - 1
+        } else {
+            0
+        };
+        Some(entry)
+    }
+}
+
+fn be_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn be_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+/// Decode git's variable-length "offset" integer used by index v4.
+fn read_offset(data: &[u8], mut pos: usize) -> Option<(usize, usize)> {
+    let mut byte = *data.get(pos)?;
+    pos += 1;
+    let mut value = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        byte = *data.get(pos)?;
+        pos += 1;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+    }
+    Some((value, pos))
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a version 2 index describing the current state of `files`.
+    fn write_index(work_tree: &Path, files: &[&str]) {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"DIRC");
+        data.extend_from_slice(&2u32.to_be_bytes());
+        data.extend_from_slice(&(files.len() as u32).to_be_bytes());
+        for name in files {
+            let meta = fs::metadata(work_tree.join(name)).unwrap();
+            let mtime = meta.modified().unwrap().duration_since(UNIX_EPOCH).unwrap();
+            let start = data.len();
+            data.extend_from_slice(&[0; 8]); // ctime
+            data.extend_from_slice(&(mtime.as_secs() as u32).to_be_bytes());
+            data.extend_from_slice(&mtime.subsec_nanos().to_be_bytes());
+            data.extend_from_slice(&[0; 8]); // dev, ino
+            data.extend_from_slice(&0o100644u32.to_be_bytes());
+            data.extend_from_slice(&[0; 8]); // uid, gid
+            data.extend_from_slice(&(meta.len() as u32).to_be_bytes());
+            data.extend_from_slice(&[0; 20]); // object id
+            data.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            data.extend_from_slice(name.as_bytes());
+            let len = data.len() - start;
+            data.resize(start + (len + 8) / 8 * 8, 0);
+        }
+        fs::write(work_tree.join(".git").join("index"), data).unwrap();
+    }
+
+    /// Give the index a distinct modification time, `secs` after the epoch.
+    fn touch_index(work_tree: &Path, secs: u64) {
+        let index = fs::File::options()
+            .write(true)
+            .open(work_tree.join(".git").join("index"))
+            .unwrap();
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        index.set_modified(time).unwrap();
+    }
+
+    fn init_repo(head: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git").join("HEAD"), head).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_branch_from_head() {
+        let dir = init_repo("ref: refs/heads/feature/x\n");
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let mut cache = GitCache::default();
+        assert_eq!(
+            cache.branch(&dir.path().join("sub")).as_deref(),
+            Some("feature/x")
+        );
+
+        let detached = init_repo("0123456789abcdef0123456789abcdef01234567\n");
+        assert_eq!(cache.branch(detached.path()).as_deref(), Some("0123456"));
+    }
+
+    #[test]
+    fn test_outside_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = GitCache::default();
+        assert_eq!(discover(dir.path()).map(|r| r.work_tree), None);
+        assert!(!cache.is_dirty(dir.path()));
+    }
+
+    #[test]
+    fn test_dirty_tracks_modified_and_deleted_files() {
+        let dir = init_repo("ref: refs/heads/main\n");
+        fs::write(dir.path().join("a.txt"), "one").unwrap();
+        fs::write(dir.path().join("b.txt"), "two").unwrap();
+        write_index(dir.path(), &["a.txt", "b.txt"]);
+
+        let mut cache = GitCache::default();
+        assert!(!cache.is_dirty(dir.path()));
+
+        fs::write(dir.path().join("b.txt"), "changed").unwrap();
+        // Cached until the shell marks the entry stale.
+        assert!(!cache.is_dirty(dir.path()));
+        cache.mark_stale();
+        assert!(cache.is_dirty(dir.path()));
+
+        // Or until the index changes.
+        write_index(dir.path(), &["a.txt", "b.txt"]);
+        touch_index(dir.path(), 1);
+        assert!(!cache.is_dirty(dir.path()));
+        fs::remove_file(dir.path().join("a.txt")).unwrap();
+        cache.mark_stale();
+        assert!(cache.is_dirty(dir.path()));
+    }
+
+    #[test]
+    fn test_branch_follows_head() {
+        let dir = init_repo("ref: refs/heads/main\n");
+        let mut cache = GitCache::default();
+        assert_eq!(cache.branch(dir.path()).as_deref(), Some("main"));
+        fs::write(
+            dir.path().join(".git").join("HEAD"),
+            "ref: refs/heads/dev\n",
+        )
+        .unwrap();
+        assert_eq!(cache.branch(dir.path()).as_deref(), Some("dev"));
+
+        let plain = tempfile::tempdir().unwrap();
+        assert_eq!(cache.branch(plain.path()), None);
+        fs::create_dir(plain.path().join(".git")).unwrap();
+        fs::write(
+            plain.path().join(".git").join("HEAD"),
+            "ref: refs/heads/new\n",
+        )
+        .unwrap();
+        assert_eq!(cache.branch(plain.path()).as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn test_read_offset() {
+        assert_eq!(read_offset(&[0x05], 0), Some((5, 1)));
+        assert_eq!(read_offset(&[0x80, 0x00], 0), Some((128, 2)));
+        assert_eq!(read_offset(&[0x80], 0), None);
+    }
+}
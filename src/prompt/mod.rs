@@ -0,0 +1,403 @@
+mod git;
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::engine::{ShellState, execute_list};
+use crate::parser::{self, expand_vars};
+
+pub use git::GitCache;
+
+/// Used when `PS1` is unset.
+const DEFAULT_PS1: &str = "\\u@\\h:\\w\\$ ";
+
+/// A rendered prompt.
+///
+/// `plain` leaves out everything between `\[` and `\]`; the line editor
+/// measures it to place the cursor. `display` is what actually gets printed,
+/// and `command_display` is printed instead while vi is in command mode.
+#[derive(Debug, Default, PartialEq)]
+pub struct Prompt {
+    pub plain: String,
+    pub display: String,
+    pub command_display: String,
+}
+
+/// Accumulates the two halves of a [`Prompt`] while escapes are expanded.
+#[derive(Default)]
+struct PromptBuilder {
+    prompt: Prompt,
+    non_printing: bool,
+}
+
+impl PromptBuilder {
+    /// Append literal text. `$` is doubled so that the variable expansion
+    /// pass that follows leaves it alone.
+    fn push_literal(&mut self, s: &str) {
+        self.push_raw(&s.replace('$', "$$"));
+    }
+
+    /// Append template text that still undergoes variable expansion.
+    fn push_raw(&mut self, s: &str) {
+        self.prompt.display.push_str(s);
+        if !self.non_printing {
+            self.prompt.plain.push_str(s);
+        }
+    }
+}
+
+/// Render the primary prompt from `PS1`.
+pub fn render_ps1(state: &ShellState, git: &mut GitCache) -> Prompt {
+    let template = state
+        .get_var_string("PS1")
+        .unwrap_or_else(|| DEFAULT_PS1.to_string());
+    let mut prompt = render(&template, state, git);
+    if state.set_options.contains("vi") && template.contains("\\s{vimode}") {
+        prompt.command_display = render_mode(&template, state, git, true).display;
+    }
+    prompt
+}
+
+/// Render the continuation prompt from `PS2`.
+pub fn render_ps2(state: &ShellState, git: &mut GitCache) -> Prompt {
+    let template = state
+        .get_var_string("PS2")
+        .unwrap_or_else(|| "> ".to_string());
+    render(&template, state, git)
+}
+
+/// Expand bash-style prompt escapes in `template`, then expand variables.
+///
+/// Supported escapes:
+/// - `\u` user, `\h` / `\H` short / full host name
+/// - `\w` / `\W` working directory / its last component (`~` for `$HOME`)
+/// - `\$` `#` for root, `$` otherwise
+/// - `\t` `\T` `\A` `\@` `\d` `\D{fmt}` times and dates (strftime for `\D`)
+/// - `\j` number of jobs, `\?` exit status of the last command
+/// - `\s` shell name, `\v` / `\V` version
+/// - `\n` `\r` `\a` `\e` `\\` `\nnn` (octal) characters
+/// - `\[` … `\]` wrap non-printing sequences such as colour codes
+///
+/// Segments, which expand to nothing when there is nothing to show:
+/// - `\g{branch}` current git branch (short commit id when detached)
+/// - `\g{dirty}` `*` when tracked files differ from the git index
+/// - `\s{status}` exit status of the last command, if non-zero
+/// - `\s{duration}` how long the last command took
+/// - `\s{jobs}` number of jobs, if any
+/// - `\s{vimode}` `(ins)` or `(cmd)` for vi's insert and command modes
+pub fn render(template: &str, state: &ShellState, git: &mut GitCache) -> Prompt {
+    render_mode(template, state, git, false)
+}
+
+/// [`render`], showing `\s{vimode}` for command mode if `vi_command`.
+fn render_mode(template: &str, state: &ShellState, git: &mut GitCache, vi_command: bool) -> Prompt {
+    let now = chrono::Local::now();
+    let mut out = PromptBuilder::default();
+    let mut chars = template.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push_raw(ch.encode_utf8(&mut [0; 4]));
+            continue;
+        }
+
+        let Some(esc) = chars.next() else {
+            out.push_literal("\\");
+            break;
+        };
+
+        match esc {
+            'u' => out.push_literal(&state.get_var_string("USER").unwrap_or_default()),
+            'h' => {
+                let host = host_name();
+                out.push_literal(host.split('.').next().unwrap_or_default());
+            }
+            'H' => out.push_literal(&host_name()),
+            'w' => out.push_literal(&tilde_cwd(state)),
+            'W' => {
+                let cwd = tilde_cwd(state);
+                let base = if cwd == "~" || cwd == "/" {
+                    cwd.as_str()
+                } else {
+                    cwd.rsplit(['/', '\\']).next().unwrap_or_default()
+                };
+                out.push_literal(base);
+            }
+            '$' => out.push_literal(if is_root() { "#" } else { "$" }),
+            't' => out.push_literal(&now.format("%H:%M:%S").to_string()),
+            'T' => out.push_literal(&now.format("%I:%M:%S").to_string()),
+            'A' => out.push_literal(&now.format("%H:%M").to_string()),
+            '@' => out.push_literal(&now.format("%I:%M %p").to_string()),
+            'd' => out.push_literal(&now.format("%a %b %d").to_string()),
+            'D' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let fmt: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let fmt = if fmt.is_empty() { "%X" } else { fmt.as_str() };
+                // An invalid format makes `write!` fail rather than panic.
+                let mut date = String::new();
+                let _ = write!(date, "{}", now.format(fmt));
+                out.push_literal(&date);
+            }
+            'j' => out.push_literal(&state.jobs.len().to_string()),
+            '?' => out.push_literal(&state.last_exit_code.to_string()),
+            'g' | 's' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                match segment(esc, &name, state, git, vi_command) {
+                    Some(text) => out.push_literal(&text),
+                    None => out.push_literal(&format!("\\{}{{{}}}", esc, name)),
+                }
+            }
+            's' => out.push_literal("cerf"),
+            'v' | 'V' => out.push_literal(env!("CARGO_PKG_VERSION")),
+            'n' => out.push_literal("\n"),
+            'r' => out.push_literal("\r"),
+            'a' => out.push_literal("\x07"),
+            'e' => out.push_literal("\x1b"),
+            '\\' => out.push_literal("\\"),
+            '[' => out.non_printing = true,
+            ']' => out.non_printing = false,
+            '0'..='7' => {
+                let mut code = esc.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            code = code * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                if let Some(c) = char::from_u32(code) {
+                    out.push_literal(c.encode_utf8(&mut [0; 4]));
+                }
+            }
+            other => {
+                out.push_literal("\\");
+                out.push_raw(other.encode_utf8(&mut [0; 4]));
+            }
+        }
+    }
+
+    let Prompt { plain, display, .. } = out.prompt;
+    let display = expand_vars(&display, state);
+    Prompt {
+        plain: expand_vars(&plain, state),
+        command_display: display.clone(),
+        display,
+    }
+}
+
+/// Expand a `\g{…}` or `\s{…}` segment. `None` for unknown names.
+fn segment(
+    kind: char,
+    name: &str,
+    state: &ShellState,
+    git: &mut GitCache,
+    vi_command: bool,
+) -> Option<String> {
+    let text = match (kind, name) {
+        ('g', "branch") => std::env::current_dir()
+            .ok()
+            .and_then(|cwd| git.branch(&cwd))
+            .unwrap_or_default(),
+        ('g', "dirty") => match std::env::current_dir() {
+            Ok(cwd) if git.is_dirty(&cwd) => "*".to_string(),
+            _ => String::new(),
+        },
+        ('s', "status") if state.last_exit_code != 0 => state.last_exit_code.to_string(),
+        ('s', "status") => String::new(),
+        ('s', "duration") => state
+            .last_command_duration
+            .map(format_duration)
+            .unwrap_or_default(),
+        ('s', "jobs") if !state.jobs.is_empty() => state.jobs.len().to_string(),
+        ('s', "jobs") => String::new(),
+        // Both are as wide, so the cursor stays put when the mode changes.
+        ('s', "vimode") if state.set_options.contains("vi") => {
+            if vi_command { "(cmd)" } else { "(ins)" }.to_string()
+        }
+        ('s', "vimode") => String::new(),
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// Short human-readable duration: `420ms`, `3.2s`, `1m05s`, `2h03m`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs == 0 {
+        format!("{}ms", d.as_millis())
+    } else if secs < 60 {
+        format!("{:.1}s", d.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+/// Run `PROMPT_COMMAND` and the `prompt` function (if defined) before the
+/// primary prompt is drawn. The last command's exit status is preserved.
+pub fn run_prompt_command(state: &mut ShellState) {
+    let saved_code = state.last_exit_code;
+
+    if let Some(command) = state.get_var_string("PROMPT_COMMAND")
+        && !command.trim().is_empty()
+        && let Some(entries) = parser::parse_pipeline(&command, state)
+    {
+        let _ = execute_list(entries, state);
+    }
+
+    if let Some(body) = state.functions.get("prompt").cloned() {
+        let _ = execute_list(body, state);
+    }
+
+    state.last_exit_code = saved_code;
+}
+
+/// The working directory, with `$HOME` abbreviated to `~`.
+fn tilde_cwd(state: &ShellState) -> String {
+    let cwd = state
+        .get_var_string("PWD")
+        .filter(|p| !p.is_empty())
+        .or_else(|| {
+            std::env::current_dir()
+                .ok()
+                .map(|p| p.to_string_lossy().to_string())
+        })
+        .unwrap_or_default();
+
+    match state.get_var_string("HOME").filter(|h| !h.is_empty()) {
+        Some(home) if cwd == home => "~".to_string(),
+        Some(home)
+            if cwd.starts_with(&home) && cwd[home.len()..].starts_with(std::path::is_separator) =>
+        {
+            format!("~{}", &cwd[home.len()..])
+        }
+        _ => cwd,
+    }
+}
+
+fn host_name() -> String {
+    sysinfo::System::host_name().unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    unsafe { nix::libc::geteuid() == 0 }
+}
+
+#[cfg(windows)]
+fn is_root() -> bool {
+    false
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::state::Variable;
+
+    fn render_test(template: &str, state: &ShellState) -> Prompt {
+        render(template, state, &mut GitCache::default())
+    }
+
+    fn state_with(vars: &[(&str, &str)]) -> ShellState {
+        let mut state = ShellState::new();
+        for (name, value) in vars {
+            state.set_var(name, Variable::new_string(value.to_string())).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn test_user_and_cwd_escapes() {
+        let state = state_with(&[
+            ("USER", "ada"),
+            ("HOME", "/home/ada"),
+            ("PWD", "/home/ada/src/cerf"),
+        ]);
+        assert_eq!(render_test("\\u:\\w", &state).plain, "ada:~/src/cerf");
+        assert_eq!(render_test("\\W", &state).plain, "cerf");
+
+        let state = state_with(&[("HOME", "/home/ada"), ("PWD", "/home/ada")]);
+        assert_eq!(render_test("\\w \\W", &state).plain, "~ ~");
+
+        let state = state_with(&[("HOME", "/home/ada"), ("PWD", "/home/adam")]);
+        assert_eq!(render_test("\\w", &state).plain, "/home/adam");
+    }
+
+    #[test]
+    fn test_non_printing_sequences() {
+        let state = state_with(&[]);
+        let prompt = render_test("\\[\\e[32m\\]>\\[\\033[0m\\] ", &state);
+        assert_eq!(prompt.plain, "> ");
+        assert_eq!(prompt.display, "\x1b[32m>\x1b[0m ");
+    }
+
+    #[test]
+    fn test_status_and_variables() {
+        let mut state = state_with(&[("CERF_PROMPT_TEST", "dev")]);
+        state.last_exit_code = 3;
+        assert_eq!(
+            render_test("[$CERF_PROMPT_TEST] \\? \\\\", &state).plain,
+            "[dev] 3 \\"
+        );
+    }
+
+    #[test]
+    fn test_literal_dollar_is_not_expanded() {
+        let state = state_with(&[("HOME", "/h"), ("PWD", "/tmp/$HOME")]);
+        assert_eq!(render_test("\\w", &state).plain, "/tmp/$HOME");
+    }
+
+    #[test]
+    fn test_unknown_escape_kept() {
+        let state = state_with(&[]);
+        assert_eq!(render_test("\\q\\", &state).plain, "\\q\\");
+        assert_eq!(render_test("\\g{nope}", &state).plain, "\\g{nope}");
+    }
+
+    #[test]
+    fn test_bad_date_format() {
+        let state = state_with(&[]);
+        assert!(render_test("\\D{%Q}>", &state).plain.ends_with('>'));
+        assert_eq!(render_test("\\D{%%}", &state).plain, "%");
+    }
+
+    #[test]
+    fn test_status_segments() {
+        let mut state = state_with(&[]);
+        assert_eq!(
+            render_test("[\\s{status}\\s{jobs}\\s{duration}] \\s", &state).plain,
+            "[] cerf"
+        );
+
+        state.last_exit_code = 127;
+        state.last_command_duration = Some(Duration::from_millis(65_300));
+        assert_eq!(
+            render_test("\\s{status} \\s{duration}", &state).plain,
+            "127 1m05s"
+        );
+    }
+
+    #[test]
+    fn test_vimode_segment() {
+        let mut state = state_with(&[("PS1", "\\s{vimode}> ")]);
+        assert_eq!(render_ps1(&state, &mut GitCache::default()).plain, "> ");
+
+        state.set_options.insert("vi".to_string());
+        let prompt = render_ps1(&state, &mut GitCache::default());
+        assert_eq!(prompt.display, "(ins)> ");
+        assert_eq!(prompt.command_display, "(cmd)> ");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(420)), "420ms");
+        assert_eq!(format_duration(Duration::from_millis(3250)), "3.2s");
+        assert_eq!(format_duration(Duration::from_secs(7380)), "2h03m");
+    }
+}
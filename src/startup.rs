@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+
+use crate::builtins;
+use crate::cli::Cli;
+use crate::engine::{ShellState, expand_home};
+use crate::parser::expand_vars;
+
+/// The system-wide startup file, read before the user's own configuration.
+#[cfg(not(windows))]
+fn system_rc_path() -> Option<PathBuf> {
+    Some(PathBuf::from("/etc/cerf/cerfrc"))
+}
+
+/// The system-wide startup file, read before the user's own configuration.
+#[cfg(windows)]
+fn system_rc_path() -> Option<PathBuf> {
+    std::env::var_os("PROGRAMDATA").map(|p| PathBuf::from(p).join("cerf").join("cerfrc"))
+}
+
+/// Return the first candidate that exists on disk.
+fn first_existing(candidates: &[Option<PathBuf>]) -> Option<PathBuf> {
+    candidates.iter().flatten().find(|p| p.exists()).cloned()
+}
+
+/// `$XDG_CONFIG_HOME/cerf/config.cf`, falling back to `~/.cerfrc`.
+fn user_rc_path(config_dir: Option<&Path>, home: Option<&Path>) -> Option<PathBuf> {
+    first_existing(&[
+        config_dir.map(|d| d.join("config.cf")),
+        home.map(|h| h.join(".cerfrc")),
+    ])
+}
+
+/// `$XDG_CONFIG_HOME/cerf/profile.cf`, falling back to `~/.cerf_profile`.
+fn login_profile_path(config_dir: Option<&Path>, home: Option<&Path>) -> Option<PathBuf> {
+    first_existing(&[
+        config_dir.map(|d| d.join("profile.cf")),
+        home.map(|h| h.join(".cerf_profile")),
+    ])
+}
+
+/// Source the startup files for this invocation, in order:
+///
+/// 1. Interactive shells: the system-wide `cerfrc`, then the user's
+///    `config.cf` (or `~/.cerfrc`). `--rcfile` replaces both; `--norc` skips both.
+/// 2. Login shells: `profile.cf` (or `~/.cerf_profile`), unless `--noprofile`.
+/// 3. Non-interactive shells: the file named by `$CERF_ENV`, if set.
+///
+/// `dir.mark` bookmarks are loaded first, so startup files can use `~name`.
+pub fn source_startup_files(state: &mut ShellState, cli: &Cli) {
+    state.load_bookmarks();
+    let config_dir = state.config_dir();
+    let home = dirs::home_dir();
+
+    if cli.is_interactive() && !cli.norc {
+        if let Some(rcfile) = &cli.rcfile {
+            builtins::source::run(std::slice::from_ref(rcfile), state);
+        } else {
+            if let Some(path) = first_existing(&[system_rc_path()]) {
+                source_file(&path, state);
+            }
+            if let Some(path) = user_rc_path(config_dir.as_deref(), home.as_deref()) {
+                source_file(&path, state);
+            }
+        }
+    }
+
+    if cli.login
+        && !cli.noprofile
+        && let Some(path) = login_profile_path(config_dir.as_deref(), home.as_deref())
+    {
+        source_file(&path, state);
+    }
+
+    if !cli.is_interactive()
+        && let Some(env_file) = state.get_var_string("CERF_ENV")
+        && !env_file.is_empty()
+    {
+        let path = expand_home(&expand_vars(&env_file, state));
+        if path.exists() {
+            source_file(&path, state);
+        }
+    }
+}
+
+/// Source `$XDG_CONFIG_HOME/cerf/logout.cf` when a login shell exits.
+pub fn source_logout_file(state: &mut ShellState, cli: &Cli) {
+    if !cli.login {
+        return;
+    }
+    if let Some(path) = first_existing(&[state.config_dir().map(|d| d.join("logout.cf"))]) {
+        source_file(&path, state);
+    }
+}
+
+fn source_file(path: &Path, state: &mut ShellState) {
+    let path_str = path.to_string_lossy().to_string();
+    builtins::source::run(&[path_str], state);
+}
+
+// ── Tests ──────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_rc_prefers_xdg_config() {
+        let config = tempfile::tempdir().unwrap();
+        let home = tempfile::tempdir().unwrap();
+        std::fs::write(config.path().join("config.cf"), "").unwrap();
+        std::fs::write(home.path().join(".cerfrc"), "").unwrap();
+
+        assert_eq!(
+            user_rc_path(Some(config.path()), Some(home.path())),
+            Some(config.path().join("config.cf"))
+        );
+    }
+
+    #[test]
+    fn test_user_rc_falls_back_to_dotfile() {
+        let config = tempfile::tempdir().unwrap();
+        let home = tempfile::tempdir().unwrap();
+        std::fs::write(home.path().join(".cerfrc"), "").unwrap();
+
+        assert_eq!(
+            user_rc_path(Some(config.path()), Some(home.path())),
+            Some(home.path().join(".cerfrc"))
+        );
+        assert_eq!(login_profile_path(Some(config.path()), Some(home.path())), None);
+    }
+}